@@ -0,0 +1,88 @@
+use anyhow::{bail, Result};
+use smart_leds::RGB8;
+
+const NAMED_COLORS: [(&str, RGB8); 10] = [
+    ("black", RGB8 { r: 0, g: 0, b: 0 }),
+    (
+        "white",
+        RGB8 {
+            r: 255,
+            g: 255,
+            b: 255,
+        },
+    ),
+    ("red", RGB8 { r: 255, g: 0, b: 0 }),
+    ("green", RGB8 { r: 0, g: 255, b: 0 }),
+    ("blue", RGB8 { r: 0, g: 0, b: 255 }),
+    (
+        "yellow",
+        RGB8 {
+            r: 255,
+            g: 255,
+            b: 0,
+        },
+    ),
+    (
+        "cyan",
+        RGB8 {
+            r: 0,
+            g: 255,
+            b: 255,
+        },
+    ),
+    (
+        "magenta",
+        RGB8 {
+            r: 255,
+            g: 0,
+            b: 255,
+        },
+    ),
+    (
+        "orange",
+        RGB8 {
+            r: 255,
+            g: 128,
+            b: 0,
+        },
+    ),
+    (
+        "purple",
+        RGB8 {
+            r: 128,
+            g: 0,
+            b: 255,
+        },
+    ),
+];
+
+/// Parses a color name, a `#rrggbb` hex color or a `r,g,b` triple.
+pub fn parse_color(color: &str) -> Result<RGB8> {
+    let lowercase = color.to_ascii_lowercase();
+
+    if let Some((_, rgb)) = NAMED_COLORS.iter().find(|(name, _)| *name == lowercase) {
+        return Ok(*rgb);
+    }
+
+    if let [r, g, b] = lowercase.split(',').collect::<Vec<_>>()[..] {
+        if let (Ok(r), Ok(g), Ok(b)) = (r.trim().parse(), g.trim().parse(), b.trim().parse()) {
+            return Ok(RGB8 { r, g, b });
+        }
+    }
+
+    let hex = lowercase.trim_start_matches('#');
+    if hex.len() == 6 {
+        if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+            return Ok(RGB8 {
+                r: (rgb >> 16) as u8,
+                g: (rgb >> 8) as u8,
+                b: rgb as u8,
+            });
+        }
+    }
+
+    bail!(
+        "Invalid color {:?}. Use a name like \"red\", a hex color like \"#ff8000\" or \"255,128,0\"",
+        color
+    )
+}
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::warn;
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+
+use osc_receiver::mdns::SERVICE_DOMAIN;
+
+use crate::ping;
+
+/// A receiver that was found by mDNS or replied to the broadcast ping
+#[derive(Debug, Default)]
+struct Found {
+    /// The mDNS service instance name
+    name: Option<String>,
+    strips: Option<String>,
+    lengths: Option<String>,
+    led_type: Option<String>,
+    version: Option<String>,
+}
+
+/// Browses for `_osc._udp` services with mDNS and broadcasts a ping on `port` for receivers that
+/// don't advertise themselves, then prints every receiver found.
+pub fn discover(port: u16, timeout: Duration) -> Result<()> {
+    println!("Searching for receivers...");
+
+    let browse = ServiceDaemon::new()
+        .and_then(|daemon| Ok((daemon.browse(SERVICE_DOMAIN)?, daemon)))
+        .map_err(|err| anyhow!("{:?}", err));
+
+    let browse = match browse {
+        Ok(browse) => Some(browse),
+        Err(err) => {
+            warn!("Unable to browse with mDNS, only pinging: {:?}", err);
+            None
+        }
+    };
+
+    // The mDNS daemon browses in the background while waiting for pongs
+    let mut receivers = ping::broadcast(port, timeout)?
+        .into_iter()
+        .map(|addr| (addr, Found::default()))
+        .collect::<BTreeMap<_, _>>();
+
+    if let Some((events, daemon)) = browse {
+        while let Ok(event) = events.try_recv() {
+            let service = match event {
+                ServiceEvent::ServiceResolved(service) => service,
+                _ => continue,
+            };
+
+            let name = service
+                .get_fullname()
+                .strip_suffix(SERVICE_DOMAIN)
+                .map(|name| name.trim_end_matches('.'))
+                .unwrap_or_else(|| service.get_fullname());
+            let txt = |key: &str| service.get_property_val_str(key).map(str::to_string);
+
+            for ip in service.get_addresses_v4() {
+                receivers.insert(
+                    SocketAddr::new(IpAddr::V4(ip), service.get_port()),
+                    Found {
+                        name: Some(name.to_string()),
+                        strips: txt("strips"),
+                        lengths: txt("lengths"),
+                        led_type: txt("led_type"),
+                        version: txt("version"),
+                    },
+                );
+            }
+        }
+
+        let _ = daemon.shutdown();
+    }
+
+    for (addr, found) in receivers.iter() {
+        match &found.name {
+            Some(name) => println!(
+                "  {:<21}  {}  {} strips ({} LEDs), {}, v{}",
+                addr,
+                name,
+                found.strips.as_deref().unwrap_or("?"),
+                found.lengths.as_deref().unwrap_or("?"),
+                found.led_type.as_deref().unwrap_or("unknown LEDs"),
+                found.version.as_deref().unwrap_or("?"),
+            ),
+            None => println!("  {:<21}  (replied to ping)", addr),
+        }
+    }
+
+    if receivers.is_empty() {
+        println!("No receivers found");
+    } else {
+        println!("Found {} receivers", receivers.len());
+    }
+
+    Ok(())
+}
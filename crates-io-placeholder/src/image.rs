@@ -0,0 +1,111 @@
+use std::fs::File;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use smart_leds::RGB8;
+
+use osc_sender::Frame;
+
+use crate::receiver::Receiver;
+
+/// Sends each row of a PNG as a frame, top to bottom. Each row is stretched or squashed to fit
+/// every LED of every strip.
+pub fn run(
+    receiver: &Receiver,
+    path: &Path,
+    strip_lengths: &[usize],
+    fps: u32,
+    looping: bool,
+) -> Result<()> {
+    let rows = read_rows(path)?;
+    let frames = rows
+        .iter()
+        .map(|row| row_to_frame(row, strip_lengths))
+        .collect::<Vec<_>>();
+
+    let frame_interval = Duration::from_secs_f64(1.0 / fps as f64);
+
+    println!(
+        "Sending {} rows of {:?} to {} at {} fps",
+        frames.len(),
+        path,
+        receiver.addr,
+        fps
+    );
+
+    loop {
+        let started_at = Instant::now();
+
+        for (i, frame) in frames.iter().enumerate() {
+            receiver.send_frame(frame)?;
+
+            let next_frame_at = started_at + frame_interval * (i + 1) as u32;
+            if let Some(delay) = next_frame_at.checked_duration_since(Instant::now()) {
+                thread::sleep(delay);
+            }
+        }
+
+        if !looping {
+            return Ok(());
+        }
+    }
+}
+
+fn read_rows(path: &Path) -> Result<Vec<Vec<RGB8>>> {
+    let file = File::open(path).with_context(|| format!("Unable to open image {:?}", path))?;
+
+    let mut decoder = png::Decoder::new(file);
+    // Expand palettes and low bit depths to 8 bit samples
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder
+        .read_info()
+        .with_context(|| format!("Invalid PNG {:?}", path))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .with_context(|| format!("Invalid PNG {:?}", path))?;
+
+    let channels = match info.color_type {
+        png::ColorType::Grayscale => 1,
+        png::ColorType::GrayscaleAlpha => 2,
+        png::ColorType::Rgb => 3,
+        png::ColorType::Rgba => 4,
+        color_type => bail!("Unsupported PNG color type {:?}", color_type),
+    };
+
+    let rows = buf[..info.buffer_size()]
+        .chunks_exact(info.line_size)
+        .map(|line| {
+            line.chunks_exact(channels)
+                .map(|pixel| match channels {
+                    1 | 2 => RGB8 {
+                        r: pixel[0],
+                        g: pixel[0],
+                        b: pixel[0],
+                    },
+                    _ => RGB8 {
+                        r: pixel[0],
+                        g: pixel[1],
+                        b: pixel[2],
+                    },
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Samples the row at one pixel per LED, treating the strips as one long strip.
+fn row_to_frame(row: &[RGB8], strip_lengths: &[usize]) -> Frame {
+    let led_count: usize = strip_lengths.iter().sum();
+    let mut frame = Frame::new(strip_lengths);
+    for (i, led) in frame.leds_mut().enumerate() {
+        *led = row[i * row.len() / led_count];
+    }
+
+    frame
+}
@@ -0,0 +1,103 @@
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rosc::{OscMessage, OscPacket, OscType};
+
+use osc_receiver::query::{INFO_ADDR, STATUS_ADDR};
+
+use crate::receiver::bind_socket;
+
+/// Asks a receiver for its strips, version and status and prints the replies.
+pub fn info(addr: SocketAddr, timeout: Duration) -> Result<()> {
+    let socket = bind_socket(&addr)?;
+
+    let info = query(&socket, addr, INFO_ADDR, timeout)?;
+    let status = query(&socket, addr, STATUS_ADDR, timeout)?;
+
+    println!("Receiver {}", addr);
+
+    match &info[..] {
+        [OscType::String(version), OscType::Int(_), strips @ ..] => {
+            println!("  Version:         {}", version);
+
+            for (i, strip) in strips.chunks(2).enumerate() {
+                if let [OscType::Int(length), OscType::String(led_type)] = strip {
+                    println!(
+                        "  {:<17}{} {} LEDs",
+                        format!("Strip {}:", i),
+                        length,
+                        led_type
+                    );
+                }
+            }
+        }
+        _ => bail!("Invalid {} reply: {:?}", INFO_ADDR, info),
+    }
+
+    match &status[..] {
+        [OscType::Float(uptime), OscType::Float(fps), OscType::Int(frames), OscType::Int(invalid_packets), OscType::Int(write_errors), ..] =>
+        {
+            let uptime = *uptime as u64;
+            println!(
+                "  Uptime:          {}h {}m {}s",
+                uptime / 3600,
+                uptime / 60 % 60,
+                uptime % 60
+            );
+            println!("  Frame rate:      {:.1} fps", fps);
+            println!("  Frames:          {}", frames);
+            println!("  Invalid packets: {}", invalid_packets);
+            println!("  Write errors:    {}", write_errors);
+        }
+        _ => bail!("Invalid {} reply: {:?}", STATUS_ADDR, status),
+    }
+
+    Ok(())
+}
+
+/// Sends a query and waits for the reply to the same address, returning its arguments.
+fn query(
+    socket: &UdpSocket,
+    to: SocketAddr,
+    query_addr: &str,
+    timeout: Duration,
+) -> Result<Vec<OscType>> {
+    let query = OscPacket::Message(OscMessage {
+        addr: query_addr.to_string(),
+        args: vec![],
+    });
+    let bytes = rosc::encoder::encode(&query).map_err(|err| anyhow!("{:?}", err))?;
+
+    socket
+        .send_to(&bytes, to)
+        .with_context(|| format!("Unable to send {} to {}", query_addr, to))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut buf = [0; 1024];
+
+    loop {
+        let remaining = match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => bail!("{} did not reply to {}", to, query_addr),
+        };
+        socket.set_read_timeout(Some(remaining))?;
+
+        let size = match socket.recv_from(&mut buf) {
+            Ok((size, _)) => size,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(err) => return Err(err).context("Unable to receive reply"),
+        };
+
+        if let Ok((_, OscPacket::Message(OscMessage { addr, args }))) =
+            rosc::decoder::decode_udp(&buf[..size])
+        {
+            if addr == query_addr {
+                return Ok(args);
+            }
+        }
+    }
+}
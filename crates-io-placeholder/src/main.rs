@@ -1,3 +1,283 @@
-fn main() {
-    println!("Hello, world!");
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{Parser, Subcommand};
+use osc_sender::{Encoding, Frame, PatternKind, PatternParams, RenderLoop};
+use smart_leds::RGB8;
+
+use osc_receiver::led_strip::STRIP_LENGTH;
+
+mod color;
+mod discover;
+mod image;
+mod info;
+mod ping;
+mod receiver;
+mod test_pattern;
+
+use receiver::{Receiver, DEFAULT_PORT};
+use test_pattern::TestPattern;
+
+/// Controls raves-on-rust LED receivers over OSC.
+///
+/// Receivers are addressed as `<host>[:<port>]`, the port defaults to 8001.
+#[derive(Debug, Parser)]
+#[command(name = "raves-on-rust", version)]
+struct Cli {
+    /// Send frames as OSC bundles so that frames split across several messages are displayed at
+    /// once
+    #[arg(long, global = true)]
+    bundles: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Set every LED to one color
+    Color {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        /// A color name like "red", a hex color like "#ff8000" or "255,128,0"
+        #[arg(value_parser = color::parse_color)]
+        color: RGB8,
+
+        /// Number of LEDs on each of the receiver's strips, eg. `450,144` for two strips
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+    },
+    /// Set a single DMX channel. Every 3 channels control the red, green and blue of one LED
+    Dmx {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        universe: usize,
+
+        /// The channel within the universe (0 - 511)
+        #[arg(value_parser = clap::value_parser!(u16).range(0..512))]
+        channel: u16,
+
+        /// The channel's value between 0 and 1
+        value: f32,
+    },
+    /// Send a test pattern until stopped with Ctrl+C
+    TestPattern {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        #[arg(value_enum, default_value_t = TestPattern::Rgb)]
+        pattern: TestPattern,
+
+        /// Number of LEDs on each of the receiver's strips, eg. `450,144` for two strips
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+
+        /// Frames sent per second
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=1000))]
+        fps: u32,
+    },
+    /// Render an animated pattern and send it to one or more receivers until stopped with Ctrl+C
+    Pattern {
+        /// One of rainbow, chase, twinkle, fire, plasma, gradient or noise
+        pattern: PatternKind,
+
+        #[arg(required = true, value_parser = receiver::parse_address)]
+        receivers: Vec<SocketAddr>,
+
+        /// Number of LEDs on each of the receivers' strips, eg. `450,144` for two strips
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+
+        /// Frames sent per second
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=1000))]
+        fps: u32,
+
+        /// Multiplies how fast the pattern moves
+        #[arg(long, default_value_t = PatternParams::default().speed)]
+        speed: f32,
+
+        /// The size of the pattern in LEDs, eg. the length of one rainbow
+        #[arg(long, default_value_t = PatternParams::default().scale)]
+        scale: f32,
+
+        /// The main color of the chase, twinkle and gradient patterns
+        #[arg(long, value_parser = color::parse_color)]
+        color: Option<RGB8>,
+
+        /// The background of the chase pattern and the end of the gradient
+        #[arg(long, value_parser = color::parse_color)]
+        background: Option<RGB8>,
+
+        /// How busy the pattern is from 0 to 1, eg. how many LEDs twinkle
+        #[arg(long, default_value_t = PatternParams::default().density)]
+        density: f32,
+
+        /// Scales every color, from 0 to 1
+        #[arg(long, default_value_t = 1.0)]
+        brightness: f32,
+
+        /// Stop after this many seconds
+        #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+        duration: Option<Duration>,
+    },
+    /// Send each row of a PNG image as a frame, top to bottom
+    Image {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        image: PathBuf,
+
+        /// Number of LEDs on each of the receiver's strips, eg. `450,144` for two strips
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+
+        /// Rows sent per second
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..=1000))]
+        fps: u32,
+
+        /// Start again from the top of the image once the last row is sent
+        #[arg(long = "loop")]
+        looping: bool,
+    },
+    /// Check that a receiver is reachable and measure the round trip time
+    Ping {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        /// Number of pings to send
+        #[arg(short, long, default_value_t = 4)]
+        count: u32,
+
+        /// Seconds to wait for each reply
+        #[arg(long, value_name = "SECONDS", default_value = "1", value_parser = parse_seconds)]
+        timeout: Duration,
+    },
+    /// Print a receiver's strips, version, uptime, frame rate and error counts
+    Info {
+        #[arg(value_parser = receiver::parse_address)]
+        receiver: SocketAddr,
+
+        /// Seconds to wait for each reply
+        #[arg(long, value_name = "SECONDS", default_value = "1", value_parser = parse_seconds)]
+        timeout: Duration,
+    },
+    /// Find the receivers on the local network with mDNS and a broadcast ping
+    Discover {
+        /// UDP port to broadcast the ping to, for receivers that aren't advertised with mDNS
+        #[arg(short, long, default_value_t = DEFAULT_PORT)]
+        port: u16,
+
+        /// Seconds to wait for replies
+        #[arg(long, value_name = "SECONDS", default_value = "2", value_parser = parse_seconds)]
+        timeout: Duration,
+    },
+}
+
+pub fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let cli = Cli::parse();
+    let encoding = if cli.bundles {
+        Encoding::Bundles
+    } else {
+        Encoding::Messages
+    };
+
+    match cli.command {
+        Command::Color {
+            receiver,
+            color,
+            strip_lengths,
+        } => Receiver::new(receiver, encoding)?.send_frame(&Frame::solid(&strip_lengths, color)),
+        Command::Dmx {
+            receiver,
+            universe,
+            channel,
+            value,
+        } => Receiver::new(receiver, encoding)?.send_dmx(universe, channel as usize, value),
+        Command::TestPattern {
+            receiver,
+            pattern,
+            strip_lengths,
+            fps,
+        } => test_pattern::run(
+            &Receiver::new(receiver, encoding)?,
+            pattern,
+            &strip_lengths,
+            fps,
+        ),
+        Command::Pattern {
+            pattern,
+            receivers,
+            strip_lengths,
+            fps,
+            speed,
+            scale,
+            color,
+            background,
+            density,
+            brightness,
+            duration,
+        } => {
+            let defaults = PatternParams::default();
+            let params = PatternParams {
+                speed,
+                scale,
+                color: color.unwrap_or(defaults.color),
+                background: background.unwrap_or(defaults.background),
+                density: density.clamp(0.0, 1.0),
+                ..defaults
+            };
+
+            let mut render_loop = RenderLoop::new(receiver::sender(&receivers[0], encoding)?, fps);
+            render_loop.brightness = brightness.clamp(0.0, 1.0);
+            for addr in receivers.iter() {
+                render_loop.add_target(*addr, &strip_lengths, pattern.build(&params));
+            }
+
+            println!(
+                "Sending the {} pattern to {} receivers at {} fps",
+                pattern,
+                receivers.len(),
+                fps
+            );
+            render_loop.run(duration)
+        }
+        Command::Image {
+            receiver,
+            image,
+            strip_lengths,
+            fps,
+            looping,
+        } => image::run(
+            &Receiver::new(receiver, encoding)?,
+            &image,
+            &strip_lengths,
+            fps,
+            looping,
+        ),
+        Command::Ping {
+            receiver,
+            count,
+            timeout,
+        } => ping::ping(receiver, count, timeout),
+        Command::Info { receiver, timeout } => info::info(receiver, timeout),
+        Command::Discover { port, timeout } => discover::discover(port, timeout),
+    }
+}
+
+/// Parses a number of seconds, rejecting negative and NaN values and durations too long to wait.
+fn parse_seconds(seconds: &str) -> Result<Duration> {
+    let parsed: f64 = seconds
+        .parse()
+        .with_context(|| format!("Invalid number of seconds {:?}", seconds))?;
+
+    Duration::try_from_secs_f64(parsed)
+        .ok()
+        .filter(|duration| Instant::now().checked_add(*duration).is_some())
+        .ok_or_else(|| anyhow!("Expected 0 or more seconds, got {:?}", seconds))
 }
@@ -0,0 +1,120 @@
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rosc::{OscMessage, OscPacket, OscType};
+
+use osc_receiver::query::{PING_ADDR, PONG_ADDR};
+
+use crate::receiver::bind_socket;
+
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends `count` pings to a receiver and prints the round trip time of each reply.
+pub fn ping(addr: SocketAddr, count: u32, timeout: Duration) -> Result<()> {
+    let socket = bind_socket(&addr)?;
+    let mut replies = 0;
+
+    println!("Pinging {}", addr);
+
+    for sequence in 0..count {
+        if sequence > 0 {
+            thread::sleep(PING_INTERVAL);
+        }
+
+        let sent_at = Instant::now();
+        send_ping(&socket, addr, sequence as i32)?;
+
+        match wait_for_pong(&socket, sequence as i32, sent_at + timeout)? {
+            Some(from) => {
+                replies += 1;
+                println!(
+                    "Reply from {}: seq={} time={:.1}ms",
+                    from,
+                    sequence,
+                    sent_at.elapsed().as_secs_f64() * 1000.0
+                );
+            }
+            None => println!("Request timed out: seq={}", sequence),
+        }
+    }
+
+    println!("{} pings sent, {} replies received", count, replies);
+
+    if replies == 0 {
+        bail!("{} did not reply", addr);
+    }
+
+    Ok(())
+}
+
+/// Broadcasts a ping on the local network and returns the address of every receiver that replies
+/// before the timeout.
+pub fn broadcast(port: u16, timeout: Duration) -> Result<Vec<SocketAddr>> {
+    let broadcast = SocketAddr::new(Ipv4Addr::BROADCAST.into(), port);
+    let socket = bind_socket(&broadcast)?;
+    socket.set_broadcast(true)?;
+
+    send_ping(&socket, broadcast, 0)?;
+
+    let deadline = Instant::now() + timeout;
+    let mut receivers = Vec::new();
+
+    while let Some(from) = wait_for_pong(&socket, 0, deadline)? {
+        if !receivers.contains(&from) {
+            receivers.push(from);
+        }
+    }
+
+    Ok(receivers)
+}
+
+fn send_ping(socket: &UdpSocket, to: SocketAddr, sequence: i32) -> Result<()> {
+    let ping = OscPacket::Message(OscMessage {
+        addr: PING_ADDR.to_string(),
+        args: vec![OscType::Int(sequence)],
+    });
+    let bytes = rosc::encoder::encode(&ping).map_err(|err| anyhow!("{:?}", err))?;
+
+    socket
+        .send_to(&bytes, to)
+        .with_context(|| format!("Unable to send ping to {}", to))?;
+
+    Ok(())
+}
+
+/// Waits until a `/pong` for `sequence` is received, returning its sender, or `None` once the
+/// deadline passes.
+fn wait_for_pong(
+    socket: &UdpSocket,
+    sequence: i32,
+    deadline: Instant,
+) -> Result<Option<SocketAddr>> {
+    let mut buf = [0; 1024];
+
+    loop {
+        let remaining = match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => return Ok(None),
+        };
+        socket.set_read_timeout(Some(remaining))?;
+
+        let (size, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                return Ok(None);
+            }
+            Err(err) => return Err(err).context("Unable to receive reply"),
+        };
+
+        if let Ok((_, OscPacket::Message(OscMessage { addr, args }))) =
+            rosc::decoder::decode_udp(&buf[..size])
+        {
+            if addr == PONG_ADDR && args.first() == Some(&OscType::Int(sequence)) {
+                return Ok(Some(from));
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use anyhow::{anyhow, Context, Result};
+use osc_sender::encode::dmx_message;
+use osc_sender::{Encoding, Frame, Sender};
+use rosc::OscPacket;
+
+/// The port receivers listen for OSC packets on by default.
+pub const DEFAULT_PORT: u16 = 8001;
+
+/// Parses a receiver address formatted as `<host>[:<port>]`.
+pub fn parse_address(address: &str) -> Result<SocketAddr> {
+    let with_port = if address.parse::<SocketAddr>().is_ok() || address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{}:{}", address, DEFAULT_PORT)
+    };
+
+    with_port
+        .to_socket_addrs()
+        .with_context(|| format!("Unable to resolve {:?}", address))?
+        .next()
+        .ok_or_else(|| anyhow!("Unable to resolve {:?}", address))
+}
+
+/// Binds a UDP socket that can send to `to`.
+pub fn bind_socket(to: &SocketAddr) -> Result<UdpSocket> {
+    UdpSocket::bind(any_port(to)).context("Unable to bind a UDP socket")
+}
+
+/// Binds a sender that can send to `to`.
+pub fn sender(to: &SocketAddr, encoding: Encoding) -> Result<Sender> {
+    Ok(Sender::bind(any_port(to))?.with_encoding(encoding))
+}
+
+/// A random port on every interface of the same IP version as `to`
+fn any_port(to: &SocketAddr) -> SocketAddr {
+    let ip: IpAddr = if to.is_ipv4() {
+        Ipv4Addr::UNSPECIFIED.into()
+    } else {
+        Ipv6Addr::UNSPECIFIED.into()
+    };
+
+    SocketAddr::new(ip, 0)
+}
+
+/// Sends frames to a single receiver.
+pub struct Receiver {
+    pub addr: SocketAddr,
+    sender: Sender,
+}
+
+impl Receiver {
+    pub fn new(addr: SocketAddr, encoding: Encoding) -> Result<Self> {
+        Ok(Self {
+            addr,
+            sender: sender(&addr, encoding)?,
+        })
+    }
+
+    pub fn send_frame(&self, frame: &Frame) -> Result<()> {
+        self.sender.send(frame, self.addr)
+    }
+
+    /// Sets a single DMX channel. `value` is between 0 and 1.
+    pub fn send_dmx(&self, universe: usize, channel: usize, value: f32) -> Result<()> {
+        self.sender.send_packet(
+            &OscPacket::Message(dmx_message(universe, channel, value)),
+            self.addr,
+        )
+    }
+}
@@ -0,0 +1,90 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use smart_leds::RGB8;
+
+use osc_sender::Frame;
+
+use crate::receiver::Receiver;
+
+// Dim enough to run long strips off of a small power supply
+const BRIGHTNESS: u8 = 0x20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TestPattern {
+    /// Cycle every strip through red, green, blue and white once a second with the last LED of
+    /// each strip lit white, to check wiring, color order and strip lengths
+    Rgb,
+    /// A single white LED moving along every strip, to find dead or miswired LEDs
+    Chase,
+}
+
+/// Sends the test pattern to the receiver until the program is stopped.
+pub fn run(
+    receiver: &Receiver,
+    pattern: TestPattern,
+    strip_lengths: &[usize],
+    fps: u32,
+) -> Result<()> {
+    let frame_interval = Duration::from_secs_f64(1.0 / fps as f64);
+    let started_at = Instant::now();
+
+    println!(
+        "Sending the {:?} test pattern to {}",
+        pattern, receiver.addr
+    );
+
+    for frame_index in 0u64.. {
+        let frame = match pattern {
+            TestPattern::Rgb => {
+                let step = started_at.elapsed().as_secs() % 4;
+                let color = match step {
+                    0 => rgb(BRIGHTNESS, 0, 0),
+                    1 => rgb(0, BRIGHTNESS, 0),
+                    2 => rgb(0, 0, BRIGHTNESS),
+                    _ => rgb(BRIGHTNESS, BRIGHTNESS, BRIGHTNESS),
+                };
+
+                let mut frame = Frame::solid(strip_lengths, color);
+                for (strip, length) in strip_lengths.iter().enumerate() {
+                    if *length > 0 {
+                        frame.set(strip, length - 1, rgb(BRIGHTNESS, BRIGHTNESS, BRIGHTNESS));
+                    }
+                }
+                frame
+            }
+            TestPattern::Chase => Frame::from_strips(
+                strip_lengths
+                    .iter()
+                    .map(|length| {
+                        let lit = (frame_index % (*length).max(1) as u64) as usize;
+                        (0..*length)
+                            .map(|i| {
+                                if i == lit {
+                                    rgb(BRIGHTNESS, BRIGHTNESS, BRIGHTNESS)
+                                } else {
+                                    RGB8::default()
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect(),
+            ),
+        };
+
+        receiver.send_frame(&frame)?;
+
+        let next_frame_at = started_at + frame_interval * (frame_index + 1) as u32;
+        if let Some(delay) = next_frame_at.checked_duration_since(Instant::now()) {
+            thread::sleep(delay);
+        }
+    }
+
+    Ok(())
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8 { r, g, b }
+}
@@ -4,8 +4,9 @@
 #![feature(c_variadic)]
 #![feature(const_mut_refs)]
 
-use alloc::{boxed::Box, vec::Vec};
+use alloc::{boxed::Box, string::String, vec::Vec};
 use apa102_spi::Apa102;
+use core::mem::discriminant;
 use embedded_svc::wifi::{
     ClientConfiguration, ClientConnectionStatus, ClientIpStatus, ClientStatus, Configuration,
     Status, Wifi,
@@ -13,29 +14,68 @@ use embedded_svc::wifi::{
 use esp32c3_hal::{
     clock::{ClockControl, CpuClock},
     gpio::IO,
-    pac::Peripherals,
+    pac::{Peripherals, UART0},
     prelude::*,
     spi::{Spi, SpiMode},
-    Rtc,
+    Rtc, Serial,
 };
 use esp_backtrace as _;
 use esp_println::println;
+use esp_storage::FlashStorage;
 use esp_wifi::wifi::initialize;
 use esp_wifi::wifi::utils::create_network_interface;
 use esp_wifi::wifi_interface::timestamp;
 use esp_wifi::{create_network_stack_storage, network_stack_storage};
+use osc_receiver::connection::{Connection, ConnectionAction, ConnectionState, Link};
+use osc_receiver::failsafe::{parse_hex_color, Failsafe, FailsafeAction};
+use osc_receiver::led_config::{ColorOrdered, LedConfig, LedConfigCommand, LedSetting, LedType};
 use osc_receiver::led_strip::{LedStrip, RGB8SmartLedsWrite};
+use osc_receiver::mdns::{self, Responder, MDNS_GROUP, MDNS_PORT};
+use osc_receiver::pattern::{PatternKind, PatternParams, Patterns};
+use osc_receiver::preset::{Crossfade, PresetCommand, Presets};
+use osc_receiver::provisioning::{Settings, DEFAULT_OSC_PORT};
+use osc_receiver::query::{QueryHandler, ReceiverInfo, StripInfo};
+use osc_receiver::scene::{self, Scene};
 use riscv_rt::entry;
 use smart_leds::RGB8;
 use smoltcp::iface::SocketHandle;
 use smoltcp::socket::{Socket, UdpPacketMetadata, UdpSocket, UdpSocketBuffer};
+use smoltcp::wire::{IpAddress, IpEndpoint, Ipv4Address};
 
 #[macro_use]
 extern crate alloc;
 
-const SSID: &str = env!("SSID");
-const PASSWORD: &str = env!("PASSWORD");
-const LED_TYPE: &str = env!("LED_TYPE");
+mod provisioning;
+mod storage;
+
+// Only used until settings are saved from the configuration page, see the README
+const SSID: Option<&str> = option_env!("SSID");
+const PASSWORD: Option<&str> = option_env!("PASSWORD");
+// Only used until LED settings are stored, see the README
+const LED_TYPE: Option<&str> = option_env!("LED_TYPE");
+// The name the receiver is advertised as with mDNS, which is also used as its hostname
+const MDNS_NAME: Option<&str> = option_env!("MDNS_NAME");
+const DEFAULT_MDNS_NAME: &str = "raves-esp32c3";
+
+// The GPIOs the LEDs can be connected to. GPIO2 and GPIO10 are taken by the SPI's unused MISO and
+// CS, and the rest are used by the flash, USB or the serial console
+const LED_PINS: [u8; 9] = [0, 1, 3, 4, 5, 6, 7, 8, 9];
+
+// How long to try joining the network before starting the configuration access point
+const CONNECT_TIMEOUT_MS: u64 = 30_000;
+// How long the configuration access point waits to be used before the stored settings are tried
+// again
+const PORTAL_TIMEOUT_MS: u64 = 5 * 60_000;
+
+// What the strips show once no frames have arrived for FAILSAFE_TIMEOUT seconds, see the README
+const FAILSAFE_TIMEOUT: Option<&str> = option_env!("FAILSAFE_TIMEOUT");
+const FAILSAFE_ACTION: Option<&str> = option_env!("FAILSAFE_ACTION");
+const FAILSAFE_FADE: Option<&str> = option_env!("FAILSAFE_FADE");
+
+// A built in pattern to run from boot until frames arrive, see the README
+const PATTERN: Option<&str> = option_env!("PATTERN");
+const PATTERN_COLOR: Option<&str> = option_env!("PATTERN_COLOR");
+const PATTERN_SPEED: Option<&str> = option_env!("PATTERN_SPEED");
 
 // #[global_allocator]
 // static ALLOCATOR: esp_alloc::EspHeap = esp_alloc::EspHeap::empty();
@@ -57,6 +97,48 @@ const LED_TYPE: &str = env!("LED_TYPE");
 //     loop {}
 // }
 
+extern "C" {
+    // Provided by the ROM
+    fn software_reset();
+}
+
+pub fn restart() -> ! {
+    println!("Restarting...");
+    unsafe { software_reset() };
+    loop {}
+}
+
+// Matches the configured pins against every pair of LED_PINS, since each GPIO has its own type.
+// Evaluates to `None` if either pin isn't one of the listed pins
+macro_rules! with_led_pins {
+    ($pins:expr, $sclk_pin:expr, $mosi_pin:expr, |$sclk:ident, $mosi:ident| $build:expr, [$($pin:ident = $number:literal),*]) => {
+        with_led_pins!(@arms $pins, ($sclk_pin, $mosi_pin), |$sclk, $mosi| $build, [$($pin = $number),*], [])
+    };
+    (@arms $pins:expr, $key:expr, |$sclk:ident, $mosi:ident| $build:expr, [$first:ident = $first_number:literal $(, $rest:ident = $rest_number:literal)*], [$($arms:tt)*]) => {
+        with_led_pins!(@arms $pins, $key, |$sclk, $mosi| $build, [$($rest = $rest_number),*], [
+            $($arms)*
+            $(
+                ($first_number, $rest_number) => {
+                    let $sclk = $pins.$first;
+                    let $mosi = $pins.$rest;
+                    Some($build)
+                }
+                ($rest_number, $first_number) => {
+                    let $sclk = $pins.$rest;
+                    let $mosi = $pins.$first;
+                    Some($build)
+                }
+            )*
+        ])
+    };
+    (@arms $pins:expr, $key:expr, |$sclk:ident, $mosi:ident| $build:expr, [], [$($arms:tt)*]) => {
+        match $key {
+            $($arms)*
+            _ => None,
+        }
+    };
+}
+
 // Set the first and third LED to indicate connection status
 fn set_indicator_leds(smart_leds: &mut Vec<LedStrip>, color: RGB8) {
     let colors = [color.clone(), RGB8::default(), color.clone()];
@@ -70,6 +152,291 @@ fn set_indicator_leds(smart_leds: &mut Vec<LedStrip>, color: RGB8) {
         .unwrap();
 }
 
+// Shows the state of the wifi connection with the indicator LEDs
+fn show_connection_state(led_strips: &mut Vec<LedStrip>, state: ConnectionState) {
+    match state {
+        // Green while connecting
+        ConnectionState::Connecting { .. } => set_indicator_leds(
+            led_strips,
+            RGB8 {
+                r: 0x0,
+                g: 0xF,
+                b: 0x0,
+            },
+        ),
+        // Orange while waiting to reconnect
+        ConnectionState::Reconnecting { .. } => set_indicator_leds(
+            led_strips,
+            RGB8 {
+                r: 0xF,
+                g: 0xF,
+                b: 0x0,
+            },
+        ),
+        // Turn off the indicator LEDs, redrawing the LEDs under them
+        ConnectionState::Connected { .. } => {
+            if let Err(err) = LedStrip::write_all(led_strips) {
+                println!("Unable to write to the LEDs: {:?}", err);
+            }
+        }
+    }
+}
+
+// Counts a frame written to the LEDs in the status reported to /status
+// Advances the failsafe, any preset fade and the running pattern, writing the strips whenever they
+// change
+fn update_effects(
+    led_strips: &mut [LedStrip],
+    failsafe: &mut Failsafe,
+    crossfade: &mut Option<Crossfade>,
+    patterns: &mut Patterns,
+    queries: &mut QueryHandler,
+    now_ms: u64,
+) {
+    if failsafe.update(led_strips, patterns, now_ms) {
+        let written = LedStrip::write_all(led_strips);
+        count_frame(queries, written, now_ms);
+    }
+    if let Some(fade) = crossfade {
+        if fade.update(led_strips, now_ms) {
+            let written = LedStrip::write_all(led_strips);
+            count_frame(queries, written, now_ms);
+        }
+        if fade.is_finished() {
+            *crossfade = None;
+        }
+    }
+    if patterns.update(led_strips, now_ms) {
+        let written = LedStrip::write_all(led_strips);
+        count_frame(queries, written, now_ms);
+    }
+}
+
+fn count_frame<E: core::fmt::Debug>(
+    queries: &mut QueryHandler,
+    written: Result<(), E>,
+    now_ms: u64,
+) {
+    match written {
+        Ok(()) => queries.status.frame_displayed(now_ms),
+        Err(err) => {
+            queries.status.write_errors += 1;
+            println!("Unable to write to the LEDs: {:?}", err);
+        }
+    }
+}
+
+// The stored LED settings, falling back to the LED_TYPE built in
+fn load_led_config(flash: &mut FlashStorage) -> LedConfig {
+    let default = || {
+        let led_type = LED_TYPE.map_or(LedType::Apa102, |led_type| {
+            LedType::parse(led_type)
+                .expect("Invalid LED_TYPE. Must be either 'WS2812B' or 'APA102'.")
+        });
+        LedConfig::new(led_type)
+    };
+
+    match storage::LED_CONFIG.read(flash) {
+        Ok(Some(bytes)) => match LedConfig::decode(&bytes) {
+            Ok(led_config) if has_led_pins(&led_config) => led_config,
+            Ok(_) => {
+                println!("Unsupported LED pins, using the default LED settings");
+                default()
+            }
+            Err(err) => {
+                println!("Invalid LED settings: {:?}", err);
+                default()
+            }
+        },
+        Ok(None) => default(),
+        Err(err) => {
+            println!("Unable to read the LED settings: {:?}", err);
+            default()
+        }
+    }
+}
+
+fn has_led_pins(led_config: &LedConfig) -> bool {
+    LED_PINS.contains(&led_config.sclk_pin) && LED_PINS.contains(&led_config.mosi_pin)
+}
+
+// Changes, applies or prints the LED settings. Changes are stored straight away and applied when
+// the receiver restarts
+fn handle_led_config_command(
+    command: LedConfigCommand,
+    led_config: &mut LedConfig,
+    flash: &mut FlashStorage,
+) {
+    match command {
+        LedConfigCommand::Set { setting, value } => {
+            let mut changed = led_config.clone();
+            if let Err(err) = changed.set(setting, &value) {
+                println!("{}", err);
+                return;
+            }
+            if !has_led_pins(&changed) {
+                println!("Invalid pin {}. Must be one of {:?}", value, LED_PINS);
+                return;
+            }
+
+            match storage::LED_CONFIG.write(flash, &changed.encode()) {
+                Ok(()) => {
+                    *led_config = changed;
+                    println!(
+                        "Set {} to {}. Send /config/led/apply or type `led apply` to restart with the new LED settings",
+                        setting.name(),
+                        led_config.get(setting)
+                    );
+                    if setting == LedSetting::Type {
+                        println!("Set spi_frequency to {}", led_config.spi_frequency_hz);
+                    }
+                }
+                Err(err) => println!("Unable to save the LED settings: {:?}", err),
+            }
+        }
+        LedConfigCommand::Apply => restart(),
+        LedConfigCommand::Show => println!("{}", led_config),
+    }
+}
+
+// Reads what has been typed on the serial console so far, returning each line once it is finished
+fn read_console_line(serial: &mut Serial<UART0>, line: &mut Vec<u8>) -> Option<String> {
+    while let Ok(byte) = serial.read() {
+        match byte {
+            b'\r' | b'\n' => {
+                if !line.is_empty() {
+                    let finished = String::from_utf8_lossy(line).into_owned();
+                    line.clear();
+                    return Some(finished);
+                }
+            }
+            byte => line.push(byte),
+        }
+    }
+
+    None
+}
+
+// The settings saved from the configuration page, falling back to the ones built in
+fn load_settings(flash: &mut FlashStorage) -> Option<Settings> {
+    match storage::SETTINGS.read(flash) {
+        Ok(Some(bytes)) => match Settings::decode(&bytes) {
+            Ok(settings) => return Some(settings),
+            Err(err) => println!("Invalid settings: {:?}", err),
+        },
+        Ok(None) => (),
+        Err(err) => println!("Unable to read the settings: {:?}", err),
+    }
+
+    SSID.map(|ssid| {
+        Settings::new(
+            ssid,
+            PASSWORD.unwrap_or(""),
+            MDNS_NAME.unwrap_or(DEFAULT_MDNS_NAME),
+        )
+    })
+}
+
+fn load_default_scene(flash: &mut FlashStorage) -> Option<Scene> {
+    match storage::DEFAULT_SCENE.read(flash) {
+        Ok(Some(bytes)) => match Scene::decode(&bytes) {
+            Ok(scene) => Some(scene),
+            Err(err) => {
+                println!("Invalid default scene: {:?}", err);
+                None
+            }
+        },
+        Ok(None) => None,
+        Err(err) => {
+            println!("Unable to read the default scene: {:?}", err);
+            None
+        }
+    }
+}
+
+// Returns whether the scene was saved
+fn save_default_scene(flash: &mut FlashStorage, scene: &Scene) -> bool {
+    let bytes = match scene.encode() {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            println!("Unable to save the default scene: {:?}", err);
+            return false;
+        }
+    };
+
+    match storage::DEFAULT_SCENE.write(flash, &bytes) {
+        Ok(()) => {
+            println!("Saved the default scene");
+            true
+        }
+        Err(err) => {
+            println!("Unable to save the default scene: {:?}", err);
+            false
+        }
+    }
+}
+
+fn load_presets(flash: &mut FlashStorage) -> Presets {
+    match storage::PRESETS.read(flash) {
+        Ok(Some(bytes)) => Presets::decode(&bytes).unwrap_or_else(|err| {
+            println!("Invalid presets: {:?}", err);
+            Presets::default()
+        }),
+        Ok(None) => Presets::default(),
+        Err(err) => {
+            println!("Unable to read the presets: {:?}", err);
+            Presets::default()
+        }
+    }
+}
+
+// Stores or recalls a preset, returning the crossfade to a recalled preset
+fn handle_preset_command(
+    command: PresetCommand,
+    presets: &mut Presets,
+    flash: &mut FlashStorage,
+    led_strips: &[LedStrip],
+    now_ms: u64,
+) -> Option<Crossfade> {
+    match command {
+        PresetCommand::Store { name } => {
+            // Every preset is rewritten together, so only keep the new preset once it is in flash
+            let mut stored = presets.clone();
+            stored.insert(&name, Scene::capture(led_strips));
+
+            match stored.encode() {
+                Ok(bytes) => match storage::PRESETS.write(flash, &bytes) {
+                    Ok(()) => {
+                        println!("Stored preset {}", name);
+                        *presets = stored;
+                    }
+                    Err(err) => println!("Unable to store preset {}: {:?}", name, err),
+                },
+                Err(err) => println!("Unable to store preset {}: {:?}", name, err),
+            }
+
+            None
+        }
+        PresetCommand::Recall { name, fade_ms } => {
+            match presets.recall(&name, led_strips, fade_ms, now_ms) {
+                Ok(crossfade) => {
+                    println!("Recalling preset {} over {}ms", name, fade_ms);
+                    Some(crossfade)
+                }
+                Err(err) => {
+                    println!("{}", err.reason);
+                    None
+                }
+            }
+        }
+    }
+}
+
+fn seconds_to_ms(seconds: &str) -> u64 {
+    let seconds: f32 = seconds.parse().expect("Invalid failsafe duration");
+    (seconds * 1000.0) as u64
+}
+
 #[entry]
 fn main() -> ! {
     init_logger();
@@ -94,64 +461,62 @@ fn main() -> ! {
 
     let mut smart_leds: Vec<&mut dyn RGB8SmartLedsWrite> = Vec::new();
 
-    let mut boxed_smart_led: Box<dyn RGB8SmartLedsWrite> = if LED_TYPE == "APA102" {
-        let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-        // Connect these two to the LEDs
-        let sclk = io.pins.gpio6;
-        let mosi = io.pins.gpio7;
-
-        let miso = io.pins.gpio2;
-        let cs = io.pins.gpio10;
-
-        let spi = Spi::new(
-            peripherals.SPI2,
-            sclk,
-            mosi,
-            miso,
-            cs,
-            // These LED strips should work up to 12 MHz but depending on wiring interference may limit that
-            12u32.MHz(),
-            SpiMode::Mode0,
-            &mut system.peripheral_clock_control,
-            &clocks,
-        );
-
-        let apa102 = Apa102::new(spi);
-
-        Box::new(apa102)
-    } else if LED_TYPE == "WS2812B" {
-        let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-        // Connect these two to the LEDs
-        let sclk = io.pins.gpio6;
-        let mosi = io.pins.gpio7;
-
-        let miso = io.pins.gpio2;
-        let cs = io.pins.gpio10;
-
-        let spi = Spi::new(
-            peripherals.SPI2,
-            sclk,
-            mosi,
-            miso,
-            cs,
-            3u32.MHz(),
-            SpiMode::Mode0,
-            &mut system.peripheral_clock_control,
-            &clocks,
-        );
-
-        let ws2812b = ws2812_spi::Ws2812::new(spi);
-
-        Box::new(ws2812b)
-    } else {
-        panic!("Invalid LED_TYPE. Must be either 'WS2812B' or 'APA102'.");
-    };
+    let mut flash = FlashStorage::new();
+    let mut led_config = load_led_config(&mut flash);
+    println!("LED settings:\n{}", led_config);
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    // The LEDs are connected to the SCLK and MOSI pins from the LED settings
+    let miso = io.pins.gpio2;
+    let cs = io.pins.gpio10;
+
+    let smart_led = with_led_pins!(
+        io.pins,
+        led_config.sclk_pin,
+        led_config.mosi_pin,
+        |sclk, mosi| {
+            let spi = Spi::new(
+                peripherals.SPI2,
+                sclk,
+                mosi,
+                miso,
+                cs,
+                led_config.spi_frequency_hz.Hz(),
+                SpiMode::Mode0,
+                &mut system.peripheral_clock_control,
+                &clocks,
+            );
+
+            let smart_led: Box<dyn RGB8SmartLedsWrite> = match led_config.led_type {
+                LedType::Apa102 => Box::new(Apa102::new(spi)),
+                LedType::Ws2812b => Box::new(ws2812_spi::Ws2812::new(spi)),
+            };
+            smart_led
+        },
+        [
+            gpio0 = 0,
+            gpio1 = 1,
+            gpio3 = 3,
+            gpio4 = 4,
+            gpio5 = 5,
+            gpio6 = 6,
+            gpio7 = 7,
+            gpio8 = 8,
+            gpio9 = 9
+        ]
+    )
+    .expect("Unsupported LED pins");
+
+    let mut boxed_smart_led: Box<dyn RGB8SmartLedsWrite> = Box::new(ColorOrdered {
+        smart_led,
+        color_order: led_config.color_order,
+    });
 
     smart_leds.push(boxed_smart_led.as_mut());
 
     let mut led_strips = smart_leds
         .into_iter()
-        .map(LedStrip::new)
+        .map(|smart_led| LedStrip::with_length(smart_led, led_config.length))
         .collect::<Vec<_>>();
 
     // Set the indicator LEDs to red to indicate that the microcontroller has restarted
@@ -164,23 +529,65 @@ fn main() -> ! {
         },
     );
 
-    // Create 2 sockets - one for DHCP and one for a placeholder TCP socket which will be replaced by UDP later on
-    let mut storage = create_network_stack_storage!(2, 8, 1);
+    let mut serial = Serial::new(peripherals.UART0);
+    let mut console_line = Vec::new();
+
+    // Show the default scene while connecting. The indicator LEDs are drawn over it until the wifi
+    // is connected
+    if let Some(scene) = load_default_scene(&mut flash) {
+        println!("Showing the default scene");
+        scene.apply(&mut led_strips);
+        if let Err(err) = LedStrip::write_all(&mut led_strips) {
+            println!("Unable to write to the LEDs: {:?}", err);
+        }
+        set_indicator_leds(
+            &mut led_strips,
+            RGB8 {
+                r: 0xF,
+                g: 0x0,
+                b: 0x0,
+            },
+        );
+    }
+
+    let settings = load_settings(&mut flash);
+    let mut presets = load_presets(&mut flash);
+    let mut crossfade: Option<Crossfade> = None;
+
+    let mut pattern_params = PatternParams::default();
+    if let Some(color) = PATTERN_COLOR {
+        pattern_params.color = parse_hex_color(color).expect("Invalid PATTERN_COLOR");
+    }
+    if let Some(speed) = PATTERN_SPEED {
+        pattern_params.speed = speed.parse().expect("Invalid PATTERN_SPEED");
+    }
+    let mut patterns = Patterns::new(pattern_params);
+    if let Some(pattern) = PATTERN {
+        let pattern = PatternKind::parse(pattern).expect("Invalid PATTERN");
+        println!("Running the {} pattern", pattern);
+        patterns.start(pattern, timestamp().total_millis() as u64);
+    }
+
+    // Create 3 sockets - one for DHCP and two placeholder TCP sockets which will be replaced by the
+    // OSC and mDNS UDP sockets later on
+    let mut storage = create_network_stack_storage!(3, 8, 1);
     let mut ethernet = create_network_interface(network_stack_storage!(storage));
 
-    // Remove a TCP socket to make room for the UDP socket
+    // Remove the TCP sockets to make room for the UDP sockets
     {
-        let mut tcp_socket_handle: Option<SocketHandle> = None;
+        let mut tcp_socket_handles: Vec<SocketHandle> = Vec::new();
 
         for (handle, socket) in ethernet.sockets_mut() {
             // println!("{:?}", socket);
             match socket {
-                Socket::Tcp(_) => tcp_socket_handle = Some(handle),
+                Socket::Tcp(_) => tcp_socket_handles.push(handle),
                 _ => {}
             }
         }
 
-        ethernet.remove_socket(tcp_socket_handle.unwrap());
+        for handle in tcp_socket_handles {
+            ethernet.remove_socket(handle);
+        }
     }
 
     // Add the udp socket, replacing the previous TCP socket
@@ -191,13 +598,74 @@ fn main() -> ! {
         let udp_rx_buffer =
             unsafe { UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY], &mut UDP_RX_DATA[..]) };
 
-        let udp_tx_buffer = UdpSocketBuffer::new(vec![UdpPacketMetadata::EMPTY], vec![0u8; 0]);
+        // Only small query replies are sent
+        const MAX_UDP_REPLY_SIZE: usize = 512;
+        let udp_tx_buffer = UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY],
+            vec![0u8; MAX_UDP_REPLY_SIZE],
+        );
 
         let udp_socket = UdpSocket::new(udp_rx_buffer, udp_tx_buffer);
 
         ethernet.add_socket(udp_socket)
     };
 
+    // Add the mDNS socket. Queries and replies are small so they get small buffers of their own
+    let mdns_socket_handle = {
+        const MAX_MDNS_PACKET_SIZE: usize = 1024;
+
+        let mdns_rx_buffer = UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; 2],
+            vec![0u8; MAX_MDNS_PACKET_SIZE],
+        );
+        let mdns_tx_buffer = UdpSocketBuffer::new(
+            vec![UdpPacketMetadata::EMPTY; 2],
+            vec![0u8; MAX_MDNS_PACKET_SIZE],
+        );
+
+        ethernet.add_socket(UdpSocket::new(mdns_rx_buffer, mdns_tx_buffer))
+    };
+
+    let mdns_name = settings
+        .as_ref()
+        .map_or(MDNS_NAME.unwrap_or(DEFAULT_MDNS_NAME), |settings| {
+            settings.name.as_str()
+        });
+    let osc_port = settings
+        .as_ref()
+        .map_or(DEFAULT_OSC_PORT, |settings| settings.osc_port);
+    let strip_lengths = led_strips
+        .iter()
+        .map(|led_strip| led_strip.data.len())
+        .collect::<Vec<_>>();
+    let responder = Responder::new(
+        mdns_name,
+        mdns_name,
+        osc_port,
+        mdns::txt_records(
+            &strip_lengths,
+            led_config.led_type.name(),
+            env!("CARGO_PKG_VERSION"),
+        ),
+    );
+    let mdns_group = IpEndpoint::new(IpAddress::Ipv4(Ipv4Address(MDNS_GROUP)), MDNS_PORT);
+
+    let info = ReceiverInfo {
+        version: env!("CARGO_PKG_VERSION").into(),
+        strips: strip_lengths
+            .iter()
+            .map(|length| StripInfo::new(*length, led_config.led_type.name()))
+            .collect(),
+    };
+    let mut queries = QueryHandler::new(info, timestamp().total_millis() as u64);
+
+    let mut failsafe = Failsafe::new(
+        seconds_to_ms(FAILSAFE_TIMEOUT.unwrap_or("10")),
+        seconds_to_ms(FAILSAFE_FADE.unwrap_or("2")),
+        FailsafeAction::parse(FAILSAFE_ACTION.unwrap_or("hold")).expect("Invalid FAILSAFE_ACTION"),
+    );
+    failsafe.load_scene(load_default_scene(&mut flash).as_ref(), &presets);
+
     let mut wifi_interface = esp_wifi::wifi_interface::Wifi::new(ethernet);
 
     initialize(&mut peripherals.SYSTIMER, peripherals.RNG, &clocks).unwrap();
@@ -213,9 +681,44 @@ fn main() -> ! {
     //     }
     // }
 
+    // Without any settings the receiver goes straight to the configuration page
+    let portal_settings = settings
+        .clone()
+        .unwrap_or_else(|| Settings::new("", "", mdns_name));
+    let settings = match settings.as_ref() {
+        Some(settings) => settings,
+        None => {
+            set_indicator_leds(
+                &mut led_strips,
+                RGB8 {
+                    r: 0x0,
+                    g: 0x0,
+                    b: 0xF,
+                },
+            );
+            provisioning::run(
+                &mut wifi_interface,
+                [socket_handle, mdns_socket_handle],
+                &mut flash,
+                portal_settings,
+                None,
+                |now_ms| {
+                    update_effects(
+                        &mut led_strips,
+                        &mut failsafe,
+                        &mut crossfade,
+                        &mut patterns,
+                        &mut queries,
+                        now_ms,
+                    )
+                },
+            );
+        }
+    };
+
     let client_config = Configuration::Client(ClientConfiguration {
-        ssid: SSID.into(),
-        password: PASSWORD.into(),
+        ssid: settings.ssid.as_str().into(),
+        password: settings.password.as_str().into(),
         ..Default::default()
     });
     wifi_interface.set_configuration(&client_config).unwrap();
@@ -243,19 +746,11 @@ fn main() -> ! {
     }
     // println!("{:?}", wifi_interface.get_status());
 
-    println!("Connecting to {}...", &SSID);
+    println!("Connecting to {}...", settings.ssid);
 
     // Set the first and third LED to green to indicate that the microcontroller is connecting to Wifi
-    set_indicator_leds(
-        &mut led_strips,
-        RGB8 {
-            r: 0x0,
-            g: 0xF,
-            b: 0x0,
-        },
-    );
-
-    let mut stage = 0;
+    let mut connection = Connection::new(CONNECT_TIMEOUT_MS, timestamp().total_millis() as u64);
+    show_connection_state(&mut led_strips, connection.state());
 
     loop {
         if let Err(err) = wifi_interface.poll_dhcp() {
@@ -265,51 +760,246 @@ fn main() -> ! {
             println!("Wifi Error: {:?}", err);
         }
 
-        // println!("{:?}", wifi_interface.get_status());
-        if let Status(
-            ClientStatus::Started(ClientConnectionStatus::Connected(ClientIpStatus::Done(config))),
-            _,
-        ) = wifi_interface.get_status()
-        {
-            match stage {
-                0 => {
-                    let (socket, _cx) = wifi_interface
-                        .network_interface()
-                        .get_socket_and_context::<UdpSocket>(socket_handle);
+        // The LED settings can be changed without a network
+        if let Some(line) = read_console_line(&mut serial, &mut console_line) {
+            match LedConfigCommand::parse_line(&line) {
+                Some(Ok(command)) => {
+                    handle_led_config_command(command, &mut led_config, &mut flash)
+                }
+                Some(Err(err)) => println!("{}", err),
+                None => println!(
+                    "Unknown command {:?}. Type `led` to show the LED settings",
+                    line
+                ),
+            }
+        }
 
-                    // Udp
-                    socket.bind(9000).unwrap();
+        // The failsafe, preset fades and patterns run whether or not the wifi is connected, so that
+        // the strips don't freeze on the last frame while the receiver reconnects
+        let now_ms = timestamp().total_millis() as u64;
+        update_effects(
+            &mut led_strips,
+            &mut failsafe,
+            &mut crossfade,
+            &mut patterns,
+            &mut queries,
+            now_ms,
+        );
 
-                    stage = 1;
+        let link = match wifi_interface.get_status() {
+            Status(
+                ClientStatus::Started(ClientConnectionStatus::Connected(ClientIpStatus::Done(
+                    config,
+                ))),
+                _,
+            ) => Link::Ip(config.ip.octets()),
+            Status(ClientStatus::Started(ClientConnectionStatus::Connected(_)), _) => Link::Up,
+            _ => Link::Down,
+        };
+
+        let previous_state = connection.state();
+        match connection.update(link, now_ms) {
+            Some(ConnectionAction::Bind(ip)) => {
+                // Rebinding closes the sockets first in case the address changed
+                let socket = wifi_interface
+                    .network_interface()
+                    .get_socket::<UdpSocket>(socket_handle);
+                socket.close();
+                socket.bind(osc_port).unwrap();
+
+                // Advertise the receiver with mDNS
+                if let Err(err) = wifi_interface
+                    .network_interface()
+                    .join_multicast_group(IpAddress::Ipv4(Ipv4Address(MDNS_GROUP)), timestamp())
+                {
+                    println!("Unable to join the mDNS multicast group: {:?}", err);
+                }
 
-                    // Turn off the connection indicator LEDs
-                    set_indicator_leds(&mut led_strips, RGB8::default());
+                let mdns_socket = wifi_interface
+                    .network_interface()
+                    .get_socket::<UdpSocket>(mdns_socket_handle);
+                mdns_socket.close();
+                mdns_socket.bind(MDNS_PORT).unwrap();
 
-                    println!("Wifi Connected! Listening on {}:9000", config.ip);
+                if let Err(err) = mdns_socket.send_slice(&responder.announcement(ip), mdns_group) {
+                    println!("Unable to send mDNS announcement: {:?}", err);
                 }
-                1 => {
-                    let socket = wifi_interface
+
+                println!(
+                    "Wifi Connected! Listening on {}:{} ({}.local)",
+                    Ipv4Address(ip),
+                    osc_port,
+                    mdns_name
+                );
+            }
+            Some(ConnectionAction::Unbind) => {
+                println!("Lost the connection to {}", settings.ssid);
+
+                for handle in [socket_handle, mdns_socket_handle] {
+                    wifi_interface
                         .network_interface()
-                        .get_socket::<UdpSocket>(socket_handle);
-
-                    if let Ok((udp_packet, _)) = socket.recv() {
-                        // for c in udp_packet {
-                        //     print!("{}", *c as char);
-                        // }
-                        // println!("");
-                        // println!("<EOF>");
-
-                        let osc_packet = rosc::decoder::decode_udp(udp_packet);
-
-                        if let Ok((&[], osc_packet)) = osc_packet {
-                            // println!("Valid packet! ({:?} Bytes)", udp_packet.len());
-                            LedStrip::update(&mut led_strips, osc_packet);
-                        } else {
-                            println!("Invalid packet");
+                        .get_socket::<UdpSocket>(handle)
+                        .close();
+                }
+            }
+            Some(ConnectionAction::Reconnect) => {
+                println!("Reconnecting to {}...", settings.ssid);
+
+                // Start DHCP from scratch rather than waiting for the old lease to be renewed
+                for (_, socket) in wifi_interface.network_interface().sockets_mut() {
+                    if let Socket::Dhcpv4(dhcp_socket) = socket {
+                        dhcp_socket.reset();
+                    }
+                }
+
+                if let Err(err) = wifi_interface.set_configuration(&client_config) {
+                    println!("Unable to reconnect: {:?}", err);
+                }
+            }
+            // Let the network be configured if it can't be joined
+            Some(ConnectionAction::Provision) => {
+                println!("Unable to connect to {}", settings.ssid);
+                set_indicator_leds(
+                    &mut led_strips,
+                    RGB8 {
+                        r: 0x0,
+                        g: 0x0,
+                        b: 0xF,
+                    },
+                );
+                provisioning::run(
+                    &mut wifi_interface,
+                    [socket_handle, mdns_socket_handle],
+                    &mut flash,
+                    portal_settings,
+                    Some(PORTAL_TIMEOUT_MS),
+                    |now_ms| {
+                        update_effects(
+                            &mut led_strips,
+                            &mut failsafe,
+                            &mut crossfade,
+                            &mut patterns,
+                            &mut queries,
+                            now_ms,
+                        )
+                    },
+                );
+            }
+            None => (),
+        }
+        if discriminant(&connection.state()) != discriminant(&previous_state) {
+            show_connection_state(&mut led_strips, connection.state());
+        }
+
+        if let Some(ip) = connection.ip() {
+            let socket = wifi_interface
+                .network_interface()
+                .get_socket::<UdpSocket>(socket_handle);
+
+            if let Ok((udp_packet, sender)) = socket.recv() {
+                // for c in udp_packet {
+                //     print!("{}", *c as char);
+                // }
+                // println!("");
+                // println!("<EOF>");
+
+                let osc_packet = rosc::decoder::decode_udp(udp_packet);
+
+                if let Ok((&[], osc_packet)) = osc_packet {
+                    // println!("Valid packet! ({:?} Bytes)", udp_packet.len());
+                    let now_ms = timestamp().total_millis() as u64;
+
+                    if let Some(reply) = queries.reply(&osc_packet, now_ms) {
+                        match rosc::encoder::encode(&reply) {
+                            Ok(reply) => {
+                                if let Err(err) = socket.send_slice(&reply, sender) {
+                                    println!("Unable to send reply: {:?}", err);
+                                }
+                            }
+                            Err(err) => println!("Unable to encode reply: {:?}", err),
+                        }
+                    } else if scene::is_save_default(&osc_packet) {
+                        let scene = Scene::capture(&led_strips);
+                        if save_default_scene(&mut flash, &scene) {
+                            failsafe.load_scene(Some(&scene), &presets);
+                        }
+                    } else if let Some(command) = LedConfigCommand::parse(&osc_packet) {
+                        match command {
+                            Ok(command) => {
+                                handle_led_config_command(command, &mut led_config, &mut flash)
+                            }
+                            Err(err) => println!("{}", err.reason),
                         }
+                    } else if let Some(command) = PresetCommand::parse(&osc_packet) {
+                        match command {
+                            Ok(command) => {
+                                let stores = matches!(command, PresetCommand::Store { .. });
+                                if let Some(fade) = handle_preset_command(
+                                    command,
+                                    &mut presets,
+                                    &mut flash,
+                                    &led_strips,
+                                    now_ms,
+                                ) {
+                                    // The preset stays up until the next frame
+                                    failsafe.suspend();
+                                    patterns.stop();
+                                    crossfade = Some(fade);
+                                }
+                                if stores {
+                                    let default_scene = load_default_scene(&mut flash);
+                                    failsafe.load_scene(default_scene.as_ref(), &presets);
+                                }
+                            }
+                            Err(err) => println!("{}", err.reason),
+                        }
+                    } else if let Some(handled) = patterns.handle_packet(&osc_packet, now_ms) {
+                        match handled {
+                            Ok(()) => {
+                                if let Some(running) = patterns.running() {
+                                    println!("Running the {} pattern", running);
+                                    // The pattern runs until the next frame
+                                    failsafe.suspend();
+                                    crossfade = None;
+                                }
+                            }
+                            Err(err) => println!("{}", err.reason),
+                        }
+                    } else if LedStrip::apply(&mut led_strips, osc_packet, &mut Vec::new()) {
+                        let written = LedStrip::write_all(&mut led_strips);
+                        count_frame(&mut queries, written, now_ms);
+                        failsafe.frame_received(now_ms);
+                        crossfade = None;
+                        patterns.stop();
                     }
+                } else {
+                    queries.status.invalid_packets += 1;
+                    println!("Invalid packet");
+                }
+            }
+
+            let mdns_socket = wifi_interface
+                .network_interface()
+                .get_socket::<UdpSocket>(mdns_socket_handle);
+
+            let mdns_reply = match mdns_socket.recv() {
+                Ok((query, sender)) => responder
+                    .reply(query, sender.port, ip)
+                    .map(|reply| (reply, sender)),
+                Err(_) => None,
+            };
+
+            if let Some((reply, sender)) = mdns_reply {
+                // Queries from ordinary DNS resolvers are answered directly
+                let to = if sender.port == MDNS_PORT {
+                    mdns_group
+                } else {
+                    sender
+                };
+
+                if let Err(err) = mdns_socket.send_slice(&reply, to) {
+                    println!("Unable to send mDNS reply: {:?}", err);
                 }
-                _ => (),
             }
         }
     }
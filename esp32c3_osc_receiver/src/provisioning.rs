@@ -0,0 +1,203 @@
+//! The configuration access point started when the receiver can't join a network, see the README.
+//!
+//! The page itself, the DNS replies and the DHCP leases come from `osc_receiver::provisioning`,
+//! this module only moves their bytes in and out of the network stack.
+use alloc::{string::String, vec::Vec};
+
+use embedded_svc::wifi::{AccessPointConfiguration, AuthMethod, Configuration, Wifi as _};
+use esp_println::println;
+use esp_storage::FlashStorage;
+use esp_wifi::wifi_interface::{timestamp, Wifi};
+use osc_receiver::provisioning::{
+    dns_reply, DhcpServer, Portal, Request, Settings, AP_IP, DHCP_CLIENT_PORT, DHCP_SERVER_PORT,
+    DNS_PORT, HTTP_PORT, MAX_REQUEST_LEN,
+};
+use smoltcp::{
+    iface::SocketHandle,
+    socket::{Socket, TcpSocket, TcpSocketBuffer, UdpSocket},
+    wire::{IpAddress, IpCidr, IpEndpoint, Ipv4Address},
+};
+
+use crate::{restart, storage};
+
+// Long enough for the saved page to reach the browser before restarting
+const RESTART_DELAY_MS: u64 = 1000;
+
+/// Serves the configuration page until new settings are saved, then restarts the receiver.
+///
+/// With `timeout_ms` the receiver also restarts to try its stored settings again once the page
+/// hasn't been used for that long, eg. because the receiver booted before the venue's access
+/// point after a power cut.
+///
+/// `tick` is called with the current time on every pass of the loop so that patterns, fades and
+/// the failsafe keep running while the page is up.
+///
+/// The DHCP client socket is swapped for the page's TCP socket and the two UDP sockets are
+/// rebound to answer DNS and DHCP, so no extra sockets are needed.
+pub fn run(
+    wifi_interface: &mut Wifi,
+    udp_socket_handles: [SocketHandle; 2],
+    flash: &mut FlashStorage,
+    settings: Settings,
+    timeout_ms: Option<u64>,
+    mut tick: impl FnMut(u64),
+) -> ! {
+    // Suggest the networks in range on the form
+    let networks = match wifi_interface.scan() {
+        Ok(access_points) => access_points
+            .into_iter()
+            .map(|access_point| String::from(access_point.ssid.as_str()))
+            .filter(|ssid| !ssid.is_empty())
+            .collect::<Vec<_>>(),
+        Err(err) => {
+            println!("Unable to scan for networks: {:?}", err);
+            Vec::new()
+        }
+    };
+
+    let ap_ssid = format!("{}-setup", settings.name);
+    let ap_config = Configuration::AccessPoint(AccessPointConfiguration {
+        ssid: ap_ssid.as_str().into(),
+        auth_method: AuthMethod::None,
+        ..Default::default()
+    });
+    wifi_interface.set_configuration(&ap_config).unwrap();
+
+    let network_interface = wifi_interface.network_interface();
+
+    let dhcp_client_handles = network_interface
+        .sockets_mut()
+        .filter_map(|(handle, socket)| match socket {
+            Socket::Dhcpv4(_) => Some(handle),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for handle in dhcp_client_handles {
+        network_interface.remove_socket(handle);
+    }
+
+    network_interface.update_ip_addrs(|addrs| {
+        addrs[0] = IpCidr::new(IpAddress::Ipv4(Ipv4Address(AP_IP)), 24);
+    });
+
+    let http_socket_handle = network_interface.add_socket(TcpSocket::new(
+        TcpSocketBuffer::new(vec![0; MAX_REQUEST_LEN]),
+        TcpSocketBuffer::new(vec![0; MAX_REQUEST_LEN]),
+    ));
+
+    let [dns_socket_handle, dhcp_socket_handle] = udp_socket_handles;
+    for (handle, port) in [
+        (dns_socket_handle, DNS_PORT),
+        (dhcp_socket_handle, DHCP_SERVER_PORT),
+    ] {
+        let socket = network_interface.get_socket::<UdpSocket>(handle);
+        socket.close();
+        socket.bind(port).unwrap();
+    }
+
+    println!(
+        "Unable to join a network. Connect to the {} network and open http://{}/ to configure the receiver",
+        ap_ssid,
+        Ipv4Address(AP_IP)
+    );
+
+    let portal = Portal::new(settings, networks);
+    let mut dhcp = DhcpServer::new();
+    let mut request = Vec::new();
+    let mut restart_at = None;
+    let mut used_at_ms = timestamp().total_millis() as u64;
+
+    loop {
+        let network_interface = wifi_interface.network_interface();
+        if let Err(err) = network_interface.poll(timestamp()) {
+            println!("Wifi Error: {:?}", err);
+        }
+
+        let now_ms = timestamp().total_millis() as u64;
+        tick(now_ms);
+        if restart_at.map_or(false, |restart_at| now_ms >= restart_at) {
+            restart();
+        }
+        if timeout_ms.map_or(false, |timeout_ms| now_ms >= used_at_ms + timeout_ms) {
+            println!("The configuration page wasn't used, trying the stored settings again");
+            restart();
+        }
+
+        let http_socket = network_interface.get_socket::<TcpSocket>(http_socket_handle);
+        if !http_socket.is_open() {
+            request.clear();
+            http_socket.listen(HTTP_PORT).unwrap();
+        }
+        if http_socket.can_recv() {
+            used_at_ms = now_ms;
+
+            let received = http_socket.recv(|bytes| {
+                request.extend_from_slice(bytes);
+                (bytes.len(), ())
+            });
+
+            let parsed = received
+                .map_err(|err| println!("Unable to read the request: {:?}", err))
+                .and_then(|()| {
+                    if request.len() > MAX_REQUEST_LEN {
+                        println!("Invalid request: The request is too large");
+                        return Err(());
+                    }
+                    Request::parse(&request).map_err(|err| println!("Invalid request: {}", err))
+                });
+            match parsed {
+                Ok(Some(parsed)) => {
+                    let (response, saved) = portal.handle(&parsed);
+
+                    if let Err(err) = http_socket.send_slice(&response.encode()) {
+                        println!("Unable to send the page: {:?}", err);
+                    }
+                    http_socket.close();
+                    request.clear();
+
+                    if let Some(saved) = saved {
+                        match storage::SETTINGS.write(flash, &saved.encode()) {
+                            Ok(()) => {
+                                println!("Saved the settings for {}", saved.ssid);
+                                restart_at = Some(now_ms + RESTART_DELAY_MS);
+                            }
+                            Err(err) => println!("Unable to save the settings: {:?}", err),
+                        }
+                    }
+                }
+                // Wait for the rest of the request
+                Ok(None) => (),
+                Err(()) => {
+                    http_socket.abort();
+                    request.clear();
+                }
+            }
+        }
+
+        // Every name resolves to the page so that it pops up as a captive portal
+        let dns_socket = network_interface.get_socket::<UdpSocket>(dns_socket_handle);
+        let dns_reply = match dns_socket.recv() {
+            Ok((query, sender)) => dns_reply(query).map(|reply| (reply, sender)),
+            Err(_) => None,
+        };
+        if let Some((reply, sender)) = dns_reply {
+            if let Err(err) = dns_socket.send_slice(&reply, sender) {
+                println!("Unable to send DNS reply: {:?}", err);
+            }
+        }
+
+        // Clients don't have an address yet, so replies are broadcast
+        let dhcp_socket = network_interface.get_socket::<UdpSocket>(dhcp_socket_handle);
+        let dhcp_reply = match dhcp_socket.recv() {
+            Ok((request, _)) => dhcp.reply(request),
+            Err(_) => None,
+        };
+        if let Some(reply) = dhcp_reply {
+            let broadcast =
+                IpEndpoint::new(IpAddress::Ipv4(Ipv4Address::BROADCAST), DHCP_CLIENT_PORT);
+            if let Err(err) = dhcp_socket.send_slice(&reply, broadcast) {
+                println!("Unable to send DHCP reply: {:?}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Records kept in flash so that they survive a power cut, eg. the Wi-Fi and LED settings, the
+//! default scene and presets.
+//!
+//! Each record lives at a fixed offset in the `nvs` partition of the default partition table and
+//! is stored as a little endian `u32` length followed by that many bytes. Erased flash reads as
+//! `0xFF`, so a length of `0xFFFFFFFF` means the record was never written.
+use alloc::vec::Vec;
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::{FlashStorage, FlashStorageError};
+
+const EMPTY: u32 = 0xFFFF_FFFF;
+
+/// A fixed region of flash holding a single record.
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    offset: u32,
+    /// The size of the region, including the length prefix
+    size: u32,
+}
+
+/// The scene shown at boot, saved with `/scene/save_default`
+pub const DEFAULT_SCENE: Record = Record {
+    offset: 0x9000,
+    size: 0x2000,
+};
+
+/// Every preset stored with `/preset/{name}/store`, encoded together
+pub const PRESETS: Record = Record {
+    offset: 0xB000,
+    size: 0x3000,
+};
+
+/// The network and receiver settings saved from the configuration page
+pub const SETTINGS: Record = Record {
+    offset: 0xE000,
+    size: 0x800,
+};
+
+/// The LED type, pins and strip settings set with `/config/led/...` or the serial console
+pub const LED_CONFIG: Record = Record {
+    offset: 0xE800,
+    size: 0x800,
+};
+
+#[derive(Debug)]
+pub enum StorageError {
+    Flash(FlashStorageError),
+    /// The record doesn't fit in its region of flash
+    TooLarge {
+        len: usize,
+        max_len: usize,
+    },
+    /// The stored length is longer than the record's region, eg. because the flash was corrupted
+    Corrupt,
+}
+
+impl From<FlashStorageError> for StorageError {
+    fn from(err: FlashStorageError) -> Self {
+        Self::Flash(err)
+    }
+}
+
+impl Record {
+    pub fn max_len(&self) -> usize {
+        self.size as usize - 4
+    }
+
+    /// Reads the record, or returns `None` if it was never written.
+    pub fn read(&self, flash: &mut FlashStorage) -> Result<Option<Vec<u8>>, StorageError> {
+        let mut len = [0; 4];
+        flash.read(self.offset, &mut len)?;
+
+        let len = u32::from_le_bytes(len);
+        if len == EMPTY {
+            return Ok(None);
+        }
+        if len as usize > self.max_len() {
+            return Err(StorageError::Corrupt);
+        }
+
+        let mut bytes = vec![0; len as usize];
+        flash.read(self.offset + 4, &mut bytes)?;
+
+        Ok(Some(bytes))
+    }
+
+    pub fn write(&self, flash: &mut FlashStorage, bytes: &[u8]) -> Result<(), StorageError> {
+        if bytes.len() > self.max_len() {
+            return Err(StorageError::TooLarge {
+                len: bytes.len(),
+                max_len: self.max_len(),
+            });
+        }
+
+        let mut record = Vec::with_capacity(bytes.len() + 4);
+        record.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        record.extend_from_slice(bytes);
+
+        flash.write(self.offset, &record)?;
+
+        Ok(())
+    }
+}
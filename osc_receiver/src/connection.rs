@@ -0,0 +1,148 @@
+//! Keeps a receiver on its Wi-Fi network, eg. the ESP32C3 which has no operating system to do it.
+//!
+//! [`Connection`] is fed the state of the link every time the receiver polls its network stack
+//! and says when to bind the sockets, close them, ask the driver to connect again or give up and
+//! start the configuration portal. Reconnect attempts back off from [`MIN_BACKOFF_MS`] to
+//! [`MAX_BACKOFF_MS`] so that a receiver doesn't hammer an access point that is restarting.
+use core::option::Option::{self, *};
+
+pub const MIN_BACKOFF_MS: u64 = 1000;
+pub const MAX_BACKOFF_MS: u64 = 32_000;
+
+/// What the Wi-Fi driver reports about the link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// Not associated with the access point
+    Down,
+    /// Associated, but DHCP hasn't given the receiver an address yet
+    Up,
+    /// Associated with an address
+    Ip([u8; 4]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// Waiting for the link to come up with an address
+    Connecting {
+        since_ms: u64,
+    },
+    Connected {
+        ip: [u8; 4],
+    },
+    /// Waiting to try connecting again after the link dropped or an attempt timed out
+    Reconnecting {
+        at_ms: u64,
+    },
+}
+
+/// What the receiver should do after a change in the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionAction {
+    /// Bind the sockets and announce the receiver at a new address
+    Bind([u8; 4]),
+    /// Close the sockets, the address they were bound for is gone
+    Unbind,
+    /// Ask the driver to connect again, restarting DHCP
+    Reconnect,
+    /// The network was never joined, so let it be configured instead
+    Provision,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Connection {
+    /// How long an attempt to connect can take before it is given up on
+    pub timeout_ms: u64,
+    state: ConnectionState,
+    /// Failed attempts since the receiver was last connected
+    attempts: u32,
+    /// Once the network has been joined the receiver keeps trying to rejoin it rather than
+    /// starting the configuration portal, so that a dropout at a venue doesn't need a phone
+    has_connected: bool,
+}
+
+impl Connection {
+    /// Starts connecting, with the driver already asked to connect.
+    pub fn new(timeout_ms: u64, now_ms: u64) -> Self {
+        Self {
+            timeout_ms,
+            state: ConnectionState::Connecting { since_ms: now_ms },
+            attempts: 0,
+            has_connected: false,
+        }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.state
+    }
+
+    pub fn ip(&self) -> Option<[u8; 4]> {
+        match self.state {
+            ConnectionState::Connected { ip } => Some(ip),
+            _ => None,
+        }
+    }
+
+    /// Moves to the next state for the link's latest status.
+    pub fn update(&mut self, link: Link, now_ms: u64) -> Option<ConnectionAction> {
+        match (self.state, link) {
+            (ConnectionState::Connected { ip }, Link::Ip(new_ip)) if ip == new_ip => None,
+            // Covers the first connection, DHCP giving the receiver a new address and the driver
+            // reconnecting on its own while waiting to reconnect
+            (_, Link::Ip(ip)) => {
+                self.state = ConnectionState::Connected { ip };
+                self.attempts = 0;
+                self.has_connected = true;
+                Some(ConnectionAction::Bind(ip))
+            }
+            // The lease was lost, so wait for DHCP to get a new one
+            (ConnectionState::Connected { .. }, Link::Up) => {
+                self.state = ConnectionState::Connecting { since_ms: now_ms };
+                Some(ConnectionAction::Unbind)
+            }
+            (ConnectionState::Connected { .. }, Link::Down) => {
+                self.state = ConnectionState::Reconnecting {
+                    at_ms: now_ms + self.backoff_ms(),
+                };
+                Some(ConnectionAction::Unbind)
+            }
+            (ConnectionState::Connecting { since_ms }, _) => {
+                if now_ms < since_ms + self.timeout_ms {
+                    return None;
+                }
+                if !self.has_connected {
+                    return Some(ConnectionAction::Provision);
+                }
+
+                self.attempts += 1;
+                self.state = ConnectionState::Reconnecting {
+                    at_ms: now_ms + self.backoff_ms(),
+                };
+                None
+            }
+            (ConnectionState::Reconnecting { at_ms }, _) => {
+                if now_ms < at_ms {
+                    return None;
+                }
+
+                self.state = ConnectionState::Connecting { since_ms: now_ms };
+                Some(ConnectionAction::Reconnect)
+            }
+        }
+    }
+
+    /// How long until [`Self::update`] next has something to do without the link changing.
+    pub fn next_update_in_ms(&self, now_ms: u64) -> Option<u64> {
+        match self.state {
+            ConnectionState::Connected { .. } => None,
+            ConnectionState::Connecting { since_ms } => {
+                Some((since_ms + self.timeout_ms).saturating_sub(now_ms))
+            }
+            ConnectionState::Reconnecting { at_ms } => Some(at_ms.saturating_sub(now_ms)),
+        }
+    }
+
+    // Doubles with every failed attempt. The shift is capped so that it can't overflow
+    fn backoff_ms(&self) -> u64 {
+        (MIN_BACKOFF_MS << self.attempts.min(16)).min(MAX_BACKOFF_MS)
+    }
+}
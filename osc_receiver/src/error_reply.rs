@@ -0,0 +1,74 @@
+//! `/error` replies describing why a message was rejected, sent back to the sender when the
+//! receiver has error replies turned on so that mistakes show up in the sender's own tools.
+//!
+//! Each reply is `/error <address> <code> <reason>`, where `<address>` is the rejected message's
+//! address (empty for packets that could not be decoded), `<code>` is one of the [`ErrorCode`]s as
+//! an int and `<reason>` is a short human readable explanation.
+use alloc::string::{String, ToString};
+use rosc::{OscMessage, OscPacket, OscType};
+
+pub const ERROR_ADDR: &str = "/error";
+
+/// Why a message was rejected. The numeric values are part of the protocol and must not change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The packet could not be decoded as OSC
+    InvalidPacket = 1,
+    /// No receiver method matches the message's address
+    UnknownAddress = 2,
+    /// The message's address is handled by a protocol that is turned off on this receiver
+    ProtocolDisabled = 3,
+    /// The `{n}` of `/led_strips/{n}` is not a number
+    InvalidStripIndex = 4,
+    /// The `{offset}` of `/led_strips/{n}/{offset}` is not a number
+    InvalidOffset = 5,
+    /// An argument has the wrong type, eg. an int sent to `/led_strips/{n}`
+    InvalidArgument = 6,
+    /// The colors run past the end of the last strip
+    StripOverflow = 7,
+    /// The universe of `/{universe}/dmx/{channel}` is not a number
+    InvalidUniverse = 8,
+    /// The channel of `/{universe}/dmx/{channel}` is not a number
+    InvalidChannel = 9,
+    /// The DMX channel is past the last LED of the last strip
+    ChannelOverflow = 10,
+    /// The receiver was unable to save to its storage, eg. a scene
+    StorageFailed = 11,
+    /// The `{name}` of `/preset/{name}` isn't 1 to 32 letters, digits, `-` or `_`
+    InvalidPresetName = 12,
+    /// No preset has been stored with the recalled name
+    UnknownPreset = 13,
+    /// The `{name}` of `/pattern/{name}` isn't one of the built in patterns
+    UnknownPattern = 14,
+}
+
+/// A rejected message, sent back to its sender as an `/error` reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageError {
+    /// The address of the rejected message
+    pub addr: String,
+    pub code: ErrorCode,
+    pub reason: String,
+}
+
+impl MessageError {
+    pub fn new(addr: &str, code: ErrorCode, reason: String) -> Self {
+        Self {
+            addr: addr.to_string(),
+            code,
+            reason,
+        }
+    }
+
+    /// The `/error` reply to send to the message's sender.
+    pub fn reply(&self) -> OscPacket {
+        OscPacket::Message(OscMessage {
+            addr: ERROR_ADDR.to_string(),
+            args: vec![
+                OscType::String(self.addr.clone()),
+                OscType::Int(self.code as i32),
+                OscType::String(self.reason.clone()),
+            ],
+        })
+    }
+}
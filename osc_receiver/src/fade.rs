@@ -0,0 +1,127 @@
+//! Fades the strips from the LEDs they showed when a fade started to a target, shared by the
+//! failsafe and preset crossfades.
+use alloc::vec::Vec;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use smart_leds::RGB8;
+
+use crate::led_strip::LedStrip;
+
+/// How often the strips are redrawn while fading
+pub(crate) const FADE_FRAME_MS: u64 = 33;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fade {
+    from: Vec<Vec<RGB8>>,
+    to: Vec<Vec<RGB8>>,
+    started_at_ms: u64,
+    fade_ms: u64,
+    /// When the strips were last redrawn
+    drawn_at_ms: Option<u64>,
+    finished: bool,
+}
+
+impl Fade {
+    /// A fade from the LEDs currently set on the strips to the colors `to` returns for each LED,
+    /// given its strip, its index on the strip and its current color.
+    pub(crate) fn new(
+        led_strips: &[LedStrip],
+        mut to: impl FnMut(usize, usize, RGB8) -> RGB8,
+        fade_ms: u64,
+        now_ms: u64,
+    ) -> Self {
+        let from = led_strips
+            .iter()
+            .map(|led_strip| led_strip.data.clone())
+            .collect::<Vec<_>>();
+        let to = from
+            .iter()
+            .enumerate()
+            .map(|(strip, leds)| {
+                leds.iter()
+                    .enumerate()
+                    .map(|(index, color)| to(strip, index, *color))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            from,
+            to,
+            started_at_ms: now_ms,
+            fade_ms,
+            drawn_at_ms: None,
+            finished: false,
+        }
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Redraws the strips at most every [`FADE_FRAME_MS`]. Returns true if the strips were
+    /// changed and need to be written to the LEDs.
+    pub(crate) fn update(&mut self, led_strips: &mut [LedStrip], now_ms: u64) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        let elapsed_ms = now_ms.saturating_sub(self.started_at_ms);
+        let finished = elapsed_ms >= self.fade_ms;
+
+        let drawn_recently = matches!(
+            self.drawn_at_ms,
+            Some(drawn_at_ms) if now_ms.saturating_sub(drawn_at_ms) < FADE_FRAME_MS
+        );
+        if drawn_recently && !finished {
+            return false;
+        }
+
+        let progress = if finished {
+            1.0
+        } else {
+            elapsed_ms as f32 / self.fade_ms as f32
+        };
+
+        for ((strip, from), to) in led_strips
+            .iter_mut()
+            .zip(self.from.iter())
+            .zip(self.to.iter())
+        {
+            for ((led, from), to) in strip.data.iter_mut().zip(from.iter()).zip(to.iter()) {
+                *led = blend(*from, *to, progress);
+            }
+        }
+
+        self.drawn_at_ms = Some(now_ms);
+        self.finished = finished;
+
+        true
+    }
+
+    /// How long until [`Fade::update`] next needs to be called, or `None` once the fade has
+    /// finished.
+    pub(crate) fn next_update_in_ms(&self, now_ms: u64) -> Option<u64> {
+        if self.finished {
+            return None;
+        }
+
+        Some(self.drawn_at_ms.map_or(0, |drawn_at_ms| {
+            (drawn_at_ms + FADE_FRAME_MS).saturating_sub(now_ms)
+        }))
+    }
+}
+
+pub(crate) fn blend(from: RGB8, to: RGB8, progress: f32) -> RGB8 {
+    let channel = |from: u8, to: u8| {
+        let value = from as f32 + (to as f32 - from as f32) * progress;
+        // Round to the nearest value without `f32::round`, which isn't available without std
+        (value + 0.5) as u8
+    };
+
+    RGB8 {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+    }
+}
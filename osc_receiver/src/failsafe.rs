@@ -0,0 +1,270 @@
+//! What the strips show when the controller goes silent, eg. because the sending laptop crashed.
+//!
+//! Once no frames have arrived for the timeout the failsafe engages and fades the strips from the
+//! last frame to black, a fallback scene, the default scene or a preset, switches to a built in
+//! pattern, or holds the last frame. The controller's next frame disengages it again. Nothing
+//! happens before the first frame arrives so that the power-on scene stays up until a controller
+//! connects.
+use alloc::string::String;
+use alloc::vec::Vec;
+use anyhow::{anyhow, bail, Result};
+use core::fmt;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use log::info;
+use smart_leds::RGB8;
+
+use crate::fade::Fade;
+use crate::led_strip::LedStrip;
+use crate::pattern::{PatternKind, Patterns};
+use crate::preset::{is_valid_name, Presets};
+use crate::scene::Scene;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailsafeAction {
+    /// Keep showing the last frame
+    Hold,
+    /// Fade to black
+    Black,
+    /// Fade to a scene of colors repeated along the strips, eg. a single dim color
+    Scene(Vec<RGB8>),
+    /// Switch to a built in pattern
+    Pattern(PatternKind),
+    /// Fade to the default scene saved with `/scene/save_default`
+    DefaultScene,
+    /// Fade to a stored preset
+    Preset(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failsafe {
+    /// How long without frames before the failsafe engages. Zero disables the failsafe
+    pub timeout_ms: u64,
+    /// How long the fade from the last frame takes
+    pub fade_ms: u64,
+    pub action: FailsafeAction,
+    /// `None` until the first frame arrives so that a power-on scene isn't faded out
+    last_frame_at_ms: Option<u64>,
+    /// The scene faded to by the `DefaultScene` and `Preset` actions, see [`Failsafe::load_scene`]
+    scene: Option<Scene>,
+    engaged: Option<Engaged>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Engaged {
+    /// The fade from the last frame from the controller, or `None` if a pattern was started
+    fade: Option<Fade>,
+}
+
+impl FailsafeAction {
+    /// Parses `hold`, `black`, `pattern:{name}`, `default_scene`, `preset:{name}` or a comma
+    /// separated list of `#rrggbb` scene colors.
+    pub fn parse(action: &str) -> Result<Self> {
+        let action = action.trim();
+
+        if let Some(pattern) = action.strip_prefix("pattern:") {
+            return PatternKind::parse(pattern)
+                .map(Self::Pattern)
+                .ok_or_else(|| anyhow!("Unknown pattern {:?}", pattern));
+        }
+        if let Some(name) = action.strip_prefix("preset:") {
+            if !is_valid_name(name) {
+                bail!("Invalid preset name {:?}", name);
+            }
+            return Ok(Self::Preset(name.into()));
+        }
+
+        match action {
+            "hold" => Ok(Self::Hold),
+            "black" => Ok(Self::Black),
+            "default_scene" => Ok(Self::DefaultScene),
+            colors => Ok(Self::Scene(
+                colors
+                    .split(',')
+                    .map(parse_hex_color)
+                    .collect::<Result<Vec<_>>>()?,
+            )),
+        }
+    }
+
+    /// The color the failsafe fades the LED at `index` to, counting across every strip, or `None`
+    /// for actions that don't fade to colors.
+    fn color(&self, index: usize) -> Option<RGB8> {
+        match self {
+            Self::Black => Some(RGB8::default()),
+            Self::Scene(colors) if colors.is_empty() => Some(RGB8::default()),
+            Self::Scene(colors) => Some(colors[index % colors.len()]),
+            Self::Hold | Self::Pattern(_) | Self::DefaultScene | Self::Preset(_) => None,
+        }
+    }
+}
+
+impl Failsafe {
+    pub fn new(timeout_ms: u64, fade_ms: u64, action: FailsafeAction) -> Self {
+        Self {
+            timeout_ms,
+            fade_ms,
+            action,
+            last_frame_at_ms: None,
+            scene: None,
+            engaged: None,
+        }
+    }
+
+    /// Picks the scene the `DefaultScene` and `Preset` actions fade to, since the receiver stores
+    /// the scenes. Call it again whenever the default scene is saved or a preset is stored. The
+    /// failsafe fades to black if the scene hasn't been stored.
+    pub fn load_scene(&mut self, default_scene: Option<&Scene>, presets: &Presets) {
+        self.scene = match &self.action {
+            FailsafeAction::DefaultScene => default_scene.cloned(),
+            FailsafeAction::Preset(name) => presets.get(name).cloned(),
+            _ => None,
+        };
+    }
+
+    /// Records a frame from the controller, disengaging the failsafe.
+    pub fn frame_received(&mut self, now_ms: u64) {
+        if self.engaged.take().is_some() {
+            info!("Frames received again, disengaging the failsafe");
+        }
+        self.last_frame_at_ms = Some(now_ms);
+    }
+
+    /// Disengages the failsafe and waits for the next frame before timing out again, eg. after a
+    /// preset was recalled so that it isn't faded out.
+    pub fn suspend(&mut self) {
+        self.engaged = None;
+        self.last_frame_at_ms = None;
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        self.engaged.is_some()
+    }
+
+    /// Engages the failsafe once the timeout has passed since the last frame and fades the strips,
+    /// or starts the failsafe's pattern. Returns true if the strips were changed and need to be
+    /// written to the LEDs.
+    pub fn update(
+        &mut self,
+        led_strips: &mut [LedStrip],
+        patterns: &mut Patterns,
+        now_ms: u64,
+    ) -> bool {
+        if self.timeout_ms == 0 || self.action == FailsafeAction::Hold {
+            return false;
+        }
+
+        let timed_out = matches!(
+            self.last_frame_at_ms,
+            Some(last_frame_at_ms) if now_ms.saturating_sub(last_frame_at_ms) >= self.timeout_ms
+        );
+
+        let engaged = match &mut self.engaged {
+            Some(engaged) => engaged,
+            None if timed_out => {
+                info!(
+                    "No frames received for {}ms, engaging the failsafe",
+                    self.timeout_ms
+                );
+                let fade = match &self.action {
+                    FailsafeAction::Pattern(pattern) => {
+                        patterns.start(*pattern, now_ms);
+                        None
+                    }
+                    FailsafeAction::DefaultScene | FailsafeAction::Preset(_) => {
+                        let scene = self.scene.as_ref();
+                        let to = |strip: usize, index: usize, _| {
+                            scene
+                                .and_then(|scene| scene.strips.get(strip)?.get(index))
+                                .copied()
+                                .unwrap_or_default()
+                        };
+                        Some(Fade::new(led_strips, to, self.fade_ms, now_ms))
+                    }
+                    action => {
+                        let mut index = 0;
+                        let to = |_, _, color| {
+                            let to = action.color(index).unwrap_or(color);
+                            index += 1;
+                            to
+                        };
+                        Some(Fade::new(led_strips, to, self.fade_ms, now_ms))
+                    }
+                };
+                self.engaged.insert(Engaged { fade })
+            }
+            None => return false,
+        };
+
+        match &mut engaged.fade {
+            Some(fade) => fade.update(led_strips, now_ms),
+            None => false,
+        }
+    }
+
+    /// How long until [`Failsafe::update`] next needs to be called, or `None` if it has nothing
+    /// left to do until the next frame arrives.
+    pub fn next_update_in_ms(&self, now_ms: u64) -> Option<u64> {
+        if self.timeout_ms == 0 || self.action == FailsafeAction::Hold {
+            return None;
+        }
+
+        match &self.engaged {
+            Some(engaged) => engaged
+                .fade
+                .as_ref()
+                .and_then(|fade| fade.next_update_in_ms(now_ms)),
+            None => self.last_frame_at_ms.map(|last_frame_at_ms| {
+                (last_frame_at_ms + self.timeout_ms).saturating_sub(now_ms)
+            }),
+        }
+    }
+}
+
+/// Parses a `#rrggbb` hex color.
+pub fn parse_hex_color(color: &str) -> Result<RGB8> {
+    let hex = color.trim().trim_start_matches('#');
+
+    if hex.len() != 6 {
+        bail!(
+            "Invalid color {:?}. Expected a hex color like #ff8000",
+            color
+        );
+    }
+
+    let rgb = u32::from_str_radix(hex, 16).map_err(|_| {
+        anyhow!(
+            "Invalid color {:?}. Expected a hex color like #ff8000",
+            color
+        )
+    })?;
+
+    Ok(RGB8 {
+        r: (rgb >> 16) as u8,
+        g: (rgb >> 8) as u8,
+        b: rgb as u8,
+    })
+}
+
+impl fmt::Display for FailsafeAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Hold => write!(f, "hold"),
+            Self::Black => write!(f, "black"),
+            Self::Pattern(pattern) => write!(f, "pattern:{}", pattern),
+            Self::DefaultScene => write!(f, "default_scene"),
+            Self::Preset(name) => write!(f, "preset:{}", name),
+            Self::Scene(colors) => {
+                for (i, color) in colors.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(
+                        f,
+                        "{}#{:02x}{:02x}{:02x}",
+                        separator, color.r, color.g, color.b
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
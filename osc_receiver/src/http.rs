@@ -0,0 +1,188 @@
+//! A minimal blocking HTTP server for the receivers' web endpoints, with support for upgrading
+//! connections to WebSockets.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use log::{trace, warn};
+use tungstenite::protocol::Role;
+use tungstenite::WebSocket;
+
+// Larger request heads are rejected
+const MAX_HEAD_LENGTH: usize = 8 * 1024;
+
+/// How long [`poll_websocket`] waits for a message from the client
+pub const WEBSOCKET_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    /// The request path without the query string
+    pub path: String,
+    pub query: Option<String>,
+    /// Header names are lowercased
+    pub headers: HashMap<String, String>,
+    /// Bytes the client sent after the head that were read along with it, eg. the start of the
+    /// first WebSocket frame
+    pub buffered: Vec<u8>,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.header("upgrade")
+            .map(|upgrade| upgrade.eq_ignore_ascii_case("websocket"))
+            .unwrap_or(false)
+    }
+}
+
+/// Listens on `addr` and handles each connection on its own thread.
+pub fn serve<F>(addr: SocketAddr, handler: F) -> Result<thread::JoinHandle<()>>
+where
+    F: Fn(Request, TcpStream) -> Result<()> + Send + Sync + Clone + 'static,
+{
+    let listener =
+        TcpListener::bind(addr).with_context(|| format!("Unable to bind to {}", addr))?;
+
+    let handle = thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    warn!("HTTP connection failed: {:?}", err);
+                    continue;
+                }
+            };
+            let handler = handler.clone();
+
+            thread::spawn(move || {
+                let result = read_request(&mut stream).and_then(|request| {
+                    trace!("HTTP {} {}", request.method, request.path);
+                    handler(request, stream)
+                });
+
+                if let Err(err) = result {
+                    trace!("HTTP connection closed: {:?}", err);
+                }
+            });
+        }
+    });
+
+    Ok(handle)
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request> {
+    // Reading stops at the limit, so a head that is too long ends without its empty line
+    let mut reader = BufReader::new((&*stream).take(MAX_HEAD_LENGTH as u64));
+    let mut lines = Vec::new();
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            bail!("Invalid HTTP request");
+        }
+
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines
+        .first()
+        .ok_or_else(|| anyhow!("Missing HTTP request line"))?
+        .split(' ');
+
+    let method = request_line.next().unwrap_or_default().to_string();
+    let target = request_line.next().unwrap_or("/");
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    let headers = lines[1..]
+        .iter()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    Ok(Request {
+        method,
+        path,
+        query,
+        headers,
+        buffered: reader.buffer().to_vec(),
+    })
+}
+
+/// Writes a complete HTTP response and closes the connection.
+pub fn respond(mut stream: TcpStream, status: &str, content_type: &str, body: &[u8]) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len(),
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    Ok(())
+}
+
+pub fn not_found(stream: TcpStream) -> Result<()> {
+    respond(stream, "404 Not Found", "text/plain", b"Not Found")
+}
+
+/// Completes the WebSocket handshake for an upgrade request.
+pub fn accept_websocket(request: &Request, mut stream: TcpStream) -> Result<WebSocket<TcpStream>> {
+    let key = request
+        .header("sec-websocket-key")
+        .ok_or_else(|| anyhow!("Missing Sec-WebSocket-Key header"))?;
+
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        tungstenite::handshake::derive_accept_key(key.as_bytes()),
+    )?;
+    stream.flush()?;
+
+    Ok(WebSocket::from_partially_read(
+        stream,
+        request.buffered.clone(),
+        Role::Server,
+        None,
+    ))
+}
+
+/// Waits up to [`WEBSOCKET_POLL_INTERVAL`] for a message from the client so that pings are
+/// answered and closes are completed. Other messages are ignored. Returns false once the client
+/// has closed the connection.
+pub fn poll_websocket(websocket: &mut WebSocket<TcpStream>) -> Result<bool> {
+    websocket
+        .get_ref()
+        .set_read_timeout(Some(WEBSOCKET_POLL_INTERVAL))?;
+
+    match websocket.read() {
+        Ok(_) => Ok(true),
+        Err(tungstenite::Error::Io(err))
+            if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+        {
+            Ok(true)
+        }
+        Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => {
+            Ok(false)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
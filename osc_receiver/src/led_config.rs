@@ -0,0 +1,440 @@
+//! The LED hardware settings of receivers that are flashed with a single firmware image, eg. the
+//! ESP32C3, so that the same image fits every board.
+//!
+//! `/config/led/{setting} value` changes a setting and `/config/led/apply` restarts the receiver
+//! with the changed settings, since the LEDs' driver can't be swapped while it is running. The
+//! same commands can be typed on the serial console as `led {setting} {value}` and `led apply`,
+//! and `led` on its own prints the current settings. The receiver stores the settings and applies
+//! them at boot.
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use anyhow::{anyhow, bail, Result};
+use core::fmt;
+use core::iter::Iterator;
+use core::ops::RangeInclusive;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use rosc::{OscMessage, OscPacket, OscType};
+use smart_leds::RGB8;
+
+use crate::error_reply::{ErrorCode, MessageError};
+use crate::led_strip::{RGB8SmartLedsWrite, STRIP_LENGTH};
+
+pub const LED_CONFIG_PREFIX: &str = "/config/led/";
+pub const APPLY_ADDR: &str = "/config/led/apply";
+
+/// The longest strip that can be configured, limited by the receiver's memory
+pub const MAX_LENGTH: usize = 2000;
+const MIN_SPI_FREQUENCY_HZ: u32 = 100_000;
+const MAX_SPI_FREQUENCY_HZ: u32 = 40_000_000;
+
+// Bumped whenever the encoded settings change
+const LED_CONFIG_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedType {
+    Apa102,
+    Ws2812b,
+}
+
+pub const LED_TYPES: [LedType; 2] = [LedType::Apa102, LedType::Ws2812b];
+
+/// The order the strip's channels are sent in, relative to the order the LED type normally uses.
+///
+/// `GRB` sends the green value where the LED type normally sends red and the red value where it
+/// normally sends green, so a strip that shows red as green is fixed by setting `GRB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorOrder {
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+pub const COLOR_ORDERS: [ColorOrder; 6] = [
+    ColorOrder::Rgb,
+    ColorOrder::Rbg,
+    ColorOrder::Grb,
+    ColorOrder::Gbr,
+    ColorOrder::Brg,
+    ColorOrder::Bgr,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedSetting {
+    Type,
+    SclkPin,
+    MosiPin,
+    SpiFrequency,
+    Length,
+    ColorOrder,
+}
+
+pub const LED_SETTINGS: [LedSetting; 6] = [
+    LedSetting::Type,
+    LedSetting::SclkPin,
+    LedSetting::MosiPin,
+    LedSetting::SpiFrequency,
+    LedSetting::Length,
+    LedSetting::ColorOrder,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedConfig {
+    pub led_type: LedType,
+    /// The GPIO the SPI clock is on, only used by APA102 strips
+    pub sclk_pin: u8,
+    /// The GPIO the SPI data is on
+    pub mosi_pin: u8,
+    pub spi_frequency_hz: u32,
+    pub length: usize,
+    pub color_order: ColorOrder,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedConfigCommand {
+    /// `/config/led/{setting} value` or `led {setting} {value}`
+    Set { setting: LedSetting, value: String },
+    /// `/config/led/apply` or `led apply`
+    Apply,
+    /// `led`
+    Show,
+}
+
+/// Writes to the LEDs with their channels in a different order.
+pub struct ColorOrdered {
+    pub smart_led: Box<dyn RGB8SmartLedsWrite>,
+    pub color_order: ColorOrder,
+}
+
+impl LedType {
+    pub fn parse(name: &str) -> Option<Self> {
+        LED_TYPES
+            .iter()
+            .copied()
+            .find(|led_type| led_type.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Apa102 => "APA102",
+            Self::Ws2812b => "WS2812B",
+        }
+    }
+
+    /// The fastest SPI clock the LED type reliably works at. APA102 strips should work up to
+    /// 12 MHz but depending on wiring interference may limit that, and WS2812B strips are driven
+    /// at a fixed rate over SPI.
+    pub fn default_spi_frequency_hz(&self) -> u32 {
+        match self {
+            Self::Apa102 => 12_000_000,
+            Self::Ws2812b => 3_000_000,
+        }
+    }
+
+    /// The SPI clocks the LED type can be driven at. WS2812B strips need between 2 and 3.8 MHz to
+    /// encode each bit with the right timing, see `ws2812_spi::Ws2812::new`.
+    pub fn spi_frequency_range_hz(&self) -> RangeInclusive<u32> {
+        match self {
+            Self::Apa102 => MIN_SPI_FREQUENCY_HZ..=MAX_SPI_FREQUENCY_HZ,
+            Self::Ws2812b => 2_000_000..=3_800_000,
+        }
+    }
+}
+
+impl ColorOrder {
+    pub fn parse(name: &str) -> Option<Self> {
+        COLOR_ORDERS
+            .iter()
+            .copied()
+            .find(|order| order.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rgb => "RGB",
+            Self::Rbg => "RBG",
+            Self::Grb => "GRB",
+            Self::Gbr => "GBR",
+            Self::Brg => "BRG",
+            Self::Bgr => "BGR",
+        }
+    }
+
+    /// Reorders a color's channels for sending to the strip.
+    pub fn apply(&self, color: RGB8) -> RGB8 {
+        let RGB8 { r, g, b } = color;
+        let (r, g, b) = match self {
+            Self::Rgb => (r, g, b),
+            Self::Rbg => (r, b, g),
+            Self::Grb => (g, r, b),
+            Self::Gbr => (g, b, r),
+            Self::Brg => (b, r, g),
+            Self::Bgr => (b, g, r),
+        };
+
+        RGB8 { r, g, b }
+    }
+}
+
+impl LedSetting {
+    pub fn parse(name: &str) -> Option<Self> {
+        LED_SETTINGS
+            .iter()
+            .copied()
+            .find(|setting| setting.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Type => "type",
+            Self::SclkPin => "sclk_pin",
+            Self::MosiPin => "mosi_pin",
+            Self::SpiFrequency => "spi_frequency",
+            Self::Length => "length",
+            Self::ColorOrder => "color_order",
+        }
+    }
+}
+
+impl Default for LedConfig {
+    fn default() -> Self {
+        Self::new(LedType::Apa102)
+    }
+}
+
+impl LedConfig {
+    /// The settings for the wiring of the original boards, with the LEDs on GPIO6 and GPIO7.
+    pub fn new(led_type: LedType) -> Self {
+        Self {
+            led_type,
+            sclk_pin: 6,
+            mosi_pin: 7,
+            spi_frequency_hz: led_type.default_spi_frequency_hz(),
+            length: STRIP_LENGTH,
+            color_order: ColorOrder::Rgb,
+        }
+    }
+
+    /// Changes a setting. Changing the LED type also changes the SPI frequency to the new type's
+    /// default.
+    pub fn set(&mut self, setting: LedSetting, value: &str) -> Result<()> {
+        let value = value.trim();
+        let mut config = self.clone();
+
+        match setting {
+            LedSetting::Type => {
+                let led_type = LedType::parse(value).ok_or_else(|| {
+                    anyhow!(
+                        "Invalid LED type {:?}. Must be either 'WS2812B' or 'APA102'",
+                        value
+                    )
+                })?;
+                config.led_type = led_type;
+                config.spi_frequency_hz = led_type.default_spi_frequency_hz();
+            }
+            LedSetting::SclkPin => config.sclk_pin = parse_number(setting, value)?,
+            LedSetting::MosiPin => config.mosi_pin = parse_number(setting, value)?,
+            LedSetting::SpiFrequency => config.spi_frequency_hz = parse_number(setting, value)?,
+            LedSetting::Length => config.length = parse_number(setting, value)?,
+            LedSetting::ColorOrder => {
+                config.color_order = ColorOrder::parse(value).ok_or_else(|| {
+                    anyhow!(
+                        "Invalid color order {:?}. Must be RGB, RBG, GRB, GBR, BRG or BGR",
+                        value
+                    )
+                })?
+            }
+        }
+
+        config.validate()?;
+        *self = config;
+
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.sclk_pin == self.mosi_pin {
+            bail!("sclk_pin and mosi_pin must be different pins");
+        }
+        let spi_frequency_range_hz = self.led_type.spi_frequency_range_hz();
+        if !spi_frequency_range_hz.contains(&self.spi_frequency_hz) {
+            bail!(
+                "Invalid spi_frequency {}. {} strips need {} to {} Hz",
+                self.spi_frequency_hz,
+                self.led_type.name(),
+                spi_frequency_range_hz.start(),
+                spi_frequency_range_hz.end()
+            );
+        }
+        if !(1..=MAX_LENGTH).contains(&self.length) {
+            bail!(
+                "Invalid length {}. Must be 1 to {} LEDs",
+                self.length,
+                MAX_LENGTH
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(11);
+        bytes.push(LED_CONFIG_VERSION);
+        bytes.push(self.led_type as u8);
+        bytes.push(self.sclk_pin);
+        bytes.push(self.mosi_pin);
+        bytes.extend_from_slice(&self.spi_frequency_hz.to_le_bytes());
+        bytes.extend_from_slice(&(self.length as u16).to_le_bytes());
+        bytes.push(self.color_order as u8);
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let bytes = match bytes {
+            [LED_CONFIG_VERSION, bytes @ ..] if bytes.len() == 10 => bytes,
+            _ => bail!("Unsupported LED settings"),
+        };
+
+        let config = Self {
+            led_type: *LED_TYPES
+                .get(bytes[0] as usize)
+                .ok_or_else(|| anyhow!("Invalid LED type"))?,
+            sclk_pin: bytes[1],
+            mosi_pin: bytes[2],
+            spi_frequency_hz: u32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]),
+            length: u16::from_le_bytes([bytes[7], bytes[8]]) as usize,
+            color_order: *COLOR_ORDERS
+                .get(bytes[9] as usize)
+                .ok_or_else(|| anyhow!("Invalid color order"))?,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// The current value of a setting, as it would be set.
+    pub fn get(&self, setting: LedSetting) -> String {
+        match setting {
+            LedSetting::Type => self.led_type.name().to_string(),
+            LedSetting::SclkPin => self.sclk_pin.to_string(),
+            LedSetting::MosiPin => self.mosi_pin.to_string(),
+            LedSetting::SpiFrequency => self.spi_frequency_hz.to_string(),
+            LedSetting::Length => self.length.to_string(),
+            LedSetting::ColorOrder => self.color_order.name().to_string(),
+        }
+    }
+}
+
+impl LedConfigCommand {
+    /// Parses a `/config/led/...` message. Returns `None` if the packet isn't an LED config
+    /// message and an error to reply with if it is one but is invalid.
+    pub fn parse(packet: &OscPacket) -> Option<Result<Self, MessageError>> {
+        let (addr, args) = match packet {
+            OscPacket::Message(OscMessage { addr, args }) => (addr.as_str(), &args[..]),
+            _ => return None,
+        };
+
+        let name = addr.strip_prefix(LED_CONFIG_PREFIX)?;
+        if addr == APPLY_ADDR {
+            return Some(Ok(Self::Apply));
+        }
+
+        let setting = match LedSetting::parse(name) {
+            Some(setting) => setting,
+            None => {
+                return Some(Err(MessageError::new(
+                    addr,
+                    ErrorCode::UnknownAddress,
+                    format!("Unknown LED setting: {}", name),
+                )))
+            }
+        };
+
+        let value = match args {
+            [OscType::String(value)] => value.clone(),
+            [OscType::Int(value)] => value.to_string(),
+            [OscType::Long(value)] => value.to_string(),
+            _ => {
+                return Some(Err(MessageError::new(
+                    addr,
+                    ErrorCode::InvalidArgument,
+                    format!("Expected a single value for {}", setting.name()),
+                )))
+            }
+        };
+
+        Some(Ok(Self::Set { setting, value }))
+    }
+
+    /// Parses a line typed on the serial console. Returns `None` if the line isn't an `led`
+    /// command.
+    pub fn parse_line(line: &str) -> Option<Result<Self>> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("led") {
+            return None;
+        }
+
+        let command = match (words.next(), words.next(), words.next()) {
+            (None, _, _) => Ok(Self::Show),
+            (Some("apply"), None, _) => Ok(Self::Apply),
+            (Some(name), Some(value), None) => match LedSetting::parse(name) {
+                Some(setting) => Ok(Self::Set {
+                    setting,
+                    value: value.to_string(),
+                }),
+                None => Err(anyhow!("Unknown LED setting: {}", name)),
+            },
+            _ => Err(anyhow!(
+                "Usage: led, led apply or led {{setting}} {{value}}"
+            )),
+        };
+
+        Some(command)
+    }
+}
+
+impl RGB8SmartLedsWrite for ColorOrdered {
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        let color_order = self.color_order;
+
+        self.smart_led
+            .write_rgb8(&mut iterator.map(|color| color_order.apply(color)))
+    }
+}
+
+fn parse_number<T: core::str::FromStr>(setting: LedSetting, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| anyhow!("Invalid {} {:?}. Expected a number", setting.name(), value))
+}
+
+impl fmt::Display for LedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for ColorOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Lists the settings as the console commands that set them.
+impl fmt::Display for LedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, setting) in LED_SETTINGS.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "led {} {}", setting.name(), self.get(*setting))?;
+        }
+
+        Ok(())
+    }
+}
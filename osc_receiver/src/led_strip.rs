@@ -1,5 +1,7 @@
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use anyhow::Result;
+#[cfg(not(feature = "std"))]
 use core::convert::Infallible;
 use core::default::Default;
 use core::iter::Iterator;
@@ -10,13 +12,15 @@ use core::result::Result::*;
 use embedded_hal::blocking::spi::Write;
 use embedded_hal::spi::FullDuplex;
 use log::{info, trace, warn};
-use rosc::{OscPacket, OscType};
+use rosc::{OscMessage, OscPacket, OscType};
 use smart_leds::{SmartLedsWrite, RGB8};
 
+use crate::error_reply::{ErrorCode, MessageError};
+
 // pub const STRIP_LENGTH: usize = 450;
 pub const STRIP_LENGTH: usize = 450;
 
-type LedStripData = [RGB8; STRIP_LENGTH];
+type LedStripData = Vec<RGB8>;
 
 pub trait RGB8SmartLedsWrite {
     fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()>;
@@ -35,21 +39,38 @@ where
     }
 }
 
+#[cfg(not(feature = "std"))]
 impl<SPI> RGB8SmartLedsWrite for apa102_spi::Apa102<SPI>
 where
     SPI: Write<u8, Error = Infallible>,
 {
     fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
-        Ok(self.write(iterator).unwrap())
+        self.write(iterator).unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl<SPI, E> RGB8SmartLedsWrite for ws2812_spi::Ws2812<SPI>
+where
+    SPI: FullDuplex<u8, Error = E>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        use anyhow::Context;
+
+        self.write(iterator).context("Writing to LED SPI port")
     }
 }
 
+#[cfg(not(feature = "std"))]
 impl<SPI> RGB8SmartLedsWrite for ws2812_spi::Ws2812<SPI>
 where
     SPI: FullDuplex<u8, Error = Infallible>,
 {
     fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
-        Ok(self.write(iterator).unwrap())
+        self.write(iterator).unwrap();
+        Ok(())
     }
 }
 
@@ -60,114 +81,145 @@ pub struct LedStrip<'a> {
 
 impl<'a> LedStrip<'a> {
     pub fn new(smart_led: &'a mut dyn RGB8SmartLedsWrite) -> Self {
+        Self::with_length(smart_led, STRIP_LENGTH)
+    }
+
+    pub fn with_length(smart_led: &'a mut dyn RGB8SmartLedsWrite, length: usize) -> Self {
         Self {
             smart_led,
-            data: [RGB8::default(); STRIP_LENGTH],
+            data: vec![RGB8::default(); length],
         }
     }
 
-    pub fn update(led_strips: &mut Vec<LedStrip>, osc_packet: OscPacket) -> () {
-        receive_osc_packet(
-            osc_packet,
-            led_strips.iter_mut().map(|led_strip| &mut led_strip.data),
-        );
+    /// Applies a packet to the strips and writes every strip to its LEDs. If a strip fails to
+    /// write the remaining strips are still written and the first error is returned.
+    pub fn update(led_strips: &mut Vec<LedStrip>, osc_packet: OscPacket) -> Result<()> {
+        Self::update_with_errors(led_strips, osc_packet, &mut Vec::new())
+    }
+
+    /// Like [`LedStrip::update`], also collecting why each rejected message in the packet was
+    /// rejected into `errors` so that they can be reported to the sender.
+    pub fn update_with_errors(
+        led_strips: &mut Vec<LedStrip>,
+        osc_packet: OscPacket,
+        errors: &mut Vec<MessageError>,
+    ) -> Result<()> {
+        Self::apply(led_strips, osc_packet, errors);
+
+        Self::write_all(led_strips)
+    }
+
+    /// Applies a packet to the strips without writing them to the LEDs, collecting why each
+    /// rejected message was rejected into `errors`. Returns true if any LEDs were set, so that
+    /// packets that only contain rejected messages aren't mistaken for frames.
+    pub fn apply(
+        led_strips: &mut [LedStrip],
+        osc_packet: OscPacket,
+        errors: &mut Vec<MessageError>,
+    ) -> bool {
+        let mut strips = led_strips
+            .iter_mut()
+            .map(|led_strip| &mut led_strip.data)
+            .collect::<Vec<_>>();
+        let mut leds_set = false;
+
+        receive_osc_packet(osc_packet, &mut strips, errors, &mut leds_set);
+
+        leds_set
+    }
+
+    /// Writes every strip to its LEDs. If a strip fails to write the remaining strips are still
+    /// written and the first error is returned.
+    pub fn write_all(led_strips: &mut [LedStrip]) -> Result<()> {
+        let mut result = Ok(());
 
         for led_strip in led_strips.iter_mut() {
             // // This seems to fix Store Prohibited errors on the esp32
             // delay::Delay::new().delay_us(100u32);
 
-            led_strip
-                .smart_led
-                .write_rgb8(&mut led_strip.data.iter().cloned())
-                .unwrap();
+            let written = led_strip.write();
+            if result.is_ok() {
+                result = written;
+            }
         }
 
-        trace!("LED Strips ({:?}) updated", &led_strips.len());
+        trace!("LED Strips ({:?}) updated", led_strips.len());
+
+        result
+    }
+
+    /// Writes the strip's current data to its LEDs
+    pub fn write(&mut self) -> Result<()> {
+        self.smart_led.write_rgb8(&mut self.data.iter().cloned())
     }
 }
 
-fn receive_osc_packet<'a, I>(
+/// Applies a packet to the strips. The messages in a bundle are applied in order, ignoring the
+/// bundle's time tag, so that a frame split into several messages is displayed all at once.
+/// `leds_set` is set once any LED has been set.
+fn receive_osc_packet(
     packet: OscPacket,
-    mut strips: I,
+    strips: &mut [&mut LedStripData],
+    errors: &mut Vec<MessageError>,
+    leds_set: &mut bool,
     // tx: &mut esp32_hal::serial::Tx<esp32::UART0>,
-) where
-    I: Iterator<Item = &'a mut LedStripData>,
-{
-    use rosc::{OscMessage, OscType::Float};
-
-    let (addr, args) = match &packet {
-        OscPacket::Message(OscMessage { addr, args }) => (
-            addr.trim_start_matches('/').split('/').collect::<Vec<_>>(),
-            args,
-        ),
-        _ => {
-            // info!("Unsupported packet received: {:?}", packet);
-            warn!("Unsupported packet received");
-            return;
+) {
+    match packet {
+        OscPacket::Message(message) => {
+            if let Err((code, reason)) = receive_osc_message(&message, strips, leds_set) {
+                errors.push(MessageError::new(&message.addr, code, reason));
+            }
         }
-    };
-
-    match (&addr[..], &args[..]) {
-        (["led_strips", led_strip_index], input) => {
-            let _led_strip_index: usize = match led_strip_index.parse() {
-                Ok(led_strip_index) => led_strip_index,
-                Err(_err) => {
-                    warn!("Invalid led_strip_index: {:?}", led_strip_index);
-                    return;
-                }
-            };
-
-            let mut current_strip = None;
-
-            for osc_type in input.into_iter() {
-                match osc_type {
-                    OscType::Color(c) => {
-                        // If the current LED strip ends before the next index i then reset the index and go to
-                        // the next LED strip
-                        if current_strip
-                            .as_ref()
-                            .map(|(strip, i): &(&mut LedStripData, usize)| *i >= strip.len() - 1)
-                            .unwrap_or(true)
-                        {
-                            if let Some(strip) = strips.next() {
-                                current_strip = Some((strip, 0usize));
-                            }
-                        }
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                receive_osc_packet(packet, strips, errors, leds_set);
+            }
+        }
+    }
+}
 
-                        if let Some((led_strip, i)) = current_strip.as_mut() {
-                            led_strip[*i].r = c.red;
-                            led_strip[*i].g = c.green;
-                            led_strip[*i].b = c.blue;
+/// Applies a message to the strips, returning the first reason it was rejected. The valid parts of
+/// a partly invalid message are still applied.
+fn receive_osc_message(
+    message: &OscMessage,
+    strips: &mut [&mut LedStripData],
+    leds_set: &mut bool,
+) -> Result<(), (ErrorCode, String)> {
+    use rosc::OscType::Float;
 
-                            // dbg!(&i);
+    let addr = message
+        .addr
+        .trim_start_matches('/')
+        .split('/')
+        .collect::<Vec<_>>();
+    let args = &message.args;
 
-                            *i += 1;
-                        } else {
-                            warn!("Input to /led_strips exceeded number of LED strips");
-                        }
-                    }
-                    osc_type => {
-                        warn!(
-                            "Invalid input to /led_strips. Expected Color, received: {:?}",
-                            osc_type
-                        )
-                    }
-                }
-            }
+    match (&addr[..], &args[..]) {
+        (["led_strips", led_strip_index], input) => {
+            return receive_colors(led_strip_index, "0", input, strips, leds_set);
+        }
+        (["led_strips", led_strip_index, offset], input) => {
+            return receive_colors(led_strip_index, offset, input, strips, leds_set);
         }
         ([universe, "dmx", channel_index], [Float(value)]) => {
             let universe: usize = match universe.parse() {
                 Ok(universe) => universe,
                 Err(_err) => {
                     warn!("Invalid DMX Universe: {:?}", universe);
-                    return;
+                    return Err((
+                        ErrorCode::InvalidUniverse,
+                        format!("Invalid DMX universe: {:?}", universe),
+                    ));
                 }
             };
             let channel_index: usize = match channel_index.parse() {
                 Ok(channel_index) => channel_index,
                 Err(_err) => {
                     warn!("Invalid DMX channel: {:?}", channel_index);
-                    return;
+                    return Err((
+                        ErrorCode::InvalidChannel,
+                        format!("Invalid DMX channel: {:?}", channel_index),
+                    ));
                 }
             };
 
@@ -176,7 +228,7 @@ fn receive_osc_packet<'a, I>(
             let global_led_index = (color_and_led_index - color_index) / 3;
 
             let mut leds_before_strip = 0;
-            let strip = strips.find_map(|strip| {
+            let strip = strips.iter_mut().find_map(|strip| {
                 if leds_before_strip <= global_led_index
                     && leds_before_strip + strip.len() > global_led_index
                 {
@@ -191,18 +243,27 @@ fn receive_osc_packet<'a, I>(
                 Some(strip) => strip,
                 None => {
                     warn!("LED global index overflow: {:?}", global_led_index);
-                    return;
+                    return Err((
+                        ErrorCode::ChannelOverflow,
+                        format!("DMX channel is past the last LED: LED {}", global_led_index),
+                    ));
                 }
             };
 
             let led_index = global_led_index - leds_before_strip;
-            if led_index > strip.len() {
+            if led_index >= strip.len() {
                 warn!("LED per-strip index overflow: {:?}", led_index);
-                return;
+                return Err((
+                    ErrorCode::ChannelOverflow,
+                    format!(
+                        "DMX channel is past the end of the strip: LED {}",
+                        led_index
+                    ),
+                ));
             }
 
             let led = &mut strip[led_index];
-            let value = (value * 255.0) as u8;
+            let value = (*value * 255.0) as u8;
             info!("u8 value: {:?}", value);
 
             match color_index {
@@ -211,19 +272,121 @@ fn receive_osc_packet<'a, I>(
                 2 => led.b = value,
                 _ => {
                     warn!("Invalid color index: {:?}", color_index);
-                    return;
+                    return Ok(());
                 }
             };
+            *leds_set = true;
 
             info!(
                 "Setting LED #{:?} index: {:?} to: {:?}",
                 global_led_index, color_index, value
             );
         }
+        ([_, "dmx", _], args) => {
+            warn!("Invalid input to DMX channel: {:?}", args);
+            return Err((
+                ErrorCode::InvalidArgument,
+                format!("Expected a single Float, received: {:?}", args),
+            ));
+        }
         _ => {
             // info!("Unsupported packet received: {:?}", packet);
             info!("Unsupported packet received");
-            return;
+            return Err((
+                ErrorCode::UnknownAddress,
+                format!("Unsupported address: {}", message.addr),
+            ));
+        }
+    };
+
+    Ok(())
+}
+
+/// Sets consecutive LEDs starting `offset` LEDs into the strip at `led_strip_index`. Colors that
+/// run past the end of a strip overflow into the next strip.
+fn receive_colors(
+    led_strip_index: &str,
+    offset: &str,
+    input: &[OscType],
+    strips: &mut [&mut LedStripData],
+    leds_set: &mut bool,
+) -> Result<(), (ErrorCode, String)> {
+    let led_strip_index: usize = match led_strip_index.parse() {
+        Ok(led_strip_index) => led_strip_index,
+        Err(_err) => {
+            warn!("Invalid led_strip_index: {:?}", led_strip_index);
+            return Err((
+                ErrorCode::InvalidStripIndex,
+                format!("Invalid strip index: {:?}", led_strip_index),
+            ));
         }
     };
+    let offset: usize = match offset.parse() {
+        Ok(offset) => offset,
+        Err(_err) => {
+            warn!("Invalid LED offset: {:?}", offset);
+            return Err((
+                ErrorCode::InvalidOffset,
+                format!("Invalid LED offset: {:?}", offset),
+            ));
+        }
+    };
+    let mut result = Ok(());
+
+    let mut strips = strips.iter_mut().skip(led_strip_index);
+    let mut current_strip = strips.next().map(|strip| (strip, offset));
+
+    for osc_type in input.iter() {
+        match osc_type {
+            OscType::Color(c) => {
+                // If the current LED strip ends before the next index i then carry the rest of the
+                // index over to the next LED strip
+                loop {
+                    match current_strip.take() {
+                        Some((strip, i)) if i >= strip.len() => {
+                            let i = i - strip.len();
+                            current_strip = strips.next().map(|strip| (strip, i));
+                        }
+                        strip => {
+                            current_strip = strip;
+                            break;
+                        }
+                    }
+                }
+
+                if let Some((led_strip, i)) = current_strip.as_mut() {
+                    led_strip[*i].r = c.red;
+                    led_strip[*i].g = c.green;
+                    led_strip[*i].b = c.blue;
+                    *leds_set = true;
+
+                    // dbg!(&i);
+
+                    *i += 1;
+                } else {
+                    warn!("Input to /led_strips exceeded number of LED strips");
+                    if result.is_ok() {
+                        result = Err((
+                            ErrorCode::StripOverflow,
+                            "Colors exceeded the number of LEDs on the strips".to_string(),
+                        ));
+                    }
+                }
+            }
+            osc_type => {
+                warn!(
+                    "Invalid input to /led_strips. Expected Color, received: {:?}",
+                    osc_type
+                );
+                if result.is_ok() {
+                    result = Err((
+                        ErrorCode::InvalidArgument,
+                        format!("Expected Color, received: {:?}", osc_type),
+                    ));
+                }
+            }
+        }
+    }
+
+    result
 }
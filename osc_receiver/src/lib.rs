@@ -1,8 +1,29 @@
-#![cfg_attr(not(feature = "pi"), no_std)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-#[cfg(feature = "pi")]
+#[cfg(feature = "std")]
 extern crate std as core;
 
+#[macro_use]
 extern crate alloc;
 
+pub mod connection;
+pub mod error_reply;
+mod fade;
+pub mod failsafe;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod led_config;
 pub mod led_strip;
+pub mod mdns;
+pub mod mock;
+#[cfg(feature = "oscquery")]
+pub mod oscquery;
+pub mod pattern;
+pub mod preset;
+#[cfg(feature = "preview")]
+pub mod preview;
+pub mod provisioning;
+pub mod query;
+pub mod scene;
+pub mod show;
+pub mod show_player;
@@ -0,0 +1,341 @@
+//! mDNS / DNS-SD advertisement of receivers as `_osc._udp` services so that controllers can find
+//! receivers without knowing their IP addresses.
+//!
+//! Every receiver advertises the same TXT records:
+//!
+//! - `strips` - the number of LED strips
+//! - `lengths` - the number of LEDs on each strip separated by commas, eg. `450,144`
+//! - `led_type` - the type of LEDs, eg. `APA102` or `WS2812B`
+//! - `version` - the receiver's firmware version
+//!
+//! Receivers with an operating system should advertise the service with their mDNS daemon.
+//! [`Responder`] answers mDNS queries for receivers that don't have one.
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+
+/// The DNS-SD service type of OSC receivers, without the `.local` domain
+pub const SERVICE_TYPE: &str = "_osc._udp";
+/// The DNS-SD service type of OSC receivers in the form expected by most mDNS libraries
+pub const SERVICE_DOMAIN: &str = "_osc._udp.local.";
+pub const MDNS_PORT: u16 = 5353;
+pub const MDNS_GROUP: [u8; 4] = [224, 0, 0, 251];
+
+const SERVICE_NAME: [&str; 3] = ["_osc", "_udp", "local"];
+const SERVICES_NAME: [&str; 4] = ["_services", "_dns-sd", "_udp", "local"];
+
+pub(crate) const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+
+pub(crate) const CLASS_IN: u16 = 1;
+/// Tells caches to replace the records they have for the name rather than adding to them
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// The TTL of records containing the host name or IP address
+const HOST_TTL: u32 = 120;
+const OTHER_TTL: u32 = 4500;
+/// Replies to queries from ordinary DNS resolvers must not be cached for longer than this
+const LEGACY_UNICAST_TTL: u32 = 10;
+
+/// The TXT records advertised by every receiver.
+pub fn txt_records(
+    strip_lengths: &[usize],
+    led_type: &str,
+    version: &str,
+) -> Vec<(String, String)> {
+    let lengths = strip_lengths
+        .iter()
+        .map(|length| length.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    vec![
+        ("strips".to_string(), strip_lengths.len().to_string()),
+        ("lengths".to_string(), lengths),
+        ("led_type".to_string(), led_type.to_string()),
+        ("version".to_string(), version.to_string()),
+    ]
+}
+
+/// A minimal mDNS responder advertising one `_osc._udp` service.
+///
+/// The responder does not own a socket. Pass it every packet received on port 5353 after joining
+/// the mDNS multicast group and send the replies to [`MDNS_GROUP`], or back to the sender for
+/// queries that didn't come from port 5353.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Responder {
+    /// The service instance name, eg. `Stage Left`
+    pub instance: String,
+    /// The host name without the `.local` domain
+    pub hostname: String,
+    /// The port the receiver listens for OSC packets on
+    pub port: u16,
+    pub txt: Vec<(String, String)>,
+}
+
+/// The records a reply contains
+#[derive(Debug, Default, Clone, Copy)]
+struct Records {
+    services: bool,
+    ptr: bool,
+    srv: bool,
+    txt: bool,
+    a: bool,
+}
+
+impl Responder {
+    pub fn new(instance: &str, hostname: &str, port: u16, txt: Vec<(String, String)>) -> Self {
+        Self {
+            instance: instance.to_string(),
+            hostname: hostname.to_string(),
+            port,
+            txt,
+        }
+    }
+
+    /// Returns the reply to an mDNS query received from `source_port`, or `None` if the packet
+    /// doesn't ask about this receiver. `ip` is the receiver's IPv4 address.
+    pub fn reply(&self, query: &[u8], source_port: u16, ip: [u8; 4]) -> Option<Vec<u8>> {
+        let header = query.get(..12)?;
+        let flags = u16::from_be_bytes([header[2], header[3]]);
+        let question_count = u16::from_be_bytes([header[4], header[5]]);
+
+        // Ignore responses from other hosts
+        if flags & 0x8000 != 0 {
+            return None;
+        }
+
+        let mut answers = Records::default();
+        let mut offset = 12;
+
+        for _ in 0..question_count {
+            let (name, name_end) = read_name(query, offset)?;
+            let question = query.get(name_end..name_end + 4)?;
+            let record_type = u16::from_be_bytes([question[0], question[1]]);
+            offset = name_end + 4;
+
+            let matches = |wanted: u16| record_type == wanted || record_type == TYPE_ANY;
+
+            if names_eq(&name, &SERVICES_NAME) {
+                answers.services |= matches(TYPE_PTR);
+            } else if names_eq(&name, &SERVICE_NAME) {
+                answers.ptr |= matches(TYPE_PTR);
+            } else if names_eq(&name, &self.instance_name()) {
+                answers.srv |= matches(TYPE_SRV);
+                answers.txt |= matches(TYPE_TXT);
+            } else if names_eq(&name, &self.host_name()) {
+                answers.a |= matches(TYPE_A);
+            }
+        }
+
+        if !(answers.services || answers.ptr || answers.srv || answers.txt || answers.a) {
+            return None;
+        }
+
+        // Save the querier from having to ask for the records it will need next
+        let additional = Records {
+            services: false,
+            ptr: false,
+            srv: answers.ptr && !answers.srv,
+            txt: answers.ptr && !answers.txt,
+            a: (answers.ptr || answers.srv) && !answers.a,
+        };
+
+        // Queries from ordinary DNS resolvers expect a conventional unicast DNS reply
+        let legacy_unicast = source_port != MDNS_PORT;
+
+        let mut reply = Vec::with_capacity(256);
+
+        let ttl_limit = if legacy_unicast {
+            write_header(
+                &mut reply,
+                [header[0], header[1]],
+                question_count,
+                answers.count(),
+                additional.count(),
+            );
+            // The questions are copied unchanged so compressed names still point at the same
+            // offsets
+            reply.extend_from_slice(&query[12..offset]);
+
+            Some(LEGACY_UNICAST_TTL)
+        } else {
+            write_header(&mut reply, [0, 0], 0, answers.count(), additional.count());
+
+            None
+        };
+
+        self.write_records(&mut reply, answers, ip, ttl_limit);
+        self.write_records(&mut reply, additional, ip, ttl_limit);
+
+        Some(reply)
+    }
+
+    /// An unsolicited reply containing every record, sent to [`MDNS_GROUP`] when the receiver
+    /// joins a network so that controllers which are already browsing find it straight away.
+    pub fn announcement(&self, ip: [u8; 4]) -> Vec<u8> {
+        let records = Records {
+            services: false,
+            ptr: true,
+            srv: true,
+            txt: true,
+            a: true,
+        };
+
+        let mut announcement = Vec::with_capacity(256);
+        write_header(&mut announcement, [0, 0], 0, records.count(), 0);
+
+        self.write_records(&mut announcement, records, ip, None);
+
+        announcement
+    }
+
+    fn instance_name(&self) -> [&str; 4] {
+        let [service, protocol, domain] = SERVICE_NAME;
+        [&self.instance, service, protocol, domain]
+    }
+
+    fn host_name(&self) -> [&str; 2] {
+        [&self.hostname, "local"]
+    }
+
+    fn write_records(
+        &self,
+        out: &mut Vec<u8>,
+        records: Records,
+        ip: [u8; 4],
+        ttl_limit: Option<u32>,
+    ) {
+        let ttl = |ttl: u32| ttl_limit.map(|limit| ttl.min(limit)).unwrap_or(ttl);
+
+        if records.services {
+            write_name(out, &SERVICES_NAME);
+            write_record_header(out, TYPE_PTR, CLASS_IN, ttl(OTHER_TTL));
+            write_rdata(out, |out| write_name(out, &SERVICE_NAME));
+        }
+        if records.ptr {
+            write_name(out, &SERVICE_NAME);
+            write_record_header(out, TYPE_PTR, CLASS_IN, ttl(OTHER_TTL));
+            write_rdata(out, |out| write_name(out, &self.instance_name()));
+        }
+        if records.srv {
+            write_name(out, &self.instance_name());
+            write_record_header(out, TYPE_SRV, CLASS_IN | CACHE_FLUSH, ttl(HOST_TTL));
+            write_rdata(out, |out| {
+                // Priority and weight
+                out.extend_from_slice(&[0, 0, 0, 0]);
+                out.extend_from_slice(&self.port.to_be_bytes());
+                write_name(out, &self.host_name());
+            });
+        }
+        if records.txt {
+            write_name(out, &self.instance_name());
+            write_record_header(out, TYPE_TXT, CLASS_IN | CACHE_FLUSH, ttl(OTHER_TTL));
+            write_rdata(out, |out| {
+                for (key, value) in self.txt.iter() {
+                    let length = (key.len() + 1 + value.len()).min(255);
+                    let entry = key.bytes().chain(Some(b'=')).chain(value.bytes());
+
+                    out.push(length as u8);
+                    out.extend(entry.take(length));
+                }
+                // A TXT record must contain at least one string
+                if self.txt.is_empty() {
+                    out.push(0);
+                }
+            });
+        }
+        if records.a {
+            write_name(out, &self.host_name());
+            write_record_header(out, TYPE_A, CLASS_IN | CACHE_FLUSH, ttl(HOST_TTL));
+            write_rdata(out, |out| out.extend_from_slice(&ip));
+        }
+    }
+}
+
+impl Records {
+    fn count(&self) -> u16 {
+        [self.services, self.ptr, self.srv, self.txt, self.a]
+            .iter()
+            .filter(|record| **record)
+            .count() as u16
+    }
+}
+
+/// Reads the (possibly compressed) name starting at `offset`, returning its labels and the offset
+/// of the end of the name.
+pub(crate) fn read_name(packet: &[u8], mut offset: usize) -> Option<(Vec<&[u8]>, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Guards against compression pointer loops
+    let mut jumps = 0;
+
+    loop {
+        let length = *packet.get(offset)? as usize;
+
+        if length == 0 {
+            return Some((labels, end.unwrap_or(offset + 1)));
+        } else if length & 0xC0 == 0xC0 {
+            let pointer = ((length & 0x3F) << 8) | *packet.get(offset + 1)? as usize;
+
+            end.get_or_insert(offset + 2);
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+
+            offset = pointer;
+        } else {
+            labels.push(packet.get(offset + 1..offset + 1 + length)?);
+            offset += 1 + length;
+        }
+    }
+}
+
+fn names_eq(labels: &[&[u8]], name: &[&str]) -> bool {
+    labels.len() == name.len()
+        && labels
+            .iter()
+            .zip(name.iter())
+            .all(|(label, expected)| label.eq_ignore_ascii_case(expected.as_bytes()))
+}
+
+/// Writes the header of an authoritative response.
+fn write_header(out: &mut Vec<u8>, id: [u8; 2], questions: u16, answers: u16, additional: u16) {
+    out.extend_from_slice(&id);
+    out.extend_from_slice(&0x8400u16.to_be_bytes());
+    out.extend_from_slice(&questions.to_be_bytes());
+    out.extend_from_slice(&answers.to_be_bytes());
+    // Authority records
+    out.extend_from_slice(&0u16.to_be_bytes());
+    out.extend_from_slice(&additional.to_be_bytes());
+}
+
+fn write_name(out: &mut Vec<u8>, name: &[&str]) {
+    for label in name.iter() {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        out.push(label.len() as u8);
+        out.extend_from_slice(label);
+    }
+    out.push(0);
+}
+
+pub(crate) fn write_record_header(out: &mut Vec<u8>, record_type: u16, class: u16, ttl: u32) {
+    out.extend_from_slice(&record_type.to_be_bytes());
+    out.extend_from_slice(&class.to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+}
+
+/// Writes the record data prefixed by its length.
+pub(crate) fn write_rdata(out: &mut Vec<u8>, write: impl FnOnce(&mut Vec<u8>)) {
+    let start = out.len();
+    out.extend_from_slice(&[0, 0]);
+    write(out);
+
+    let length = (out.len() - start - 2) as u16;
+    out[start..start + 2].copy_from_slice(&length.to_be_bytes());
+}
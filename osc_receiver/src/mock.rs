@@ -0,0 +1,33 @@
+use alloc::vec::Vec;
+use anyhow::Result;
+use core::iter::Iterator;
+use core::option::Option;
+use core::result::Result::*;
+use smart_leds::RGB8;
+
+use crate::led_strip::RGB8SmartLedsWrite;
+
+/// An `RGB8SmartLedsWrite` that records every frame written to it instead of driving any LEDs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordingSmartLed {
+    pub frames: Vec<Vec<RGB8>>,
+}
+
+impl RecordingSmartLed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The most recently written frame, if any frames have been written
+    pub fn last_frame(&self) -> Option<&[RGB8]> {
+        self.frames.last().map(|frame| &frame[..])
+    }
+}
+
+impl RGB8SmartLedsWrite for RecordingSmartLed {
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        self.frames.push(iterator.collect());
+
+        Ok(())
+    }
+}
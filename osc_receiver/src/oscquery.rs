@@ -0,0 +1,308 @@
+//! An [OSCQuery](https://github.com/Vidvox/OSCQueryProposal) server describing the receiver's OSC
+//! address space so that controller apps can discover its addresses, argument types and ranges.
+//!
+//! - `GET /` returns the whole namespace as JSON and `GET /some/path` returns one node of it
+//! - `GET /some/path?TYPE` returns a single attribute of a node, eg. `{"TYPE": "f"}`
+//! - `GET /?HOST_INFO` returns the receiver's name, OSC port and supported extensions
+//! - WebSocket connections are sent `PATH_ADDED`, `PATH_REMOVED` and `PATH_CHANGED` commands when
+//!   the namespace is updated, eg. after the receiver's config is reloaded
+//!
+//! Node values are not reported so the `VALUE` and `LISTEN` extensions are not supported.
+
+use std::collections::BTreeMap;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+
+use anyhow::Result;
+use log::info;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tungstenite::Message;
+
+use crate::http::{self, Request};
+
+pub const ACCESS_NONE: u8 = 0;
+pub const ACCESS_READ: u8 = 1;
+pub const ACCESS_WRITE: u8 = 2;
+pub const ACCESS_READ_WRITE: u8 = 3;
+
+/// A node of the OSC address space. Nodes with a `type_tag` are OSC methods that accept messages,
+/// nodes with `contents` are containers, and a node can be both.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct Node {
+    pub full_path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The OSC type tags of the method's arguments, eg. `ff` for two floats
+    #[serde(rename = "TYPE", skip_serializing_if = "Option::is_none")]
+    pub type_tag: Option<String>,
+    /// One of the `ACCESS_*` constants
+    pub access: u8,
+    /// The range of each argument
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub range: Vec<Range>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub contents: BTreeMap<String, Node>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub struct Range {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+/// The receiver's details returned by `GET /?HOST_INFO`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    pub name: String,
+    /// The UDP port the receiver listens for OSC packets on
+    pub osc_port: u16,
+}
+
+impl Node {
+    /// An empty container, eg. the root node `/`.
+    pub fn container(full_path: &str) -> Self {
+        Self {
+            full_path: full_path.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// A method accepting messages with arguments of the given OSC type tags.
+    pub fn method(full_path: &str, type_tag: &str, access: u8, description: &str) -> Self {
+        Self {
+            full_path: full_path.to_string(),
+            description: Some(description.to_string()),
+            type_tag: Some(type_tag.to_string()),
+            access,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    pub fn with_range(mut self, min: Option<f64>, max: Option<f64>) -> Self {
+        self.range.push(Range { min, max });
+        self
+    }
+
+    /// Adds `node` below this node at its full path, creating any missing containers in between.
+    /// A node that already exists at the path keeps its contents but has its attributes replaced.
+    pub fn insert(&mut self, mut node: Node) {
+        let mut parent = self;
+        let mut path = String::new();
+        let mut segments = node
+            .full_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .peekable();
+
+        while let Some(segment) = segments.next() {
+            path.push('/');
+            path.push_str(segment);
+
+            let child = parent
+                .contents
+                .entry(segment.to_string())
+                .or_insert_with(|| Node::container(&path));
+
+            if segments.peek().is_none() {
+                node.contents = std::mem::take(&mut child.contents);
+                *child = node;
+                break;
+            }
+            parent = child;
+        }
+    }
+
+    /// The node at `path`, if it exists.
+    pub fn find(&self, path: &str) -> Option<&Node> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |node, segment| node.contents.get(segment))
+    }
+
+    /// The attributes of this node and every node below it, keyed by their full paths.
+    fn flatten(&self, nodes: &mut BTreeMap<String, Value>) {
+        let attributes = Node {
+            contents: BTreeMap::new(),
+            ..self.clone()
+        };
+        nodes.insert(self.full_path.clone(), json!(attributes));
+
+        for child in self.contents.values() {
+            child.flatten(nodes);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Namespace {
+    sequence: u64,
+    root: Node,
+}
+
+/// A handle to the OSCQuery server, used to update the namespace it describes.
+#[derive(Debug, Clone)]
+pub struct OscQuery {
+    namespace: Arc<(Mutex<Namespace>, Condvar)>,
+}
+
+impl OscQuery {
+    /// Starts the OSCQuery server on `addr`, describing the namespace below `root`.
+    pub fn spawn(addr: SocketAddr, host_info: HostInfo, root: Node) -> Result<Self> {
+        let oscquery = Self {
+            namespace: Arc::new((Mutex::new(Namespace { sequence: 0, root }), Condvar::new())),
+        };
+
+        let namespace = Arc::clone(&oscquery.namespace);
+        let host_info = Arc::new(host_info);
+        http::serve(addr, move |request, stream| {
+            handle_request(request, stream, &namespace, &host_info)
+        })?;
+
+        info!("Serving the OSCQuery namespace at http://{}/", addr);
+
+        Ok(oscquery)
+    }
+
+    /// Replaces the namespace and notifies the connected WebSocket clients of what changed.
+    pub fn update(&self, root: Node) {
+        let (namespace, updated) = &*self.namespace;
+        let mut namespace = namespace.lock().unwrap();
+
+        if namespace.root != root {
+            namespace.root = root;
+            namespace.sequence += 1;
+            updated.notify_all();
+        }
+    }
+}
+
+fn handle_request(
+    request: Request,
+    stream: TcpStream,
+    namespace: &(Mutex<Namespace>, Condvar),
+    host_info: &HostInfo,
+) -> Result<()> {
+    if request.is_websocket_upgrade() {
+        return send_changes(request, stream, namespace);
+    }
+
+    if request.query.as_deref() == Some("HOST_INFO") {
+        let host_info = json!({
+            "NAME": host_info.name,
+            "OSC_PORT": host_info.osc_port,
+            "OSC_TRANSPORT": "UDP",
+            "EXTENSIONS": {
+                "ACCESS": true,
+                "VALUE": false,
+                "RANGE": true,
+                "DESCRIPTION": true,
+                "LISTEN": false,
+                "PATH_CHANGED": true,
+                "PATH_ADDED": true,
+                "PATH_REMOVED": true,
+            },
+        });
+
+        return respond_json(stream, &host_info);
+    }
+
+    let node = {
+        let (namespace, _) = namespace;
+        let namespace = namespace.lock().unwrap();
+
+        match namespace.root.find(&request.path) {
+            Some(node) => json!(node),
+            None => return http::not_found(stream),
+        }
+    };
+
+    match request.query.as_deref() {
+        None | Some("") => respond_json(stream, &node),
+        Some(attribute) => match node.get(attribute) {
+            Some(value) => respond_json(stream, &json!({ attribute: value })),
+            None => http::respond(stream, "204 No Content", "application/json", b""),
+        },
+    }
+}
+
+fn respond_json(stream: TcpStream, value: &Value) -> Result<()> {
+    http::respond(
+        stream,
+        "200 OK",
+        "application/json",
+        &serde_json::to_vec(value)?,
+    )
+}
+
+/// Sends a command to the WebSocket for every node that was added, removed or changed each time
+/// the namespace is updated, until the client closes the connection.
+fn send_changes(
+    request: Request,
+    stream: TcpStream,
+    namespace: &(Mutex<Namespace>, Condvar),
+) -> Result<()> {
+    let (namespace, updated) = namespace;
+
+    // Taken before the handshake completes so that no update after it is missed
+    let (mut sent_sequence, mut sent_nodes) = {
+        let namespace = namespace.lock().unwrap();
+        let mut nodes = BTreeMap::new();
+        namespace.root.flatten(&mut nodes);
+
+        (namespace.sequence, nodes)
+    };
+
+    let mut websocket = http::accept_websocket(&request, stream)?;
+
+    loop {
+        // Answers pings and notices closed connections between updates
+        if !http::poll_websocket(&mut websocket)? {
+            return Ok(());
+        }
+
+        let nodes = {
+            let namespace = namespace.lock().unwrap();
+            let (namespace, _) = updated
+                .wait_timeout_while(namespace, http::WEBSOCKET_POLL_INTERVAL, |namespace| {
+                    namespace.sequence == sent_sequence
+                })
+                .unwrap();
+            if namespace.sequence == sent_sequence {
+                continue;
+            }
+            sent_sequence = namespace.sequence;
+
+            let mut nodes = BTreeMap::new();
+            namespace.root.flatten(&mut nodes);
+            nodes
+        };
+
+        for (path, node) in nodes.iter() {
+            let command = match sent_nodes.get(path) {
+                None => json!({ "COMMAND": "PATH_ADDED", "DATA": node }),
+                Some(sent_node) if sent_node != node => {
+                    json!({ "COMMAND": "PATH_CHANGED", "DATA": path })
+                }
+                Some(_) => continue,
+            };
+            websocket.send(Message::Text(command.to_string().into()))?;
+        }
+
+        for path in sent_nodes.keys().filter(|path| !nodes.contains_key(*path)) {
+            let command = json!({ "COMMAND": "PATH_REMOVED", "DATA": path });
+            websocket.send(Message::Text(command.to_string().into()))?;
+        }
+
+        sent_nodes = nodes;
+    }
+}
@@ -0,0 +1,432 @@
+//! Animated patterns the receiver runs by itself so that it lights up without a controller, eg. to
+//! make a single ESP32C3 a self-contained light.
+//!
+//! `/pattern/{name}` starts a pattern and `/pattern/{name}/{param} <value>` sets one of the
+//! pattern's parameters and starts it, eg. `/pattern/rainbow/speed 0.5`. `/pattern/stop` stops
+//! the running pattern, leaving its last frame on the strips. Patterns run until the next frame
+//! arrives from the network.
+//!
+//! The patterns only use basic float arithmetic since `f32::sin` and friends aren't available
+//! without std.
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use rosc::{OscMessage, OscPacket, OscType};
+use smart_leds::RGB8;
+
+use crate::error_reply::{ErrorCode, MessageError};
+use crate::fade::blend;
+use crate::failsafe::parse_hex_color;
+use crate::led_strip::LedStrip;
+
+pub const PATTERN_PREFIX: &str = "/pattern/";
+pub const STOP_ADDR: &str = "/pattern/stop";
+
+/// How often the strips are redrawn while a pattern runs
+const FRAME_MS: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    /// A rainbow scrolling along every strip
+    Rainbow = 0,
+    /// Every LED slowly fading in and out
+    Breathing = 1,
+    /// Evenly spaced blocks of color moving along every strip
+    Chase = 2,
+    /// LEDs randomly lighting up and fading out
+    Sparkle = 3,
+}
+
+pub const PATTERN_KINDS: [PatternKind; 4] = [
+    PatternKind::Rainbow,
+    PatternKind::Breathing,
+    PatternKind::Chase,
+    PatternKind::Sparkle,
+];
+
+/// The parameters shared by every pattern. Each pattern uses the parameters that make sense for
+/// it and ignores the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternParams {
+    /// Multiplies how fast the pattern moves
+    pub speed: f32,
+    /// The size of the pattern in LEDs, eg. the length of one rainbow or the gap between chasers
+    pub scale: f32,
+    /// The color of breathing, chase and sparkle
+    pub color: RGB8,
+    /// How busy the pattern is from 0 to 1, eg. the width of chasers or how many LEDs sparkle
+    pub density: f32,
+}
+
+/// Runs the selected pattern on the strips and keeps each pattern's parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patterns {
+    params: [PatternParams; 4],
+    running: Option<Running>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Running {
+    kind: PatternKind,
+    started_at_ms: u64,
+    /// When the strips were last redrawn
+    drawn_at_ms: Option<u64>,
+    /// The brightness of each sparkling LED
+    levels: Vec<Vec<f32>>,
+    rng: Rng,
+}
+
+impl PatternKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        PATTERN_KINDS
+            .iter()
+            .copied()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Rainbow => "rainbow",
+            Self::Breathing => "breathing",
+            Self::Chase => "chase",
+            Self::Sparkle => "sparkle",
+        }
+    }
+}
+
+impl Default for PatternParams {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            scale: 60.0,
+            color: RGB8 {
+                r: 0xFF,
+                g: 0x80,
+                b: 0x00,
+            },
+            density: 0.5,
+        }
+    }
+}
+
+impl Default for Patterns {
+    fn default() -> Self {
+        Self::new(PatternParams::default())
+    }
+}
+
+impl Patterns {
+    /// Starts every pattern with the same parameters.
+    pub fn new(params: PatternParams) -> Self {
+        Self {
+            params: [params; 4],
+            running: None,
+        }
+    }
+
+    pub fn params(&self, kind: PatternKind) -> &PatternParams {
+        &self.params[kind as usize]
+    }
+
+    pub fn params_mut(&mut self, kind: PatternKind) -> &mut PatternParams {
+        &mut self.params[kind as usize]
+    }
+
+    /// The running pattern, if any.
+    pub fn running(&self) -> Option<PatternKind> {
+        self.running.as_ref().map(|running| running.kind)
+    }
+
+    pub fn start(&mut self, kind: PatternKind, now_ms: u64) {
+        self.running = Some(Running {
+            kind,
+            started_at_ms: now_ms,
+            drawn_at_ms: None,
+            levels: Vec::new(),
+            rng: Rng::new(now_ms),
+        });
+    }
+
+    /// Stops the running pattern, leaving its last frame on the strips.
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    /// Handles a `/pattern/...` message. Returns `None` if the packet isn't a pattern message and
+    /// an error to reply with if it is one but is invalid.
+    pub fn handle_packet(
+        &mut self,
+        packet: &OscPacket,
+        now_ms: u64,
+    ) -> Option<Result<(), MessageError>> {
+        let (addr, args) = match packet {
+            OscPacket::Message(OscMessage { addr, args }) => (addr.as_str(), &args[..]),
+            _ => return None,
+        };
+
+        let path = addr.strip_prefix(PATTERN_PREFIX)?;
+        let error = |code, reason: String| Some(Err(MessageError::new(addr, code, reason)));
+
+        if addr == STOP_ADDR {
+            self.stop();
+            return Some(Ok(()));
+        }
+
+        let (name, param) = match path.split_once('/') {
+            Some((name, param)) => (name, Some(param)),
+            None => (path, None),
+        };
+
+        let kind = match PatternKind::parse(name) {
+            Some(kind) => kind,
+            None => {
+                return error(
+                    ErrorCode::UnknownPattern,
+                    format!(
+                        "Unknown pattern {:?}. Must be one of: rainbow, breathing, chase, sparkle",
+                        name
+                    ),
+                )
+            }
+        };
+
+        if let Some(param) = param {
+            let params = self.params_mut(kind);
+
+            let set = match (param, args) {
+                ("speed", [speed]) => as_f32(speed)
+                    .filter(|speed| *speed >= 0.0)
+                    .map(|speed| params.speed = speed),
+                ("scale", [scale]) => as_f32(scale)
+                    .filter(|scale| *scale >= 1.0)
+                    .map(|scale| params.scale = scale),
+                ("density", [density]) => as_f32(density)
+                    .filter(|density| (0.0..=1.0).contains(density))
+                    .map(|density| params.density = density),
+                ("color", [color]) => as_color(color).map(|color| params.color = color),
+                ("speed" | "scale" | "density" | "color", _) => None,
+                _ => {
+                    return error(
+                        ErrorCode::UnknownAddress,
+                        format!(
+                            "Unknown pattern parameter {:?}. Must be one of: speed, scale, color, density",
+                            param
+                        ),
+                    )
+                }
+            };
+
+            if set.is_none() {
+                return error(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid {} for {}: {:?}", param, kind, args),
+                );
+            }
+        }
+
+        if self.running() != Some(kind) {
+            self.start(kind, now_ms);
+        }
+
+        Some(Ok(()))
+    }
+
+    /// Draws the next frame of the running pattern. Returns true if the strips were changed and
+    /// need to be written to the LEDs.
+    pub fn update(&mut self, led_strips: &mut [LedStrip], now_ms: u64) -> bool {
+        let running = match &mut self.running {
+            Some(running) => running,
+            None => return false,
+        };
+
+        let elapsed_s = match running.drawn_at_ms {
+            Some(drawn_at_ms) if now_ms.saturating_sub(drawn_at_ms) < FRAME_MS => return false,
+            Some(drawn_at_ms) => now_ms.saturating_sub(drawn_at_ms) as f32 / 1000.0,
+            None => 0.0,
+        };
+        running.drawn_at_ms = Some(now_ms);
+
+        let params = &self.params[running.kind as usize];
+        let time =
+            now_ms.saturating_sub(running.started_at_ms) as f64 / 1000.0 * params.speed as f64;
+
+        match running.kind {
+            PatternKind::Rainbow => rainbow(led_strips, params, time),
+            PatternKind::Breathing => breathing(led_strips, params, time),
+            PatternKind::Chase => chase(led_strips, params, time),
+            PatternKind::Sparkle => sparkle(led_strips, params, running, elapsed_s),
+        }
+
+        true
+    }
+
+    /// How long until [`Patterns::update`] next needs to be called, or `None` if no pattern is
+    /// running.
+    pub fn next_update_in_ms(&self, now_ms: u64) -> Option<u64> {
+        self.running.as_ref().map(|running| {
+            running.drawn_at_ms.map_or(0, |drawn_at_ms| {
+                (drawn_at_ms + FRAME_MS).saturating_sub(now_ms)
+            })
+        })
+    }
+}
+
+fn rainbow(led_strips: &mut [LedStrip], params: &PatternParams, time: f64) {
+    // One rainbow scrolls past each LED every 4 seconds
+    let offset = fract(time * 0.25) as f32;
+
+    for led_strip in led_strips.iter_mut() {
+        for (i, led) in led_strip.data.iter_mut().enumerate() {
+            *led = hue(i as f32 / params.scale.max(1.0) + offset);
+        }
+    }
+}
+
+fn breathing(led_strips: &mut [LedStrip], params: &PatternParams, time: f64) {
+    // One breath every 4 seconds, easing in and out of each end like a sine wave
+    let triangle = 1.0 - (fract(time * 0.25) as f32 * 2.0 - 1.0).abs();
+    let level = triangle * triangle * (3.0 - 2.0 * triangle);
+    let color = blend(RGB8::default(), params.color, level);
+
+    for led_strip in led_strips.iter_mut() {
+        led_strip.data.fill(color);
+    }
+}
+
+fn chase(led_strips: &mut [LedStrip], params: &PatternParams, time: f64) {
+    let spacing = params.scale.max(1.0);
+    let width = (spacing * params.density).max(1.0);
+    // 30 LEDs per second
+    let position = fract(time * 30.0 / spacing as f64) as f32 * spacing;
+
+    for led_strip in led_strips.iter_mut() {
+        for (i, led) in led_strip.data.iter_mut().enumerate() {
+            let distance = fract(((i as f32 - position) / spacing) as f64) as f32 * spacing;
+
+            *led = if distance < width {
+                params.color
+            } else {
+                RGB8::default()
+            };
+        }
+    }
+}
+
+fn sparkle(
+    led_strips: &mut [LedStrip],
+    params: &PatternParams,
+    running: &mut Running,
+    elapsed_s: f32,
+) {
+    // Each LED lights up up to twice a second and fades out over a second at a speed of 1
+    let chance = params.density * 2.0 * params.speed * elapsed_s;
+    let fade = params.speed * elapsed_s;
+
+    running.levels.resize(led_strips.len(), Vec::new());
+
+    for (led_strip, levels) in led_strips.iter_mut().zip(running.levels.iter_mut()) {
+        levels.resize(led_strip.data.len(), 0.0);
+
+        for (led, level) in led_strip.data.iter_mut().zip(levels.iter_mut()) {
+            *level = (*level - fade).max(0.0);
+            if running.rng.next_f32() < chance {
+                *level = 1.0;
+            }
+
+            *led = blend(RGB8::default(), params.color, *level);
+        }
+    }
+}
+
+/// A fully saturated color for a hue wrapping from 0 to 1.
+fn hue(hue: f32) -> RGB8 {
+    let hue = fract(hue as f64) as f32 * 6.0;
+    let sector = hue as u8;
+    let rise = ((hue - sector as f32) * 255.0) as u8;
+    let fall = 255 - rise;
+
+    let (r, g, b) = match sector {
+        0 => (255, rise, 0),
+        1 => (fall, 255, 0),
+        2 => (0, 255, rise),
+        3 => (0, fall, 255),
+        4 => (rise, 0, 255),
+        _ => (255, 0, fall),
+    };
+
+    RGB8 { r, g, b }
+}
+
+/// The fractional part of `value`, from 0 up to but not including 1 even for negative values.
+fn fract(value: f64) -> f64 {
+    let truncated = value as i64 as f64;
+    let floor = if truncated > value {
+        truncated - 1.0
+    } else {
+        truncated
+    };
+
+    value - floor
+}
+
+fn as_f32(osc_type: &OscType) -> Option<f32> {
+    match osc_type {
+        OscType::Float(value) => Some(*value),
+        OscType::Double(value) => Some(*value as f32),
+        OscType::Int(value) => Some(*value as f32),
+        OscType::Long(value) => Some(*value as f32),
+        _ => None,
+    }
+}
+
+/// Colors are sent as an OSC color or a `#rrggbb` string.
+fn as_color(osc_type: &OscType) -> Option<RGB8> {
+    match osc_type {
+        OscType::Color(color) => Some(RGB8 {
+            r: color.red,
+            g: color.green,
+            b: color.blue,
+        }),
+        OscType::String(color) => parse_hex_color(color).ok(),
+        _ => None,
+    }
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl fmt::Display for PatternParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "speed = {}, scale = {}, color = #{:02x}{:02x}{:02x}, density = {}",
+            self.speed, self.scale, self.color.r, self.color.g, self.color.b, self.density
+        )
+    }
+}
+
+/// A small xorshift random number generator for sparkle
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at 0 and produces similar sequences for similar seeds
+        Self(seed.wrapping_mul(0x2545_F491_4F6C_DD1D) | 1)
+    }
+
+    /// A random value from 0 up to but not including 1
+    fn next_f32(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,247 @@
+//! Named presets that operators can jump between without a controller running.
+//!
+//! `/preset/{name}/store` snapshots every strip as the named preset and
+//! `/preset/{name}/recall [fade_seconds]` restores it, crossfading from the current LEDs when a
+//! fade is given. Each preset is a [`Scene`], persisted by the receiver.
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use anyhow::{bail, Result};
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use rosc::{OscMessage, OscPacket, OscType};
+use smart_leds::RGB8;
+
+use crate::error_reply::{ErrorCode, MessageError};
+use crate::fade::Fade;
+use crate::led_strip::LedStrip;
+use crate::scene::Scene;
+
+pub const PRESET_PREFIX: &str = "/preset/";
+
+/// The longest allowed preset name
+pub const MAX_NAME_LEN: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresetCommand {
+    /// `/preset/{name}/store`
+    Store { name: String },
+    /// `/preset/{name}/recall [fade_seconds]`
+    Recall { name: String, fade_ms: u64 },
+}
+
+/// The stored presets by name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Presets {
+    scenes: BTreeMap<String, Scene>,
+}
+
+/// A fade from the LEDs shown when a preset was recalled to the preset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Crossfade {
+    fade: Fade,
+}
+
+impl PresetCommand {
+    /// Parses a `/preset/...` message. Returns `None` if the packet isn't a preset message and an
+    /// error to reply with if it is one but is invalid.
+    pub fn parse(packet: &OscPacket) -> Option<Result<Self, MessageError>> {
+        let (addr, args) = match packet {
+            OscPacket::Message(OscMessage { addr, args }) => (addr.as_str(), &args[..]),
+            _ => return None,
+        };
+
+        let path = addr.strip_prefix(PRESET_PREFIX)?;
+        let error = |code, reason: String| Some(Err(MessageError::new(addr, code, reason)));
+
+        let (name, command) = match path.rsplit_once('/') {
+            Some(parts) => parts,
+            None => {
+                return error(
+                    ErrorCode::UnknownAddress,
+                    format!("Unknown preset command: {}", addr),
+                )
+            }
+        };
+
+        if !is_valid_name(name) {
+            return error(
+                ErrorCode::InvalidPresetName,
+                format!(
+                    "Invalid preset name {:?}. Names are 1 to {} letters, digits, - or _",
+                    name, MAX_NAME_LEN
+                ),
+            );
+        }
+
+        let name = name.to_string();
+
+        match (command, args) {
+            ("store", _) => Some(Ok(Self::Store { name })),
+            ("recall", []) => Some(Ok(Self::Recall { name, fade_ms: 0 })),
+            ("recall", [fade_seconds]) => match as_f64(fade_seconds) {
+                Some(fade_seconds) if fade_seconds >= 0.0 => Some(Ok(Self::Recall {
+                    name,
+                    fade_ms: (fade_seconds * 1000.0) as u64,
+                })),
+                _ => error(
+                    ErrorCode::InvalidArgument,
+                    format!("Invalid fade: {:?}. Expected seconds", fade_seconds),
+                ),
+            },
+            ("recall", _) => error(
+                ErrorCode::InvalidArgument,
+                "Expected at most one fade argument".to_string(),
+            ),
+            _ => error(
+                ErrorCode::UnknownAddress,
+                format!("Unknown preset command: {}", addr),
+            ),
+        }
+    }
+}
+
+impl Presets {
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.scenes.get(name)
+    }
+
+    /// Stores the scene as the named preset, replacing any preset with the same name.
+    pub fn insert(&mut self, name: &str, scene: Scene) {
+        self.scenes.insert(name.to_string(), scene);
+    }
+
+    /// The preset names in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.scenes.keys().map(|name| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    /// Starts fading the strips to the named preset.
+    pub fn recall(
+        &self,
+        name: &str,
+        led_strips: &[LedStrip],
+        fade_ms: u64,
+        now_ms: u64,
+    ) -> Result<Crossfade, MessageError> {
+        match self.get(name) {
+            Some(scene) => Ok(Crossfade::new(led_strips, scene, fade_ms, now_ms)),
+            None => Err(MessageError::new(
+                &format!("{}{}/recall", PRESET_PREFIX, name),
+                ErrorCode::UnknownPreset,
+                format!("No preset named {:?} has been stored", name),
+            )),
+        }
+    }
+
+    /// Encodes every preset, for receivers that store them all together, eg. in flash. Each
+    /// preset is its name's length as a `u8`, the name, the scene's length as a little endian
+    /// `u32` and the scene.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+
+        for (name, scene) in self.scenes.iter() {
+            let scene = scene.encode()?;
+
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(scene.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&scene);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn decode(mut bytes: &[u8]) -> Result<Self> {
+        let mut presets = Self::default();
+
+        while let Some((&name_len, rest)) = bytes.split_first() {
+            let name_len = name_len as usize;
+            if rest.len() < name_len + 4 {
+                bail!("The presets are truncated");
+            }
+
+            let name = core::str::from_utf8(&rest[..name_len])?;
+            if !is_valid_name(name) {
+                bail!("Invalid preset name {:?}", name);
+            }
+
+            let mut scene_len = [0; 4];
+            scene_len.copy_from_slice(&rest[name_len..name_len + 4]);
+            let scene_len = u32::from_le_bytes(scene_len) as usize;
+
+            let rest = &rest[name_len + 4..];
+            if rest.len() < scene_len {
+                bail!("The presets are truncated");
+            }
+
+            presets.insert(name, Scene::decode(&rest[..scene_len])?);
+            bytes = &rest[scene_len..];
+        }
+
+        Ok(presets)
+    }
+}
+
+impl Crossfade {
+    /// A fade from the LEDs currently set on the strips to the scene. LEDs that the scene doesn't
+    /// cover are left unchanged.
+    pub fn new(led_strips: &[LedStrip], scene: &Scene, fade_ms: u64, now_ms: u64) -> Self {
+        let to = |strip: usize, index: usize, color: RGB8| {
+            scene
+                .strips
+                .get(strip)
+                .and_then(|leds| leds.get(index))
+                .copied()
+                .unwrap_or(color)
+        };
+
+        Self {
+            fade: Fade::new(led_strips, to, fade_ms, now_ms),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.fade.is_finished()
+    }
+
+    /// Fades the strips towards the preset. Returns true if the strips were changed and need to be
+    /// written to the LEDs.
+    pub fn update(&mut self, led_strips: &mut [LedStrip], now_ms: u64) -> bool {
+        self.fade.update(led_strips, now_ms)
+    }
+
+    /// How long until [`Crossfade::update`] next needs to be called, or `None` once the fade has
+    /// finished.
+    pub fn next_update_in_ms(&self, now_ms: u64) -> Option<u64> {
+        self.fade.next_update_in_ms(now_ms)
+    }
+}
+
+/// Preset names are used as file names, so they are limited to letters, digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn as_f64(osc_type: &OscType) -> Option<f64> {
+    match osc_type {
+        OscType::Float(value) => Some(*value as f64),
+        OscType::Double(value) => Some(*value),
+        OscType::Int(value) => Some(*value as f64),
+        OscType::Long(value) => Some(*value as f64),
+        _ => None,
+    }
+}
@@ -0,0 +1,142 @@
+//! Live browser preview of the LED strips.
+//!
+//! The preview server serves a small web page at `/` which connects to a WebSocket at `/ws`. Each
+//! WebSocket message is a binary frame containing the contents of every `LedStrip`:
+//!
+//! - the number of strips as a big endian `u16`
+//! - the length of each strip as a big endian `u16`
+//! - the red, green and blue bytes of every LED of every strip, in strip order
+
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::info;
+use smart_leds::RGB8;
+use tungstenite::Message;
+
+use crate::http::{self, Request};
+use crate::led_strip::LedStrip;
+
+pub const DEFAULT_PREVIEW_FPS: u32 = 15;
+
+const PREVIEW_PAGE: &str = include_str!("preview.html");
+
+#[derive(Debug, Default)]
+struct Frame {
+    sequence: u64,
+    strips: Vec<Vec<RGB8>>,
+}
+
+/// A handle to the preview server, used to publish the current contents of the LED strips.
+#[derive(Debug, Clone)]
+pub struct Preview {
+    frame: Arc<(Mutex<Frame>, Condvar)>,
+}
+
+impl Preview {
+    /// Starts the preview server on `addr`. Each WebSocket client receives at most `fps` frames a
+    /// second.
+    pub fn spawn(addr: SocketAddr, fps: u32) -> Result<Self> {
+        let preview = Self {
+            frame: Arc::new((Mutex::new(Frame::default()), Condvar::new())),
+        };
+        let frame_duration = Duration::from_secs(1) / fps.max(1);
+
+        let frame = Arc::clone(&preview.frame);
+        http::serve(addr, move |request, stream| {
+            handle_request(request, stream, &frame, frame_duration)
+        })?;
+
+        info!("Serving the LED preview at http://{}/", addr);
+
+        Ok(preview)
+    }
+
+    /// Publishes the current contents of the LED strips to every connected client.
+    pub fn publish(&self, led_strips: &[LedStrip]) {
+        self.publish_frame(led_strips.iter().map(|led_strip| &led_strip.data[..]));
+    }
+
+    pub fn publish_frame<'a, I>(&self, strips: I)
+    where
+        I: Iterator<Item = &'a [RGB8]>,
+    {
+        let (frame, updated) = &*self.frame;
+        let mut frame = frame.lock().unwrap();
+
+        frame.strips.clear();
+        frame.strips.extend(strips.map(<[RGB8]>::to_vec));
+        frame.sequence += 1;
+
+        updated.notify_all();
+    }
+}
+
+fn handle_request(
+    request: Request,
+    stream: TcpStream,
+    frame: &(Mutex<Frame>, Condvar),
+    frame_duration: Duration,
+) -> Result<()> {
+    match request.path.as_str() {
+        "/ws" if request.is_websocket_upgrade() => {
+            let mut websocket = http::accept_websocket(&request, stream)?;
+            let (frame, updated) = frame;
+            // Nothing has been published until the sequence is incremented
+            let mut sent_sequence = 0;
+
+            loop {
+                // Answers pings and notices closed connections between frames
+                if !http::poll_websocket(&mut websocket)? {
+                    return Ok(());
+                }
+
+                // Wait for a frame that has not been sent yet
+                let message = {
+                    let frame = frame.lock().unwrap();
+                    let (frame, _) = updated
+                        .wait_timeout_while(frame, http::WEBSOCKET_POLL_INTERVAL, |frame| {
+                            frame.sequence == sent_sequence
+                        })
+                        .unwrap();
+                    if frame.sequence == sent_sequence {
+                        continue;
+                    }
+                    sent_sequence = frame.sequence;
+
+                    encode_frame(&frame.strips)
+                };
+
+                let sent_at = Instant::now();
+                websocket.send(Message::Binary(message.into()))?;
+
+                thread::sleep(frame_duration.saturating_sub(sent_at.elapsed()));
+            }
+        }
+        "/" | "/index.html" => http::respond(
+            stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            PREVIEW_PAGE.as_bytes(),
+        ),
+        _ => http::not_found(stream),
+    }
+}
+
+pub fn encode_frame(strips: &[Vec<RGB8>]) -> Vec<u8> {
+    let led_count = strips.iter().map(Vec::len).sum::<usize>();
+    let mut bytes = Vec::with_capacity(2 + 2 * strips.len() + 3 * led_count);
+
+    bytes.extend_from_slice(&(strips.len() as u16).to_be_bytes());
+    for strip in strips.iter() {
+        bytes.extend_from_slice(&(strip.len() as u16).to_be_bytes());
+    }
+    for led in strips.iter().flatten() {
+        bytes.extend_from_slice(&[led.r, led.g, led.b]);
+    }
+
+    bytes
+}
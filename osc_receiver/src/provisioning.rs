@@ -0,0 +1,655 @@
+//! Wi-Fi provisioning for receivers that have no other way to be configured, eg. the ESP32C3.
+//!
+//! When none of a receiver's stored credentials work it starts an open access point and serves a
+//! small configuration page at [`AP_IP`]. Every DNS query is answered with that address so that
+//! phones and laptops joining the access point pop the page up as a captive portal. Submitting
+//! the form returns the new [`Settings`] for the receiver to store before restarting.
+//!
+//! The receiver has a static address while the access point is up, so [`DhcpServer`] hands out
+//! addresses to the devices that join it.
+//!
+//! This module only deals in bytes so that the portal can be tested on the host. The receiver
+//! feeds it HTTP requests, DNS queries and DHCP requests from its own network stack.
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use anyhow::{anyhow, bail, Result};
+use core::fmt::Write;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+
+use crate::mdns::{read_name, write_rdata, write_record_header, CLASS_IN, TYPE_A};
+
+/// The access point's address, which is also the address of the configuration page
+pub const AP_IP: [u8; 4] = [192, 168, 4, 1];
+pub const HTTP_PORT: u16 = 80;
+pub const DNS_PORT: u16 = 53;
+pub const DHCP_SERVER_PORT: u16 = 67;
+pub const DHCP_CLIENT_PORT: u16 = 68;
+
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+
+const MAX_SSID_LEN: usize = 32;
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 63;
+const MAX_NAME_LEN: usize = 63;
+
+// Larger requests are rejected
+pub const MAX_REQUEST_LEN: usize = 4 * 1024;
+
+// Bumped whenever the encoded settings change
+const SETTINGS_VERSION: u8 = 1;
+
+/// The network to join and the receiver settings entered on the configuration page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub ssid: String,
+    /// Empty for open networks
+    pub password: String,
+    /// The name the receiver is advertised as with mDNS, which is also its hostname
+    pub name: String,
+    pub osc_port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub method: &'a str,
+    /// The request path without the query string
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    /// Set for redirects
+    pub location: Option<String>,
+    pub body: String,
+}
+
+/// Serves the configuration page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Portal {
+    /// Fills in the form, eg. with the settings that didn't work
+    pub settings: Settings,
+    /// The SSIDs of the networks in range, suggested on the form
+    pub networks: Vec<String>,
+}
+
+impl Settings {
+    pub fn new(ssid: &str, password: &str, name: &str) -> Self {
+        Self {
+            ssid: ssid.to_string(),
+            password: password.to_string(),
+            name: name.to_string(),
+            osc_port: DEFAULT_OSC_PORT,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.ssid.is_empty() || self.ssid.len() > MAX_SSID_LEN {
+            bail!(
+                "The network name must be between 1 and {} bytes long",
+                MAX_SSID_LEN
+            );
+        }
+        if !self.password.is_empty()
+            && !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&self.password.len())
+        {
+            bail!(
+                "The password must be empty for open networks or between {} and {} characters long",
+                MIN_PASSWORD_LEN,
+                MAX_PASSWORD_LEN
+            );
+        }
+        if self.name.is_empty()
+            || self.name.len() > MAX_NAME_LEN
+            || self.name.starts_with('-')
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        {
+            bail!(
+                "The receiver name must be 1 to {} letters, digits or dashes, not starting with a dash",
+                MAX_NAME_LEN
+            );
+        }
+        if self.osc_port == 0 {
+            bail!("The OSC port must be between 1 and 65535");
+        }
+
+        Ok(())
+    }
+
+    /// Encodes the settings for storage, eg. in flash.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![SETTINGS_VERSION];
+
+        for field in [&self.ssid, &self.password, &self.name] {
+            bytes.push(field.len() as u8);
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.extend_from_slice(&self.osc_port.to_le_bytes());
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let (version, mut bytes) = bytes
+            .split_first()
+            .ok_or_else(|| anyhow!("The settings are empty"))?;
+
+        if *version != SETTINGS_VERSION {
+            bail!("Unsupported settings version {}", version);
+        }
+
+        let mut field = || -> Result<String> {
+            let (len, rest) = bytes
+                .split_first()
+                .ok_or_else(|| anyhow!("The settings are truncated"))?;
+            let field = rest
+                .get(..*len as usize)
+                .ok_or_else(|| anyhow!("The settings are truncated"))?;
+            bytes = &rest[*len as usize..];
+
+            Ok(core::str::from_utf8(field)?.to_string())
+        };
+
+        let ssid = field()?;
+        let password = field()?;
+        let name = field()?;
+
+        let osc_port = match bytes {
+            [low, high] => u16::from_le_bytes([*low, *high]),
+            _ => bail!("The settings are truncated"),
+        };
+
+        let settings = Self {
+            ssid,
+            password,
+            name,
+            osc_port,
+        };
+        settings.validate()?;
+
+        Ok(settings)
+    }
+}
+
+impl<'a> Request<'a> {
+    /// Parses an HTTP request. Returns `None` until the whole request, including its body, has
+    /// been received.
+    pub fn parse(bytes: &'a [u8]) -> Result<Option<Self>> {
+        let head_len = match bytes.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(head_len) => head_len,
+            None if bytes.len() > MAX_REQUEST_LEN => bail!("The request is too large"),
+            None => return Ok(None),
+        };
+
+        let head = core::str::from_utf8(&bytes[..head_len])?;
+        let mut lines = head.split("\r\n");
+
+        let mut request_line = lines.next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default();
+        let target = request_line
+            .next()
+            .ok_or_else(|| anyhow!("Invalid request line {:?}", head))?;
+        let path = target.split('?').next().unwrap_or_default();
+
+        let mut content_length = 0;
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse()?;
+                }
+            }
+        }
+
+        let body_start = head_len + 4;
+        let request_len = match body_start.checked_add(content_length) {
+            Some(request_len) if request_len <= MAX_REQUEST_LEN => request_len,
+            _ => bail!("The request is too large"),
+        };
+
+        Ok(bytes
+            .get(body_start..request_len)
+            .map(|body| Self { method, path, body }))
+    }
+}
+
+impl Response {
+    fn page(status: u16, body: String) -> Self {
+        Self {
+            status,
+            location: None,
+            body,
+        }
+    }
+
+    fn redirect(location: String) -> Self {
+        Self {
+            status: 302,
+            location: Some(location),
+            body: String::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let reason = match self.status {
+            200 => "OK",
+            302 => "Found",
+            400 => "Bad Request",
+            _ => "Method Not Allowed",
+        };
+
+        let mut head = String::new();
+        let _ = write!(head, "HTTP/1.1 {} {}\r\n", self.status, reason);
+        if let Some(location) = &self.location {
+            let _ = write!(head, "Location: {}\r\n", location);
+        }
+        let _ = write!(
+            head,
+            "Content-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+            self.body.len()
+        );
+
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(self.body.as_bytes());
+
+        bytes
+    }
+}
+
+impl Portal {
+    pub fn new(settings: Settings, networks: Vec<String>) -> Self {
+        Self { settings, networks }
+    }
+
+    /// Handles a request to the portal. Returns the response and, once the form has been
+    /// submitted with valid settings, the settings to store.
+    pub fn handle(&self, request: &Request) -> (Response, Option<Settings>) {
+        match (request.method, request.path) {
+            ("GET", "/") => (Response::page(200, self.form(&self.settings, None)), None),
+            ("POST", "/") => {
+                let settings = self.parse_form(request.body);
+
+                match settings.validate() {
+                    Ok(()) => (Response::page(200, saved_page(&settings)), Some(settings)),
+                    Err(err) => {
+                        let error = err.to_string();
+                        (
+                            Response::page(400, self.form(&settings, Some(&error))),
+                            None,
+                        )
+                    }
+                }
+            }
+            // Operating systems check for a captive portal by fetching a known page, eg.
+            // `/generate_204`. Redirecting every other page makes them show the form
+            ("GET", _) | ("HEAD", _) => (
+                Response::redirect(format!(
+                    "http://{}.{}.{}.{}/",
+                    AP_IP[0], AP_IP[1], AP_IP[2], AP_IP[3]
+                )),
+                None,
+            ),
+            _ => (Response::page(405, String::new()), None),
+        }
+    }
+
+    /// The settings submitted with the form. Fields missing from the form keep their current
+    /// values, as does the password when it is left empty, unless the network is marked as open.
+    fn parse_form(&self, body: &[u8]) -> Settings {
+        let mut settings = self.settings.clone();
+        let mut open_network = false;
+        let body = String::from_utf8_lossy(body);
+
+        for pair in body.split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = url_decode(value);
+
+            match key {
+                "ssid" => settings.ssid = value,
+                "password" if !value.is_empty() => settings.password = value,
+                "open_network" => open_network = true,
+                "name" => settings.name = value,
+                // An invalid port is caught by the validation
+                "osc_port" => settings.osc_port = value.trim().parse().unwrap_or(0),
+                _ => (),
+            }
+        }
+
+        if open_network {
+            settings.password.clear();
+        }
+
+        settings
+    }
+
+    fn form(&self, settings: &Settings, error: Option<&str>) -> String {
+        let mut page = String::new();
+
+        page.push_str(PAGE_HEAD);
+        page.push_str("<h1>Receiver Wi-Fi Setup</h1>");
+        if let Some(error) = error {
+            let _ = write!(page, "<p class=\"error\">{}</p>", escape(error));
+        }
+
+        page.push_str("<form method=\"post\" action=\"/\">");
+        let _ = write!(
+            page,
+            "<label>Network<input name=\"ssid\" list=\"networks\" maxlength=\"{}\" value=\"{}\" required></label>",
+            MAX_SSID_LEN,
+            escape(&settings.ssid)
+        );
+        page.push_str("<datalist id=\"networks\">");
+        for network in self.networks.iter() {
+            let _ = write!(page, "<option value=\"{}\">", escape(network));
+        }
+        page.push_str("</datalist>");
+        // The stored password isn't filled in so that it can't be read back from the portal
+        let _ = write!(
+            page,
+            "<label>Password (leave empty to keep the saved one)<input name=\"password\" type=\"password\" maxlength=\"{}\"></label>",
+            MAX_PASSWORD_LEN
+        );
+        page.push_str(
+            "<label><input name=\"open_network\" type=\"checkbox\">Open network (no password)</label>",
+        );
+        let _ = write!(
+            page,
+            "<label>Receiver name<input name=\"name\" maxlength=\"{}\" value=\"{}\" required></label>",
+            MAX_NAME_LEN,
+            escape(&settings.name)
+        );
+        let _ = write!(
+            page,
+            "<label>OSC port<input name=\"osc_port\" type=\"number\" min=\"1\" max=\"65535\" value=\"{}\" required></label>",
+            settings.osc_port
+        );
+        page.push_str("<button type=\"submit\">Save and restart</button></form>");
+        page.push_str(PAGE_TAIL);
+
+        page
+    }
+}
+
+/// Replies to a DNS query with [`AP_IP`] for every A record question, or returns `None` if the
+/// packet isn't a query.
+pub fn dns_reply(query: &[u8]) -> Option<Vec<u8>> {
+    let header = query.get(..12)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let question_count = u16::from_be_bytes([header[4], header[5]]);
+
+    // Only answer standard queries
+    if flags & 0xF800 != 0 {
+        return None;
+    }
+
+    let mut questions = Vec::new();
+    let mut offset = 12;
+    for _ in 0..question_count {
+        let (_, name_end) = read_name(query, offset)?;
+        let question = query.get(name_end..name_end + 4)?;
+        let record_type = u16::from_be_bytes([question[0], question[1]]);
+
+        questions.push((offset, record_type));
+        offset = name_end + 4;
+    }
+
+    let answers = questions
+        .iter()
+        .filter(|(_, record_type)| *record_type == TYPE_A)
+        .count() as u16;
+
+    let mut reply = Vec::with_capacity(offset + answers as usize * 16);
+    reply.extend_from_slice(&header[..2]);
+    // A response to a recursive query that recursion was available for
+    reply.extend_from_slice(&(0x8180 | (flags & 0x0100)).to_be_bytes());
+    reply.extend_from_slice(&question_count.to_be_bytes());
+    reply.extend_from_slice(&answers.to_be_bytes());
+    reply.extend_from_slice(&[0, 0, 0, 0]);
+    // The questions are copied unchanged so that the answers can point at their names
+    reply.extend_from_slice(&query[12..offset]);
+
+    for (name_offset, record_type) in questions {
+        if record_type != TYPE_A {
+            continue;
+        }
+
+        reply.extend_from_slice(&(0xC000 | name_offset as u16).to_be_bytes());
+        write_record_header(&mut reply, TYPE_A, CLASS_IN, DNS_TTL);
+        write_rdata(&mut reply, |out| out.extend_from_slice(&AP_IP));
+    }
+
+    Some(reply)
+}
+
+// Short so that devices stop using the portal's address soon after the receiver is configured
+const DNS_TTL: u32 = 10;
+
+// Addresses are handed out from AP_IP + 1, evicting the oldest lease once they have all been used
+const MAX_LEASES: usize = 8;
+const LEASE_TIME_SECS: u32 = 60 * 60;
+
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+// The fixed part of a DHCP message, up to the options
+const DHCP_HEADER_LEN: usize = 240;
+// Some clients ignore replies shorter than a BOOTP message
+const MIN_DHCP_REPLY_LEN: usize = 300;
+
+const DHCP_DISCOVER: u8 = 1;
+const DHCP_OFFER: u8 = 2;
+const DHCP_REQUEST: u8 = 3;
+const DHCP_ACK: u8 = 5;
+const DHCP_NAK: u8 = 6;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS_SERVER: u8 = 6;
+const OPTION_REQUESTED_IP: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_END: u8 = 255;
+
+/// A minimal DHCP server for the devices joining the access point, which also points their DNS
+/// at [`AP_IP`] so that [`dns_reply`] can send them to the configuration page.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DhcpServer {
+    /// The hardware addresses leases were given to, indexed by address
+    leases: Vec<[u8; 6]>,
+    /// The lease to reuse next once every address has been handed out
+    next_eviction: usize,
+}
+
+impl DhcpServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replies to a DHCP discover with an offer and to a request with an ack, or a nak if the
+    /// client asked for an address it can't have. Returns `None` for anything else, including
+    /// requests meant for another server. Replies are broadcast to [`DHCP_CLIENT_PORT`].
+    pub fn reply(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        let header = request.get(..DHCP_HEADER_LEN)?;
+        // Only ethernet requests
+        if header[0] != 1 || header[1] != 1 || header[2] != 6 || header[236..] != DHCP_MAGIC_COOKIE
+        {
+            return None;
+        }
+
+        let mut message_type = None;
+        let mut requested_ip = None;
+        let mut server_id = None;
+        let mut options = &request[DHCP_HEADER_LEN..];
+        while let Some((&code, rest)) = options.split_first() {
+            match code {
+                OPTION_PAD => {
+                    options = rest;
+                    continue;
+                }
+                OPTION_END => break,
+                _ => (),
+            }
+            let (&len, rest) = rest.split_first()?;
+            let value = rest.get(..len as usize)?;
+            match (code, value) {
+                (OPTION_MESSAGE_TYPE, &[message_type_value]) => {
+                    message_type = Some(message_type_value)
+                }
+                (OPTION_REQUESTED_IP, &[a, b, c, d]) => requested_ip = Some([a, b, c, d]),
+                (OPTION_SERVER_ID, &[a, b, c, d]) => server_id = Some([a, b, c, d]),
+                _ => (),
+            }
+            options = &rest[len as usize..];
+        }
+
+        let mut hardware_address = [0; 6];
+        hardware_address.copy_from_slice(&header[28..34]);
+
+        let reply_type = match message_type? {
+            DHCP_DISCOVER => DHCP_OFFER,
+            DHCP_REQUEST => {
+                if matches!(server_id, Some(server_id) if server_id != AP_IP) {
+                    return None;
+                }
+
+                // Clients rebooting or renewing ask for the address they had before
+                let ciaddr = [header[12], header[13], header[14], header[15]];
+                let requested_ip = requested_ip.unwrap_or(ciaddr);
+                if requested_ip != [0; 4] && requested_ip != self.lease(hardware_address) {
+                    DHCP_NAK
+                } else {
+                    DHCP_ACK
+                }
+            }
+            _ => return None,
+        };
+
+        let mut reply = Vec::with_capacity(MIN_DHCP_REPLY_LEN);
+        // A boot reply with the request's hardware type and address length
+        reply.extend_from_slice(&[2, header[1], header[2], 0]);
+        // The transaction ID
+        reply.extend_from_slice(&header[4..8]);
+        // No seconds elapsed, keeping the client's broadcast flag
+        reply.extend_from_slice(&[0, 0, header[10], header[11]]);
+        // ciaddr
+        reply.extend_from_slice(&[0; 4]);
+        if reply_type == DHCP_NAK {
+            reply.extend_from_slice(&[0; 8]);
+        } else {
+            reply.extend_from_slice(&self.lease(hardware_address));
+            reply.extend_from_slice(&AP_IP);
+        }
+        // giaddr, chaddr, sname and file are copied from the request
+        reply.extend_from_slice(&header[24..]);
+
+        reply.extend_from_slice(&[OPTION_MESSAGE_TYPE, 1, reply_type]);
+        reply.extend_from_slice(&[OPTION_SERVER_ID, 4]);
+        reply.extend_from_slice(&AP_IP);
+        if reply_type != DHCP_NAK {
+            reply.extend_from_slice(&[OPTION_LEASE_TIME, 4]);
+            reply.extend_from_slice(&LEASE_TIME_SECS.to_be_bytes());
+            reply.extend_from_slice(&[OPTION_SUBNET_MASK, 4, 255, 255, 255, 0]);
+            for option in [OPTION_ROUTER, OPTION_DNS_SERVER] {
+                reply.extend_from_slice(&[option, 4]);
+                reply.extend_from_slice(&AP_IP);
+            }
+        }
+        reply.push(OPTION_END);
+        reply.resize(reply.len().max(MIN_DHCP_REPLY_LEN), OPTION_PAD);
+
+        Some(reply)
+    }
+
+    /// Returns the address leased to a client, leasing it a new one if it has none.
+    fn lease(&mut self, hardware_address: [u8; 6]) -> [u8; 4] {
+        let index = match self
+            .leases
+            .iter()
+            .position(|leased| *leased == hardware_address)
+        {
+            Some(index) => index,
+            None if self.leases.len() < MAX_LEASES => {
+                self.leases.push(hardware_address);
+                self.leases.len() - 1
+            }
+            None => {
+                // Leases are reused in the order they were given out, so this is the oldest one
+                let index = self.next_eviction;
+                self.next_eviction = (index + 1) % MAX_LEASES;
+                self.leases[index] = hardware_address;
+                index
+            }
+        };
+
+        [AP_IP[0], AP_IP[1], AP_IP[2], AP_IP[3] + 1 + index as u8]
+    }
+}
+
+const PAGE_HEAD: &str = "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width, initial-scale=1\"><title>Receiver Wi-Fi Setup</title><style>body{font-family:sans-serif;max-width:24em;margin:2em auto;padding:0 1em}label{display:block;margin:1em 0}input{display:block;width:100%;box-sizing:border-box;padding:.5em}input[type=checkbox]{display:inline;width:auto;margin-right:.5em}button{padding:.5em 1em}.error{color:#b00}</style></head><body>";
+const PAGE_TAIL: &str = "</body></html>";
+
+fn saved_page(settings: &Settings) -> String {
+    let mut page = String::from(PAGE_HEAD);
+    let _ = write!(
+        page,
+        "<h1>Saved</h1><p>The receiver is restarting and joining {}. Once it has joined it can be found as {}.local on port {}.</p>",
+        escape(&settings.ssid),
+        escape(&settings.name),
+        settings.osc_port
+    );
+    page.push_str(PAGE_TAIL);
+
+    page
+}
+
+/// Decodes a value from an `application/x-www-form-urlencoded` body.
+pub fn url_decode(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut input = value.bytes();
+
+    while let Some(byte) = input.next() {
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let high = input.next();
+                let low = input.next();
+                let hex = |digit: Option<u8>| (digit? as char).to_digit(16);
+
+                match (hex(high), hex(low)) {
+                    (Some(high), Some(low)) => bytes.push((high * 16 + low) as u8),
+                    // Keep malformed escapes as they were
+                    _ => {
+                        bytes.push(b'%');
+                        bytes.extend(high.iter().chain(low.iter()));
+                    }
+                }
+            }
+            byte => bytes.push(byte),
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Escapes text for HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
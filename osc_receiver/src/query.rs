@@ -0,0 +1,193 @@
+//! Queries that the receiver replies to, sent back to the address the query came from.
+//!
+//! - `/ping [args...]` replies with `/pong` and the same arguments, so a controller can check that
+//!   a receiver is alive, measure the round trip time or discover receivers with a broadcast
+//! - `/info` replies with `/info <version> <strip count>` followed by `<length> <led type>` for
+//!   each strip
+//! - `/led_strips/{n}/info` replies with `/led_strips/{n}/info <length> <led type>`
+//! - `/status` replies with `/status <uptime seconds> <frames per second> <frames displayed>
+//!   <invalid packets> <write errors>`
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::option::Option::{self, *};
+use rosc::{OscMessage, OscPacket, OscType};
+
+pub const PING_ADDR: &str = "/ping";
+pub const PONG_ADDR: &str = "/pong";
+pub const INFO_ADDR: &str = "/info";
+pub const STATUS_ADDR: &str = "/status";
+
+/// How long the frame rate is averaged over
+const FPS_WINDOW_MS: u64 = 1000;
+
+/// Returns the reply to `packet` if it is a query, or `None` if it should be displayed.
+pub fn reply(packet: &OscPacket) -> Option<OscPacket> {
+    let OscMessage { addr, args } = match packet {
+        OscPacket::Message(message) => message,
+        OscPacket::Bundle(_) => return None,
+    };
+
+    match addr.as_str() {
+        PING_ADDR => Some(OscPacket::Message(OscMessage {
+            addr: PONG_ADDR.to_string(),
+            args: args.clone(),
+        })),
+        _ => None,
+    }
+}
+
+/// The fixed description of a receiver reported by `/info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceiverInfo {
+    /// The receiver's firmware version
+    pub version: String,
+    pub strips: Vec<StripInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StripInfo {
+    pub length: usize,
+    /// eg. `APA102` or `WS2812B`
+    pub led_type: String,
+}
+
+/// The counters reported by `/status`, updated by the receiver's main loop.
+///
+/// Times are in milliseconds from any fixed starting point, eg. since the receiver booted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    pub started_at_ms: u64,
+    /// The number of frames written to the LEDs
+    pub frames: u64,
+    /// The number of packets that could not be decoded as OSC
+    pub invalid_packets: u64,
+    /// The number of frames that failed to write to the LEDs
+    pub write_errors: u64,
+    fps: f32,
+    window_started_at_ms: u64,
+    window_frames: u32,
+}
+
+/// Replies to every query, including the queries that depend on the receiver's configuration and
+/// status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryHandler {
+    pub info: ReceiverInfo,
+    pub status: Status,
+}
+
+impl StripInfo {
+    pub fn new(length: usize, led_type: &str) -> Self {
+        Self {
+            length,
+            led_type: led_type.to_string(),
+        }
+    }
+}
+
+impl Status {
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            started_at_ms: now_ms,
+            window_started_at_ms: now_ms,
+            ..Self::default()
+        }
+    }
+
+    /// Records a frame being written to the LEDs.
+    pub fn frame_displayed(&mut self, now_ms: u64) {
+        self.frames += 1;
+        self.window_frames += 1;
+        self.update_fps(now_ms);
+    }
+
+    /// The number of frames displayed per second over the last second.
+    pub fn fps(&self, now_ms: u64) -> f32 {
+        // Stop reporting the previous frame rate once frames stop arriving
+        if now_ms.saturating_sub(self.window_started_at_ms) >= 2 * FPS_WINDOW_MS {
+            0.0
+        } else {
+            self.fps
+        }
+    }
+
+    pub fn uptime_ms(&self, now_ms: u64) -> u64 {
+        now_ms.saturating_sub(self.started_at_ms)
+    }
+
+    fn update_fps(&mut self, now_ms: u64) {
+        let elapsed_ms = now_ms.saturating_sub(self.window_started_at_ms);
+
+        if elapsed_ms >= FPS_WINDOW_MS {
+            self.fps = self.window_frames as f32 * 1000.0 / elapsed_ms as f32;
+            self.window_frames = 0;
+            self.window_started_at_ms = now_ms;
+        }
+    }
+}
+
+impl QueryHandler {
+    pub fn new(info: ReceiverInfo, now_ms: u64) -> Self {
+        Self {
+            info,
+            status: Status::new(now_ms),
+        }
+    }
+
+    /// Returns the reply to `packet` if it is a query, or `None` if it should be displayed.
+    pub fn reply(&self, packet: &OscPacket, now_ms: u64) -> Option<OscPacket> {
+        if let Some(reply) = reply(packet) {
+            return Some(reply);
+        }
+
+        let addr = match packet {
+            OscPacket::Message(OscMessage { addr, .. }) => addr.as_str(),
+            OscPacket::Bundle(_) => return None,
+        };
+
+        let args = match addr {
+            INFO_ADDR => {
+                let mut args = vec![
+                    OscType::String(self.info.version.clone()),
+                    int(self.info.strips.len()),
+                ];
+                for strip in self.info.strips.iter() {
+                    args.push(int(strip.length));
+                    args.push(OscType::String(strip.led_type.clone()));
+                }
+                args
+            }
+            STATUS_ADDR => vec![
+                OscType::Float(self.status.uptime_ms(now_ms) as f32 / 1000.0),
+                OscType::Float(self.status.fps(now_ms)),
+                int(self.status.frames),
+                int(self.status.invalid_packets),
+                int(self.status.write_errors),
+            ],
+            _ => {
+                let strip = addr
+                    .strip_prefix("/led_strips/")
+                    .and_then(|addr| addr.strip_suffix("/info"))
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| self.info.strips.get(index))?;
+
+                vec![int(strip.length), OscType::String(strip.led_type.clone())]
+            }
+        };
+
+        Some(OscPacket::Message(OscMessage {
+            addr: addr.to_string(),
+            args,
+        }))
+    }
+}
+
+/// Most OSC apps only support 32 bit integers, so larger counts are clamped rather than sent as
+/// 64 bit integers.
+fn int<T>(value: T) -> OscType
+where
+    i32: TryFrom<T>,
+{
+    OscType::Int(i32::try_from(value).unwrap_or(i32::MAX))
+}
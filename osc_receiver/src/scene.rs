@@ -0,0 +1,81 @@
+//! Scenes are snapshots of every strip, eg. the default scene a receiver shows as soon as it powers
+//! on, before any controller has connected.
+//!
+//! `/scene/save_default` saves the LEDs currently displayed as the default scene.
+//!
+//! A scene is stored as a show file containing a single frame (see [`crate::show`]) so that saved
+//! scenes can be rendered with `show_tools` like any other show.
+use alloc::vec::Vec;
+use anyhow::{anyhow, Result};
+use core::iter::Iterator;
+use core::result::Result::*;
+use rosc::{OscMessage, OscPacket};
+use smart_leds::RGB8;
+
+use crate::led_strip::LedStrip;
+use crate::show::{ShowFrame, ShowHeader, ShowReader};
+
+pub const SAVE_DEFAULT_ADDR: &str = "/scene/save_default";
+
+/// The file extension used for scene files
+pub const EXTENSION: &str = "scene";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene {
+    /// The LEDs of each strip
+    pub strips: Vec<Vec<RGB8>>,
+}
+
+impl Scene {
+    /// Snapshots the LEDs currently set on the strips.
+    pub fn capture(led_strips: &[LedStrip]) -> Self {
+        Self {
+            strips: led_strips
+                .iter()
+                .map(|led_strip| led_strip.data.clone())
+                .collect(),
+        }
+    }
+
+    /// Copies the scene into the strips without writing them to the LEDs. LEDs that the scene
+    /// doesn't cover, eg. because a strip got longer since the scene was saved, are left unchanged.
+    pub fn apply(&self, led_strips: &mut [LedStrip]) {
+        for (led_strip, strip) in led_strips.iter_mut().zip(self.strips.iter()) {
+            for (led, color) in led_strip.data.iter_mut().zip(strip.iter()) {
+                *led = *color;
+            }
+        }
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let header = ShowHeader {
+            started_at_unix_ms: 0,
+            strip_lengths: self.strips.iter().map(|strip| strip.len()).collect(),
+        };
+        let frame = ShowFrame {
+            timestamp_ms: 0,
+            strips: self.strips.clone(),
+        };
+
+        let mut bytes = Vec::new();
+        header.encode(&mut bytes)?;
+        frame.encode(&mut bytes);
+
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let frame = ShowReader::new(bytes)?
+            .next()
+            .ok_or_else(|| anyhow!("The scene contains no frames"))??;
+
+        Ok(Self {
+            strips: frame.strips,
+        })
+    }
+}
+
+/// Returns true if the packet is a `/scene/save_default` message.
+pub fn is_save_default(packet: &OscPacket) -> bool {
+    matches!(packet, OscPacket::Message(OscMessage { addr, .. }) if addr == SAVE_DEFAULT_ADDR)
+}
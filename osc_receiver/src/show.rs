@@ -0,0 +1,306 @@
+//! The show file format used to record and play back the frames displayed on the LED strips.
+//!
+//! A show file is a header followed by any number of frames. All integers are little endian.
+//!
+//! Header:
+//!
+//! - the magic bytes `RAVESHOW`
+//! - the format version as a `u16` (currently `1`)
+//! - the unix time the recording started at in milliseconds as a `u64`
+//! - the number of strips as a `u16`
+//! - the length of each strip as a `u16`
+//!
+//! Frame:
+//!
+//! - the time since the recording started in milliseconds as a `u32`
+//! - the frame encoding as a `u8`: `0` for raw or `1` for run-length encoded
+//! - the length of the encoded LEDs in bytes as a `u32`
+//! - the encoded LEDs of every strip, in strip order. Raw frames contain the red, green and blue
+//!   bytes of each LED. Run-length encoded frames contain `[count, red, green, blue]` runs of
+//!   identical LEDs which may span multiple strips.
+use alloc::vec::Vec;
+use anyhow::{anyhow, bail, Result};
+use core::convert::TryInto;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use smart_leds::RGB8;
+
+pub const MAGIC: &[u8; 8] = b"RAVESHOW";
+pub const VERSION: u16 = 1;
+
+/// The file extension used for show files
+pub const EXTENSION: &str = "show";
+
+const ENCODING_RAW: u8 = 0;
+const ENCODING_RUN_LENGTH: u8 = 1;
+
+const FRAME_HEADER_LENGTH: usize = 4 + 1 + 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowHeader {
+    pub started_at_unix_ms: u64,
+    pub strip_lengths: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShowFrame {
+    /// Milliseconds since the recording started
+    pub timestamp_ms: u32,
+    /// The LEDs of each strip
+    pub strips: Vec<Vec<RGB8>>,
+}
+
+impl ShowHeader {
+    pub fn led_count(&self) -> usize {
+        self.strip_lengths.iter().sum()
+    }
+
+    /// Encodes the header. Fails if there are more than 65535 strips or a strip is longer than
+    /// 65535 LEDs, since the format stores them as `u16`s.
+    pub fn encode(&self, bytes: &mut Vec<u8>) -> Result<()> {
+        let strip_count: u16 = self.strip_lengths.len().try_into().map_err(|_| {
+            anyhow!(
+                "Shows can't contain {} strips. The most is {}",
+                self.strip_lengths.len(),
+                u16::MAX
+            )
+        })?;
+        let strip_lengths = self
+            .strip_lengths
+            .iter()
+            .map(|strip_length| {
+                (*strip_length).try_into().map_err(|_| {
+                    anyhow!(
+                        "Shows can't contain a strip of {} LEDs. The longest is {}",
+                        strip_length,
+                        u16::MAX
+                    )
+                })
+            })
+            .collect::<Result<Vec<u16>>>()?;
+
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.started_at_unix_ms.to_le_bytes());
+        bytes.extend_from_slice(&strip_count.to_le_bytes());
+        for strip_length in strip_lengths {
+            bytes.extend_from_slice(&strip_length.to_le_bytes());
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the header at the start of `bytes`, returning it and its length in bytes.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut reader = Reader::new(bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            bail!("Not a show file");
+        }
+
+        let version = reader.u16()?;
+        if version != VERSION {
+            bail!("Unsupported show file version: {:?}", version);
+        }
+
+        let started_at_unix_ms = reader.u64()?;
+        let strip_count = reader.u16()? as usize;
+        let strip_lengths = (0..strip_count)
+            .map(|_| reader.u16().map(|length| length as usize))
+            .collect::<Result<Vec<_>>>()?;
+
+        let header = Self {
+            started_at_unix_ms,
+            strip_lengths,
+        };
+
+        Ok((header, reader.offset))
+    }
+}
+
+impl ShowFrame {
+    /// Encodes the frame, run-length encoding the LEDs if that is smaller than the raw LEDs.
+    pub fn encode(&self, bytes: &mut Vec<u8>) {
+        Self::encode_strips(
+            self.timestamp_ms,
+            self.strips.iter().map(|strip| &strip[..]),
+            bytes,
+        )
+    }
+
+    pub fn encode_strips<'a, I>(timestamp_ms: u32, strips: I, bytes: &mut Vec<u8>)
+    where
+        I: Iterator<Item = &'a [RGB8]> + Clone,
+    {
+        let leds = || strips.clone().flat_map(|strip| strip.iter());
+
+        let led_count = leds().count();
+        let mut runs: Vec<(u8, RGB8)> = Vec::new();
+
+        for led in leds() {
+            match runs.last_mut() {
+                Some((count, color)) if *color == *led && *count < u8::MAX => *count += 1,
+                _ => runs.push((1, *led)),
+            }
+        }
+
+        bytes.extend_from_slice(&timestamp_ms.to_le_bytes());
+
+        if runs.len() * 4 < led_count * 3 {
+            bytes.push(ENCODING_RUN_LENGTH);
+            bytes.extend_from_slice(&((runs.len() * 4) as u32).to_le_bytes());
+            for (count, color) in runs {
+                bytes.extend_from_slice(&[count, color.r, color.g, color.b]);
+            }
+        } else {
+            bytes.push(ENCODING_RAW);
+            bytes.extend_from_slice(&((led_count * 3) as u32).to_le_bytes());
+            for led in leds() {
+                bytes.extend_from_slice(&[led.r, led.g, led.b]);
+            }
+        }
+    }
+
+    /// Decodes the frame at the start of `bytes`, returning it and its length in bytes.
+    pub fn decode(header: &ShowHeader, bytes: &[u8]) -> Result<(Self, usize)> {
+        let mut reader = Reader::new(bytes);
+
+        let timestamp_ms = reader.u32()?;
+        let encoding = reader.u8()?;
+        let length = reader.u32()? as usize;
+        let payload = reader.take(length)?;
+
+        // The header isn't trusted to size the allocation, so it is limited by what the payload
+        // can hold
+        let mut leds = Vec::new();
+
+        match encoding {
+            ENCODING_RAW => {
+                leds.reserve(header.led_count().min(payload.len() / 3));
+                leds.extend(payload.chunks_exact(3).map(|led| RGB8 {
+                    r: led[0],
+                    g: led[1],
+                    b: led[2],
+                }));
+            }
+            ENCODING_RUN_LENGTH => {
+                leds.reserve(header.led_count().min(payload.len() / 4 * u8::MAX as usize));
+                for run in payload.chunks_exact(4) {
+                    let color = RGB8 {
+                        r: run[1],
+                        g: run[2],
+                        b: run[3],
+                    };
+                    leds.extend(core::iter::repeat_n(color, run[0] as usize));
+                }
+            }
+            _ => bail!("Invalid frame encoding: {:?}", encoding),
+        }
+
+        if leds.len() != header.led_count() {
+            bail!(
+                "Frame contains {} LEDs, expected {}",
+                leds.len(),
+                header.led_count()
+            );
+        }
+
+        let mut leds = leds.into_iter();
+        let strips = header
+            .strip_lengths
+            .iter()
+            .map(|length| leds.by_ref().take(*length).collect())
+            .collect();
+
+        let frame = Self {
+            timestamp_ms,
+            strips,
+        };
+
+        Ok((frame, FRAME_HEADER_LENGTH + length))
+    }
+}
+
+/// Reads the header and frames of a show file.
+#[derive(Debug, Clone)]
+pub struct ShowReader<'a> {
+    pub header: ShowHeader,
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ShowReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        let (header, offset) = ShowHeader::decode(bytes)?;
+
+        Ok(Self {
+            header,
+            bytes,
+            offset,
+        })
+    }
+}
+
+impl<'a> Iterator for ShowReader<'a> {
+    type Item = Result<ShowFrame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.bytes.len() {
+            return None;
+        }
+
+        match ShowFrame::decode(&self.header, &self.bytes[self.offset..]) {
+            Ok((frame, length)) => {
+                self.offset += length;
+                Some(Ok(frame))
+            }
+            Err(err) => {
+                // Stop reading after the first invalid frame, eg. a frame truncated by a power cut
+                self.offset = self.bytes.len();
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(length)
+            .ok_or_else(|| anyhow!("Unexpected end of show file"))?;
+        let bytes = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or_else(|| anyhow!("Unexpected end of show file"))?;
+        self.offset = end;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
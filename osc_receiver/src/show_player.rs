@@ -0,0 +1,227 @@
+//! Plays back show files recorded in the `show` format.
+//!
+//! The player is controlled over OSC with the following transport commands:
+//!
+//! - `/show/play` resumes playback
+//! - `/show/pause` pauses playback on the current frame
+//! - `/show/seek <seconds>` jumps to a position in the show
+//! - `/show/speed <multiplier>` sets the playback speed, eg. `0.5` for half speed
+//! - `/show/loop <bool>` sets whether the show restarts once it ends
+use alloc::vec::Vec;
+use anyhow::Result;
+use core::iter::Iterator;
+use core::option::Option::{self, *};
+use core::result::Result::*;
+use log::{info, warn};
+use rosc::{OscMessage, OscPacket, OscType};
+
+use crate::led_strip::LedStrip;
+use crate::show::{ShowFrame, ShowHeader};
+
+pub struct ShowPlayer {
+    pub header: ShowHeader,
+    bytes: Vec<u8>,
+    /// The timestamp and byte offset of each frame
+    frames: Vec<(u32, usize)>,
+    /// The index of the frame currently being displayed
+    current_frame: Option<usize>,
+    position_ms: f64,
+    pub playing: bool,
+    pub looping: bool,
+    pub speed: f32,
+}
+
+impl ShowPlayer {
+    /// Indexes the frames of a show file. The player starts paused at the beginning of the show.
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        let (header, mut offset) = ShowHeader::decode(&bytes)?;
+        let mut frames = Vec::new();
+
+        while offset < bytes.len() {
+            match ShowFrame::decode(&header, &bytes[offset..]) {
+                Ok((frame, length)) => {
+                    frames.push((frame.timestamp_ms, offset));
+                    offset += length;
+                }
+                Err(err) => {
+                    warn!("Skipping the end of the show: {:?}", err);
+                    break;
+                }
+            }
+        }
+
+        Ok(Self {
+            header,
+            bytes,
+            frames,
+            current_frame: None,
+            position_ms: 0.0,
+            playing: false,
+            looping: true,
+            speed: 1.0,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The length of the show, including the time the last frame is displayed for
+    pub fn duration_ms(&self) -> u32 {
+        match self.frames[..] {
+            [] => 0,
+            [(last, _)] => last,
+            // Corrupt shows may not have increasing timestamps
+            [.., (previous, _), (last, _)] => last.saturating_add(last.saturating_sub(previous)),
+        }
+    }
+
+    pub fn position_ms(&self) -> f64 {
+        self.position_ms
+    }
+
+    pub fn play(&mut self) {
+        if !self.looping && self.position_ms >= self.duration_ms() as f64 {
+            self.seek(0.0);
+        }
+        self.playing = true;
+    }
+
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn seek(&mut self, position_ms: f64) {
+        self.position_ms = position_ms.clamp(0.0, self.duration_ms() as f64);
+        // Force the frame at the new position to be displayed
+        self.current_frame = None;
+    }
+
+    /// Moves the playback position forward by `elapsed_ms` of wall clock time, returning the frame
+    /// to display if it has changed.
+    pub fn advance(&mut self, elapsed_ms: f64) -> Option<ShowFrame> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        if self.playing {
+            self.position_ms += elapsed_ms * self.speed as f64;
+
+            let duration_ms = self.duration_ms() as f64;
+
+            if self.position_ms > duration_ms {
+                if self.looping && duration_ms > 0.0 {
+                    self.position_ms %= duration_ms;
+                    self.current_frame = None;
+                } else {
+                    self.position_ms = duration_ms;
+                    self.playing = false;
+                }
+            }
+        }
+
+        // The last frame at or before the playback position
+        let frame = self
+            .frames
+            .partition_point(|(timestamp, _)| (*timestamp as f64) <= self.position_ms)
+            .saturating_sub(1);
+
+        if self.current_frame == Some(frame) {
+            return None;
+        }
+        self.current_frame = Some(frame);
+
+        let (_, offset) = self.frames[frame];
+
+        ShowFrame::decode(&self.header, &self.bytes[offset..])
+            .map(|(frame, _)| frame)
+            .ok()
+    }
+
+    /// The wall clock time until the next frame is due, if the show is playing
+    pub fn next_frame_in_ms(&self) -> Option<f64> {
+        if !self.playing || self.speed <= 0.0 {
+            return None;
+        }
+
+        let next_frame = self.current_frame.map(|frame| frame + 1).unwrap_or(0);
+        let next_timestamp = self
+            .frames
+            .get(next_frame)
+            .map(|(timestamp, _)| *timestamp)
+            .unwrap_or_else(|| self.duration_ms());
+
+        Some(((next_timestamp as f64 - self.position_ms) / self.speed as f64).max(0.0))
+    }
+
+    /// Handles `/show/*` transport commands, returning false if the packet is not a transport
+    /// command.
+    pub fn handle_packet(&mut self, packet: &OscPacket) -> bool {
+        let (addr, args) = match packet {
+            OscPacket::Message(OscMessage { addr, args }) => (addr.as_str(), &args[..]),
+            _ => return false,
+        };
+
+        let command = match addr.strip_prefix("/show/") {
+            Some(command) => command,
+            None => return false,
+        };
+
+        match (command, args) {
+            ("play", _) => {
+                info!("Playing show");
+                self.play();
+            }
+            ("pause", _) => {
+                info!("Pausing show");
+                self.pause();
+            }
+            ("seek", [seconds]) => match as_f64(seconds) {
+                Some(seconds) if !seconds.is_nan() => {
+                    info!("Seeking show to {}s", seconds);
+                    self.seek(seconds * 1000.0);
+                }
+                _ => warn!("Invalid /show/seek position: {:?}", seconds),
+            },
+            ("speed", [speed]) => match as_f64(speed) {
+                // Large doubles become infinite as an f32
+                Some(speed) if speed > 0.0 && (speed as f32).is_finite() => {
+                    info!("Setting show speed to {}", speed);
+                    self.speed = speed as f32;
+                }
+                _ => warn!("Invalid /show/speed: {:?}", speed),
+            },
+            ("loop", [looping]) => match looping {
+                OscType::Bool(looping) => self.looping = *looping,
+                OscType::Int(looping) => self.looping = *looping != 0,
+                _ => warn!("Invalid /show/loop: {:?}", looping),
+            },
+            _ => warn!("Invalid show command: {} {:?}", addr, args),
+        }
+
+        true
+    }
+
+    /// Copies a frame into the LED strips and writes it to the LEDs.
+    pub fn display(frame: &ShowFrame, led_strips: &mut [LedStrip]) -> Result<()> {
+        for (led_strip, strip) in led_strips.iter_mut().zip(frame.strips.iter()) {
+            for (led, color) in led_strip.data.iter_mut().zip(strip.iter()) {
+                *led = *color;
+            }
+
+            led_strip.write()?;
+        }
+
+        Ok(())
+    }
+}
+
+fn as_f64(osc_type: &OscType) -> Option<f64> {
+    match osc_type {
+        OscType::Float(value) => Some(*value as f64),
+        OscType::Double(value) => Some(*value),
+        OscType::Int(value) => Some(*value as f64),
+        OscType::Long(value) => Some(*value as f64),
+        _ => None,
+    }
+}
@@ -0,0 +1,38 @@
+//! Helpers shared by the integration tests. Each test crate only uses some of them.
+#![allow(dead_code)]
+
+use osc_receiver::error_reply::{ErrorCode, MessageError};
+use rosc::{OscColor, OscMessage, OscPacket, OscType};
+use smart_leds::RGB8;
+
+pub const BLACK: RGB8 = RGB8 { r: 0, g: 0, b: 0 };
+
+pub fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8 { r, g, b }
+}
+
+pub fn color(c: RGB8) -> OscType {
+    OscType::Color(OscColor {
+        red: c.r,
+        green: c.g,
+        blue: c.b,
+        alpha: 0xFF,
+    })
+}
+
+pub fn message(addr: &str, args: Vec<OscType>) -> OscPacket {
+    OscPacket::Message(OscMessage {
+        addr: addr.to_string(),
+        args,
+    })
+}
+
+/// Parses a message with one of the command parsers, keeping only the error code of rejected
+/// messages.
+pub fn parse<T>(
+    parser: impl Fn(&OscPacket) -> Option<Result<T, MessageError>>,
+    addr: &str,
+    args: Vec<OscType>,
+) -> Option<Result<T, ErrorCode>> {
+    parser(&message(addr, args)).map(|result| result.map_err(|err| err.code))
+}
@@ -0,0 +1,103 @@
+use osc_receiver::connection::{Connection, ConnectionAction, ConnectionState, Link};
+
+const IP: [u8; 4] = [192, 168, 1, 20];
+
+#[test]
+fn binds_once_connected() {
+    let mut connection = Connection::new(30_000, 0);
+
+    assert_eq!(connection.update(Link::Down, 100), None);
+    assert_eq!(connection.update(Link::Up, 200), None);
+    assert_eq!(
+        connection.update(Link::Ip(IP), 300),
+        Some(ConnectionAction::Bind(IP))
+    );
+    assert_eq!(connection.ip(), Some(IP));
+    assert_eq!(connection.update(Link::Ip(IP), 400), None);
+    assert_eq!(connection.next_update_in_ms(400), None);
+}
+
+#[test]
+fn provisions_if_the_network_is_never_joined() {
+    let mut connection = Connection::new(30_000, 0);
+
+    assert_eq!(connection.next_update_in_ms(10_000), Some(20_000));
+    assert_eq!(connection.update(Link::Up, 29_999), None);
+    assert_eq!(
+        connection.update(Link::Down, 30_000),
+        Some(ConnectionAction::Provision)
+    );
+}
+
+#[test]
+fn rebinds_when_the_address_changes() {
+    let mut connection = Connection::new(30_000, 0);
+    connection.update(Link::Ip(IP), 0);
+
+    let new_ip = [192, 168, 1, 21];
+    assert_eq!(
+        connection.update(Link::Ip(new_ip), 1000),
+        Some(ConnectionAction::Bind(new_ip))
+    );
+
+    // Losing the lease waits for DHCP without reconnecting
+    assert_eq!(
+        connection.update(Link::Up, 2000),
+        Some(ConnectionAction::Unbind)
+    );
+    assert_eq!(
+        connection.state(),
+        ConnectionState::Connecting { since_ms: 2000 }
+    );
+    assert_eq!(
+        connection.update(Link::Ip(IP), 3000),
+        Some(ConnectionAction::Bind(IP))
+    );
+}
+
+#[test]
+fn reconnects_with_backoff() {
+    let mut connection = Connection::new(10_000, 0);
+    connection.update(Link::Ip(IP), 0);
+
+    assert_eq!(
+        connection.update(Link::Down, 1000),
+        Some(ConnectionAction::Unbind)
+    );
+    assert_eq!(connection.ip(), None);
+    assert_eq!(connection.next_update_in_ms(1000), Some(1000));
+    assert_eq!(connection.update(Link::Down, 1999), None);
+    assert_eq!(
+        connection.update(Link::Down, 2000),
+        Some(ConnectionAction::Reconnect)
+    );
+
+    // Each failed attempt doubles the wait, up to 32 seconds
+    let mut now_ms = 2000;
+    for backoff_ms in [2000, 4000, 8000, 16_000, 32_000, 32_000] {
+        now_ms += 10_000;
+        assert_eq!(connection.update(Link::Down, now_ms), None);
+        assert_eq!(
+            connection.state(),
+            ConnectionState::Reconnecting {
+                at_ms: now_ms + backoff_ms
+            }
+        );
+
+        now_ms += backoff_ms;
+        assert_eq!(
+            connection.update(Link::Down, now_ms),
+            Some(ConnectionAction::Reconnect)
+        );
+    }
+
+    // The backoff starts again after connecting
+    connection.update(Link::Ip(IP), now_ms);
+    connection.update(Link::Down, now_ms);
+    assert_eq!(
+        connection.state(),
+        ConnectionState::Reconnecting {
+            at_ms: now_ms + 1000
+        }
+    );
+}
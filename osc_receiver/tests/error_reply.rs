@@ -0,0 +1,167 @@
+use osc_receiver::error_reply::{ErrorCode, MessageError, ERROR_ADDR};
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use rosc::{OscBundle, OscPacket, OscTime, OscType};
+
+mod common;
+
+use common::{message, rgb};
+
+fn color() -> OscType {
+    common::color(rgb(1, 2, 3))
+}
+
+/// Sends the packet to LED strips of the given lengths and returns the address and code of each
+/// rejected message
+fn errors(strip_lengths: &[usize], packet: OscPacket) -> Vec<(String, ErrorCode)> {
+    let mut smart_leds = vec![RecordingSmartLed::new(); strip_lengths.len()];
+
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .zip(strip_lengths.iter())
+        .map(|(smart_led, length)| LedStrip::with_length(smart_led, *length))
+        .collect::<Vec<_>>();
+
+    let mut errors = Vec::new();
+    LedStrip::update_with_errors(&mut led_strips, packet, &mut errors).unwrap();
+
+    errors
+        .into_iter()
+        .map(|error| (error.addr, error.code))
+        .collect()
+}
+
+#[test]
+fn valid_messages_are_not_rejected() {
+    let packet = message("/led_strips/0/1", vec![color(), color()]);
+
+    assert_eq!(errors(&[3], packet), vec![]);
+    assert_eq!(
+        errors(&[3], message("/0/dmx/8", vec![OscType::Float(1.0)])),
+        vec![]
+    );
+}
+
+#[test]
+fn rejects_invalid_led_strip_messages() {
+    let cases = vec![
+        (
+            "/led_strips/first",
+            vec![color()],
+            ErrorCode::InvalidStripIndex,
+        ),
+        (
+            "/led_strips/0/second",
+            vec![color()],
+            ErrorCode::InvalidOffset,
+        ),
+        (
+            "/led_strips/0",
+            vec![OscType::Int(7)],
+            ErrorCode::InvalidArgument,
+        ),
+        (
+            "/led_strips/0",
+            vec![color(), color()],
+            ErrorCode::StripOverflow,
+        ),
+        ("/led_strips/1", vec![color()], ErrorCode::StripOverflow),
+    ];
+
+    for (addr, args, code) in cases {
+        assert_eq!(
+            errors(&[1], message(addr, args)),
+            vec![(addr.to_string(), code)],
+            "{}",
+            addr
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_dmx_messages() {
+    let cases = vec![
+        (
+            "/zero/dmx/0",
+            OscType::Float(1.0),
+            ErrorCode::InvalidUniverse,
+        ),
+        ("/0/dmx/red", OscType::Float(1.0), ErrorCode::InvalidChannel),
+        ("/0/dmx/0", OscType::Int(255), ErrorCode::InvalidArgument),
+        ("/0/dmx/6", OscType::Float(1.0), ErrorCode::ChannelOverflow),
+    ];
+
+    for (addr, arg, code) in cases {
+        assert_eq!(
+            errors(&[2], message(addr, vec![arg])),
+            vec![(addr.to_string(), code)],
+            "{}",
+            addr
+        );
+    }
+}
+
+#[test]
+fn rejects_unknown_addresses() {
+    assert_eq!(
+        errors(&[1], message("/lights/0", vec![color()])),
+        vec![("/lights/0".to_string(), ErrorCode::UnknownAddress)]
+    );
+}
+
+#[test]
+fn each_message_is_rejected_once() {
+    let packet = message(
+        "/led_strips/0",
+        vec![OscType::Int(1), OscType::Int(2), color(), color()],
+    );
+
+    assert_eq!(
+        errors(&[1], packet),
+        vec![("/led_strips/0".to_string(), ErrorCode::InvalidArgument)]
+    );
+}
+
+#[test]
+fn rejects_each_invalid_message_in_a_bundle() {
+    let packet = OscPacket::Bundle(OscBundle {
+        timetag: OscTime {
+            seconds: 0,
+            fractional: 1,
+        },
+        content: vec![
+            message("/led_strips/0", vec![color()]),
+            message("/led_strips/x", vec![color()]),
+            message("/lights", vec![]),
+        ],
+    });
+
+    assert_eq!(
+        errors(&[1], packet),
+        vec![
+            ("/led_strips/x".to_string(), ErrorCode::InvalidStripIndex),
+            ("/lights".to_string(), ErrorCode::UnknownAddress),
+        ]
+    );
+}
+
+#[test]
+fn replies_with_the_address_code_and_reason() {
+    let error = MessageError::new(
+        "/led_strips/x",
+        ErrorCode::InvalidStripIndex,
+        "Invalid strip index: \"x\"".to_string(),
+    );
+
+    assert_eq!(
+        error.reply(),
+        message(
+            ERROR_ADDR,
+            vec![
+                OscType::String("/led_strips/x".to_string()),
+                OscType::Int(4),
+                OscType::String("Invalid strip index: \"x\"".to_string()),
+            ]
+        )
+    );
+}
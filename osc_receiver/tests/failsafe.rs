@@ -0,0 +1,258 @@
+use osc_receiver::failsafe::{Failsafe, FailsafeAction};
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use osc_receiver::pattern::{PatternKind, Patterns};
+use osc_receiver::preset::Presets;
+use osc_receiver::scene::Scene;
+use smart_leds::RGB8;
+
+mod common;
+
+use common::BLACK;
+
+const WHITE: RGB8 = RGB8 {
+    r: 200,
+    g: 200,
+    b: 200,
+};
+
+fn data(led_strips: &[LedStrip]) -> Vec<Vec<RGB8>> {
+    led_strips.iter().map(|strip| strip.data.clone()).collect()
+}
+
+/// Two white strips of two LEDs each
+fn white_strips(smart_leds: &mut [RecordingSmartLed]) -> Vec<LedStrip<'_>> {
+    smart_leds
+        .iter_mut()
+        .map(|smart_led| {
+            let mut led_strip = LedStrip::with_length(smart_led, 2);
+            led_strip.data.fill(WHITE);
+            led_strip
+        })
+        .collect()
+}
+
+#[test]
+fn engages_after_the_timeout() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 999));
+    assert!(!failsafe.is_engaged());
+    assert_eq!(failsafe.next_update_in_ms(999), Some(1));
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    assert!(failsafe.is_engaged());
+    assert_eq!(data(&led_strips), vec![vec![BLACK; 2]; 2]);
+
+    // Nothing is left to draw once the fade has finished
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 2000));
+    assert_eq!(failsafe.next_update_in_ms(2000), None);
+}
+
+#[test]
+fn waits_for_the_first_frame() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 5000));
+    assert_eq!(failsafe.next_update_in_ms(5000), None);
+    assert_eq!(data(&led_strips), vec![vec![WHITE; 2]; 2]);
+}
+
+#[test]
+fn frames_postpone_the_timeout() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    failsafe.frame_received(800);
+
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 1500));
+    assert_eq!(data(&led_strips), vec![vec![WHITE; 2]; 2]);
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1800));
+}
+
+#[test]
+fn fades_from_the_last_frame() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 1000, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    assert_eq!(led_strips[0].data[0], WHITE);
+
+    // Redrawn at most every 33ms
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 1010));
+    assert_eq!(failsafe.next_update_in_ms(1010), Some(23));
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1500));
+    assert_eq!(
+        led_strips[1].data[1],
+        RGB8 {
+            r: 100,
+            g: 100,
+            b: 100
+        }
+    );
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 2000));
+    assert_eq!(data(&led_strips), vec![vec![BLACK; 2]; 2]);
+}
+
+#[test]
+fn scenes_repeat_along_every_strip() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let action = FailsafeAction::parse("#100800,#000010,#ffffff").unwrap();
+    let mut failsafe = Failsafe::new(1000, 0, action);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+
+    let amber = RGB8 { r: 16, g: 8, b: 0 };
+    let blue = RGB8 { r: 0, g: 0, b: 16 };
+    let white = RGB8 {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    assert_eq!(
+        data(&led_strips),
+        vec![vec![amber, blue], vec![white, amber]]
+    );
+}
+
+#[test]
+fn hold_never_changes_the_strips() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Hold);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 5000));
+    assert_eq!(failsafe.next_update_in_ms(5000), None);
+    assert_eq!(data(&led_strips), vec![vec![WHITE; 2]; 2]);
+}
+
+#[test]
+fn frames_disengage_the_failsafe() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    failsafe.frame_received(1200);
+
+    assert!(!failsafe.is_engaged());
+    assert_eq!(failsafe.next_update_in_ms(1200), Some(1000));
+}
+
+#[test]
+fn parses_and_displays_actions() {
+    for action in [
+        "hold",
+        "black",
+        "#ff8000",
+        "#ff8000,#000000",
+        "pattern:rainbow",
+        "default_scene",
+        "preset:sunset",
+    ] {
+        assert_eq!(FailsafeAction::parse(action).unwrap().to_string(), action);
+    }
+
+    assert!(FailsafeAction::parse("purple").is_err());
+    assert!(FailsafeAction::parse("#ff80").is_err());
+    assert!(FailsafeAction::parse("pattern:fire").is_err());
+    assert!(FailsafeAction::parse("preset:../sunset").is_err());
+}
+
+#[test]
+fn suspending_waits_for_the_next_frame() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Black);
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    failsafe.suspend();
+
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 5000));
+    assert_eq!(data(&led_strips), vec![vec![WHITE; 2]; 2]);
+
+    failsafe.frame_received(6000);
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 7000));
+}
+
+#[test]
+fn starts_a_pattern() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Pattern(PatternKind::Chase));
+    let mut patterns = Patterns::default();
+    failsafe.frame_received(0);
+
+    // The pattern draws the strips itself
+    assert!(!failsafe.update(&mut led_strips, &mut patterns, 1000));
+    assert!(failsafe.is_engaged());
+    assert_eq!(patterns.running(), Some(PatternKind::Chase));
+    assert_eq!(failsafe.next_update_in_ms(1000), None);
+}
+
+#[test]
+fn fades_to_the_default_scene_or_a_preset() {
+    let red = RGB8 { r: 200, g: 0, b: 0 };
+    let blue = RGB8 { r: 0, g: 0, b: 200 };
+    let default_scene = Scene {
+        strips: vec![vec![red; 2], vec![red]],
+    };
+    let mut presets = Presets::default();
+    presets.insert(
+        "sunset",
+        Scene {
+            strips: vec![vec![blue; 2]; 2],
+        },
+    );
+
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::DefaultScene);
+    let mut patterns = Patterns::default();
+    failsafe.load_scene(Some(&default_scene), &presets);
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    // LEDs the scene doesn't cover fade to black
+    assert_eq!(data(&led_strips), vec![vec![red; 2], vec![red, BLACK]]);
+
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Preset("sunset".to_string()));
+    failsafe.load_scene(Some(&default_scene), &presets);
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    assert_eq!(data(&led_strips), vec![vec![blue; 2]; 2]);
+
+    // Presets that haven't been stored fade to black
+    let mut led_strips = white_strips(&mut smart_leds);
+    let mut failsafe = Failsafe::new(1000, 0, FailsafeAction::Preset("sunrise".to_string()));
+    failsafe.load_scene(Some(&default_scene), &presets);
+    failsafe.frame_received(0);
+
+    assert!(failsafe.update(&mut led_strips, &mut patterns, 1000));
+    assert_eq!(data(&led_strips), vec![vec![BLACK; 2]; 2]);
+}
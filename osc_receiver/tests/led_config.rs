@@ -0,0 +1,158 @@
+use osc_receiver::error_reply::ErrorCode;
+use osc_receiver::led_config::{
+    ColorOrder, ColorOrdered, LedConfig, LedConfigCommand, LedSetting, LedType,
+};
+use osc_receiver::led_strip::RGB8SmartLedsWrite;
+use osc_receiver::mock::RecordingSmartLed;
+use rosc::OscType;
+use smart_leds::RGB8;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+mod common;
+
+fn parse(addr: &str, args: Vec<OscType>) -> Option<Result<LedConfigCommand, ErrorCode>> {
+    common::parse(LedConfigCommand::parse, addr, args)
+}
+
+#[test]
+fn parses_osc_commands() {
+    assert_eq!(
+        parse("/config/led/length", vec![OscType::Int(300)]),
+        Some(Ok(LedConfigCommand::Set {
+            setting: LedSetting::Length,
+            value: "300".to_string()
+        }))
+    );
+    assert_eq!(
+        parse(
+            "/config/led/type",
+            vec![OscType::String("WS2812B".to_string())]
+        ),
+        Some(Ok(LedConfigCommand::Set {
+            setting: LedSetting::Type,
+            value: "WS2812B".to_string()
+        }))
+    );
+    assert_eq!(
+        parse("/config/led/apply", vec![]),
+        Some(Ok(LedConfigCommand::Apply))
+    );
+
+    assert_eq!(
+        parse("/config/led/brightness", vec![OscType::Int(1)]),
+        Some(Err(ErrorCode::UnknownAddress))
+    );
+    assert_eq!(
+        parse("/config/led/length", vec![]),
+        Some(Err(ErrorCode::InvalidArgument))
+    );
+    assert_eq!(parse("/led_strips/0", vec![]), None);
+}
+
+#[test]
+fn parses_console_commands() {
+    assert_eq!(
+        LedConfigCommand::parse_line("led mosi_pin 5\r")
+            .unwrap()
+            .unwrap(),
+        LedConfigCommand::Set {
+            setting: LedSetting::MosiPin,
+            value: "5".to_string()
+        }
+    );
+    assert_eq!(
+        LedConfigCommand::parse_line("led").unwrap().unwrap(),
+        LedConfigCommand::Show
+    );
+    assert_eq!(
+        LedConfigCommand::parse_line("  led apply")
+            .unwrap()
+            .unwrap(),
+        LedConfigCommand::Apply
+    );
+
+    assert!(LedConfigCommand::parse_line("led pins 5").unwrap().is_err());
+    assert!(LedConfigCommand::parse_line("led length").unwrap().is_err());
+    assert!(LedConfigCommand::parse_line("help").is_none());
+}
+
+#[test]
+fn changes_settings() {
+    let mut config = LedConfig::default();
+    assert_eq!(config.spi_frequency_hz, 12_000_000);
+
+    // The SPI frequency follows the LED type
+    config.set(LedSetting::Type, "ws2812b").unwrap();
+    assert_eq!(config.led_type, LedType::Ws2812b);
+    assert_eq!(config.spi_frequency_hz, 3_000_000);
+
+    config.set(LedSetting::Length, "144").unwrap();
+    config.set(LedSetting::ColorOrder, "grb").unwrap();
+    assert_eq!(config.length, 144);
+    assert_eq!(config.color_order, ColorOrder::Grb);
+
+    // Invalid values leave the settings unchanged
+    let unchanged = config.clone();
+    assert!(config.set(LedSetting::Type, "neopixel").is_err());
+    assert!(config.set(LedSetting::Length, "0").is_err());
+    assert!(config.set(LedSetting::Length, "lots").is_err());
+    assert!(config.set(LedSetting::SpiFrequency, "100000000").is_err());
+    // WS2812B bits are timed by the SPI clock
+    assert!(config.set(LedSetting::SpiFrequency, "1000000").is_err());
+    assert!(config.set(LedSetting::SpiFrequency, "4000000").is_err());
+    assert!(config.set(LedSetting::MosiPin, "6").is_err());
+    assert!(config.set(LedSetting::SclkPin, "256").is_err());
+    assert_eq!(config, unchanged);
+
+    config.set(LedSetting::SpiFrequency, "2500000").unwrap();
+    assert_eq!(config.spi_frequency_hz, 2_500_000);
+    config.set(LedSetting::SpiFrequency, "3000000").unwrap();
+
+    assert_eq!(
+        config.to_string(),
+        "led type WS2812B\nled sclk_pin 6\nled mosi_pin 7\nled spi_frequency 3000000\nled length 144\nled color_order GRB"
+    );
+}
+
+#[test]
+fn settings_round_trip() {
+    let mut config = LedConfig::new(LedType::Ws2812b);
+    config.set(LedSetting::SclkPin, "4").unwrap();
+    config.set(LedSetting::ColorOrder, "BRG").unwrap();
+
+    assert_eq!(LedConfig::decode(&config.encode()).unwrap(), config);
+
+    let bytes = config.encode();
+    assert!(LedConfig::decode(&bytes[..bytes.len() - 1]).is_err());
+    // Erased flash
+    assert!(LedConfig::decode(&[0xFF; 11]).is_err());
+}
+
+// Lets the test see what was written through the boxed smart LED
+#[derive(Clone, Default)]
+struct SharedSmartLed(Rc<RefCell<RecordingSmartLed>>);
+
+impl RGB8SmartLedsWrite for SharedSmartLed {
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> anyhow::Result<()> {
+        self.0.borrow_mut().write_rgb8(iterator)
+    }
+}
+
+#[test]
+fn reorders_colors() {
+    let recording = SharedSmartLed::default();
+    let mut smart_led = ColorOrdered {
+        smart_led: Box::new(recording.clone()),
+        color_order: ColorOrder::Brg,
+    };
+
+    smart_led
+        .write_rgb8(&mut vec![RGB8 { r: 1, g: 2, b: 3 }].into_iter())
+        .unwrap();
+
+    assert_eq!(
+        recording.0.borrow().last_frame(),
+        Some(&[RGB8 { r: 3, g: 1, b: 2 }][..])
+    );
+}
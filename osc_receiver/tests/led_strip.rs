@@ -0,0 +1,309 @@
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use rosc::{OscBundle, OscPacket, OscTime, OscType};
+use smart_leds::RGB8;
+
+mod common;
+
+use common::{color, message, rgb, BLACK};
+
+fn dmx(universe: usize, channel: usize, value: f32) -> OscPacket {
+    message(
+        &format!("/{}/dmx/{}", universe, channel),
+        vec![OscType::Float(value)],
+    )
+}
+
+/// Sends each packet to LED strips of the given lengths and returns what was written to each strip
+fn receive(strip_lengths: &[usize], packets: Vec<OscPacket>) -> Vec<RecordingSmartLed> {
+    let mut smart_leds = vec![RecordingSmartLed::new(); strip_lengths.len()];
+
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .zip(strip_lengths.iter())
+        .map(|(smart_led, length)| LedStrip::with_length(smart_led, *length))
+        .collect::<Vec<_>>();
+
+    for packet in packets {
+        LedStrip::update(&mut led_strips, packet).unwrap();
+    }
+
+    drop(led_strips);
+    smart_leds
+}
+
+fn last_frames(smart_leds: &[RecordingSmartLed]) -> Vec<&[RGB8]> {
+    smart_leds
+        .iter()
+        .map(|smart_led| smart_led.last_frame().unwrap())
+        .collect()
+}
+
+#[test]
+fn writes_a_frame_to_every_strip_per_packet() {
+    let smart_leds = receive(
+        &[3, 3],
+        vec![
+            message("/led_strips/0", vec![color(rgb(1, 2, 3))]),
+            message("/led_strips/0", vec![color(rgb(4, 5, 6))]),
+        ],
+    );
+
+    for smart_led in smart_leds.iter() {
+        assert_eq!(smart_led.frames.len(), 2);
+    }
+    assert_eq!(smart_leds[0].frames[0], vec![rgb(1, 2, 3), BLACK, BLACK]);
+    assert_eq!(smart_leds[0].frames[1], vec![rgb(4, 5, 6), BLACK, BLACK]);
+}
+
+#[test]
+fn sets_colors_in_order() {
+    let smart_leds = receive(
+        &[4],
+        vec![message(
+            "/led_strips/0",
+            vec![
+                color(rgb(1, 0, 0)),
+                color(rgb(0, 2, 0)),
+                color(rgb(0, 0, 3)),
+            ],
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[rgb(1, 0, 0), rgb(0, 2, 0), rgb(0, 0, 3), BLACK][..]]
+    );
+}
+
+#[test]
+fn colors_overflow_into_the_next_strip() {
+    let colors = (1..=5).map(|i| rgb(i, i, i)).collect::<Vec<_>>();
+
+    let smart_leds = receive(
+        &[2, 2, 2],
+        vec![message(
+            "/led_strips/0",
+            colors.iter().cloned().map(color).collect(),
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&colors[0..2], &colors[2..4], &[colors[4], BLACK][..]]
+    );
+}
+
+#[test]
+fn colors_beyond_the_last_strip_are_dropped() {
+    let smart_leds = receive(
+        &[1, 1],
+        vec![message(
+            "/led_strips/0",
+            vec![
+                color(rgb(1, 1, 1)),
+                color(rgb(2, 2, 2)),
+                color(rgb(3, 3, 3)),
+            ],
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[rgb(1, 1, 1)][..], &[rgb(2, 2, 2)][..]]
+    );
+}
+
+#[test]
+fn dmx_channels_set_individual_color_components() {
+    let smart_leds = receive(
+        &[3],
+        vec![
+            dmx(0, 0, 1.0),
+            dmx(0, 4, 0.5),
+            dmx(0, 8, 0.0),
+            dmx(0, 7, 1.0),
+        ],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[rgb(255, 0, 0), rgb(0, 127, 0), rgb(0, 255, 0)][..]]
+    );
+}
+
+#[test]
+fn dmx_universes_span_multiple_strips() {
+    // Universe 1, channel 1 is global channel 513 which is the red channel of LED #171
+    let smart_leds = receive(&[100, 100], vec![dmx(1, 1, 1.0)]);
+
+    let frames = last_frames(&smart_leds);
+    assert!(frames[0].iter().all(|led| *led == BLACK));
+    assert_eq!(frames[1][71], rgb(255, 0, 0));
+    assert_eq!(frames[1].iter().filter(|led| **led != BLACK).count(), 1);
+}
+
+#[test]
+fn dmx_channels_beyond_the_last_strip_are_ignored() {
+    let smart_leds = receive(&[2, 2], vec![dmx(0, 12, 1.0)]);
+
+    for frame in last_frames(&smart_leds) {
+        assert!(frame.iter().all(|led| *led == BLACK));
+    }
+}
+
+#[test]
+fn invalid_led_strip_indexes_are_ignored() {
+    let smart_leds = receive(
+        &[2],
+        vec![message("/led_strips/first", vec![color(rgb(1, 1, 1))])],
+    );
+
+    assert_eq!(last_frames(&smart_leds), vec![&[BLACK, BLACK][..]]);
+}
+
+#[test]
+fn non_color_arguments_are_skipped() {
+    let smart_leds = receive(
+        &[3],
+        vec![message(
+            "/led_strips/0",
+            vec![
+                color(rgb(1, 1, 1)),
+                OscType::Int(7),
+                OscType::String("red".to_string()),
+                color(rgb(2, 2, 2)),
+            ],
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[rgb(1, 1, 1), rgb(2, 2, 2), BLACK][..]]
+    );
+}
+
+#[test]
+fn malformed_dmx_messages_are_ignored() {
+    let smart_leds = receive(
+        &[2],
+        vec![
+            message("/zero/dmx/0", vec![OscType::Float(1.0)]),
+            message("/0/dmx/red", vec![OscType::Float(1.0)]),
+            message("/0/dmx/0", vec![OscType::Int(255)]),
+            message("/0/dmx/0", vec![OscType::Float(1.0), OscType::Float(1.0)]),
+        ],
+    );
+
+    assert_eq!(last_frames(&smart_leds), vec![&[BLACK, BLACK][..]]);
+}
+
+#[test]
+fn unknown_addresses_are_ignored() {
+    let smart_leds = receive(
+        &[1],
+        vec![
+            message("/led_strips", vec![color(rgb(1, 1, 1))]),
+            message("/lights/0", vec![color(rgb(1, 1, 1))]),
+        ],
+    );
+
+    assert_eq!(smart_leds[0].frames, vec![vec![BLACK], vec![BLACK]]);
+}
+
+#[test]
+fn colors_start_at_the_addressed_strip() {
+    let smart_leds = receive(
+        &[2, 2],
+        vec![message(
+            "/led_strips/1",
+            vec![color(rgb(1, 1, 1)), color(rgb(2, 2, 2))],
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[BLACK, BLACK][..], &[rgb(1, 1, 1), rgb(2, 2, 2)][..]]
+    );
+}
+
+#[test]
+fn offsets_skip_leds_and_overflow_into_the_next_strip() {
+    let smart_leds = receive(
+        &[3, 3, 3],
+        vec![message(
+            "/led_strips/0/5",
+            vec![
+                color(rgb(1, 1, 1)),
+                color(rgb(2, 2, 2)),
+                color(rgb(3, 3, 3)),
+            ],
+        )],
+    );
+
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![
+            &[BLACK, BLACK, BLACK][..],
+            &[BLACK, BLACK, rgb(1, 1, 1)][..],
+            &[rgb(2, 2, 2), rgb(3, 3, 3), BLACK][..],
+        ]
+    );
+}
+
+#[test]
+fn bundles_apply_every_message_before_writing() {
+    let smart_leds = receive(
+        &[1, 1],
+        vec![OscPacket::Bundle(OscBundle {
+            timetag: OscTime {
+                seconds: 0,
+                fractional: 1,
+            },
+            content: vec![
+                message("/led_strips/0", vec![color(rgb(1, 1, 1))]),
+                message("/led_strips/1", vec![color(rgb(2, 2, 2))]),
+            ],
+        })],
+    );
+
+    for smart_led in smart_leds.iter() {
+        assert_eq!(smart_led.frames.len(), 1);
+    }
+    assert_eq!(
+        last_frames(&smart_leds),
+        vec![&[rgb(1, 1, 1)][..], &[rgb(2, 2, 2)][..]]
+    );
+}
+
+#[test]
+fn apply_reports_whether_any_leds_were_set() {
+    let mut smart_led = RecordingSmartLed::new();
+    let mut led_strips = vec![LedStrip::with_length(&mut smart_led, 3)];
+    let mut errors = Vec::new();
+
+    assert!(LedStrip::apply(
+        &mut led_strips,
+        message("/led_strips/0", vec![color(rgb(1, 2, 3)), OscType::Int(7)]),
+        &mut errors
+    ));
+    assert!(LedStrip::apply(
+        &mut led_strips,
+        dmx(0, 0, 1.0),
+        &mut errors
+    ));
+    assert!(!LedStrip::apply(
+        &mut led_strips,
+        message("/foo", vec![]),
+        &mut errors
+    ));
+    assert!(!LedStrip::apply(
+        &mut led_strips,
+        message("/led_strips/1", vec![color(rgb(1, 2, 3))]),
+        &mut errors
+    ));
+
+    drop(led_strips);
+    // Nothing is written to the LEDs
+    assert!(smart_led.frames.is_empty());
+}
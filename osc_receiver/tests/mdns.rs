@@ -0,0 +1,130 @@
+use osc_receiver::mdns::{self, Responder, MDNS_PORT};
+use simple_dns::rdata::RData;
+use simple_dns::{Name, Packet, Question, CLASS, QCLASS, QTYPE, TYPE};
+
+const IP: [u8; 4] = [192, 168, 1, 50];
+
+fn responder() -> Responder {
+    Responder::new(
+        "Stage Left",
+        "raves-stage-left",
+        9000,
+        mdns::txt_records(&[450, 144], "WS2812B", "0.1.0"),
+    )
+}
+
+fn query(id: u16, name: &str, record_type: TYPE) -> Vec<u8> {
+    let mut packet = Packet::new_query(id);
+    packet.questions.push(Question::new(
+        Name::new_unchecked(name),
+        QTYPE::TYPE(record_type),
+        QCLASS::CLASS(CLASS::IN),
+        false,
+    ));
+
+    packet.build_bytes_vec_compressed().unwrap()
+}
+
+/// Every record in the reply as `(name, record)` strings
+fn records(reply: &[u8]) -> Vec<(String, String)> {
+    let packet = Packet::parse(reply).unwrap();
+
+    packet
+        .answers
+        .iter()
+        .chain(packet.additional_records.iter())
+        .map(|record| {
+            let data = match &record.rdata {
+                RData::PTR(ptr) => format!("PTR {}", ptr.0),
+                RData::SRV(srv) => format!("SRV {} {}", srv.port, srv.target),
+                RData::TXT(txt) => {
+                    let mut attributes = txt
+                        .attributes()
+                        .into_iter()
+                        .map(|(key, value)| format!("{}={}", key, value.unwrap_or_default()))
+                        .collect::<Vec<_>>();
+                    attributes.sort();
+                    format!("TXT {}", attributes.join(" "))
+                }
+                RData::A(a) => format!("A {}", std::net::Ipv4Addr::from(a.address)),
+                other => format!("{:?}", other),
+            };
+
+            (record.name.to_string(), data)
+        })
+        .collect()
+}
+
+#[test]
+fn browsing_for_the_service_returns_every_record_needed_to_connect() {
+    let reply = responder()
+        .reply(&query(0, "_osc._udp.local", TYPE::PTR), MDNS_PORT, IP)
+        .unwrap();
+
+    assert_eq!(
+        records(&reply),
+        vec![
+            (
+                "_osc._udp.local".to_string(),
+                "PTR Stage Left._osc._udp.local".to_string()
+            ),
+            (
+                "Stage Left._osc._udp.local".to_string(),
+                "SRV 9000 raves-stage-left.local".to_string()
+            ),
+            (
+                "Stage Left._osc._udp.local".to_string(),
+                "TXT led_type=WS2812B lengths=450,144 strips=2 version=0.1.0".to_string()
+            ),
+            (
+                "raves-stage-left.local".to_string(),
+                "A 192.168.1.50".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn host_names_are_resolved_case_insensitively() {
+    let reply = responder()
+        .reply(&query(0, "RAVES-Stage-Left.local", TYPE::A), MDNS_PORT, IP)
+        .unwrap();
+
+    assert_eq!(
+        records(&reply),
+        vec![(
+            "raves-stage-left.local".to_string(),
+            "A 192.168.1.50".to_string()
+        )]
+    );
+}
+
+#[test]
+fn legacy_unicast_replies_echo_the_query() {
+    let reply = responder()
+        .reply(&query(1234, "_osc._udp.local", TYPE::PTR), 50_000, IP)
+        .unwrap();
+    let packet = Packet::parse(&reply).unwrap();
+
+    assert_eq!(packet.id(), 1234);
+    assert_eq!(packet.questions.len(), 1);
+    assert!(packet
+        .answers
+        .iter()
+        .chain(packet.additional_records.iter())
+        .all(|record| record.ttl <= 10));
+}
+
+#[test]
+fn queries_for_other_services_are_ignored() {
+    let query = query(0, "_http._tcp.local", TYPE::PTR);
+
+    assert_eq!(responder().reply(&query, MDNS_PORT, IP), None);
+}
+
+#[test]
+fn announcements_contain_every_record() {
+    let announcement = responder().announcement(IP);
+
+    assert_eq!(records(&announcement).len(), 4);
+}
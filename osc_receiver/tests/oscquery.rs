@@ -0,0 +1,205 @@
+#![cfg(feature = "oscquery")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::Duration;
+
+use osc_receiver::oscquery::{HostInfo, Node, OscQuery, ACCESS_WRITE};
+use serde_json::{json, Value};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::Message;
+
+fn namespace() -> Node {
+    let mut root = Node::container("/");
+    root.insert(Node::method(
+        "/led_strips/0",
+        "rr",
+        ACCESS_WRITE,
+        "Colors of strip 0",
+    ));
+    root.insert(
+        Node::method("/0/dmx/0", "f", ACCESS_WRITE, "Red of LED 0")
+            .with_range(Some(0.0), Some(1.0)),
+    );
+    root
+}
+
+fn spawn(root: Node) -> (SocketAddr, OscQuery) {
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let host_info = HostInfo {
+        name: "Stage Left".to_string(),
+        osc_port: 8001,
+    };
+
+    (addr, OscQuery::spawn(addr, host_info, root).unwrap())
+}
+
+/// Returns the status line and JSON body of `GET path`.
+fn get(addr: SocketAddr, path: &str) -> (String, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+    let body = serde_json::from_str(body).unwrap_or(Value::Null);
+
+    (status, body)
+}
+
+#[test]
+fn insert_creates_the_containers_between_the_root_and_the_node() {
+    let root = namespace();
+
+    let led_strips = root.find("/led_strips").unwrap();
+    assert_eq!(led_strips.full_path, "/led_strips");
+    assert_eq!(led_strips.type_tag, None);
+    assert_eq!(
+        root.find("/led_strips/0").unwrap().type_tag.as_deref(),
+        Some("rr")
+    );
+    assert_eq!(root.find("/led_strips/1"), None);
+}
+
+#[test]
+fn insert_keeps_the_contents_of_an_existing_node() {
+    let mut root = namespace();
+    root.insert(Node::method("/led_strips", "", ACCESS_WRITE, "Every strip"));
+
+    let led_strips = root.find("/led_strips").unwrap();
+    assert_eq!(led_strips.description.as_deref(), Some("Every strip"));
+    assert!(led_strips.contents.contains_key("0"));
+}
+
+#[test]
+fn nodes_are_serialized_with_oscquery_attribute_names() {
+    let root = namespace();
+
+    assert_eq!(
+        json!(root.find("/0/dmx/0").unwrap()),
+        json!({
+            "FULL_PATH": "/0/dmx/0",
+            "DESCRIPTION": "Red of LED 0",
+            "TYPE": "f",
+            "ACCESS": 2,
+            "RANGE": [{ "MIN": 0.0, "MAX": 1.0 }],
+        })
+    );
+}
+
+#[test]
+fn serves_nodes_attributes_and_host_info() {
+    let (addr, _oscquery) = spawn(namespace());
+
+    let (status, body) = get(addr, "/");
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(
+        body["CONTENTS"]["led_strips"]["CONTENTS"]["0"]["TYPE"],
+        "rr"
+    );
+
+    let (_, body) = get(addr, "/0/dmx/0?RANGE");
+    assert_eq!(body, json!({ "RANGE": [{ "MIN": 0.0, "MAX": 1.0 }] }));
+
+    let (_, body) = get(addr, "/?HOST_INFO");
+    assert_eq!(body["NAME"], "Stage Left");
+    assert_eq!(body["OSC_PORT"], 8001);
+    assert_eq!(body["OSC_TRANSPORT"], "UDP");
+
+    let (status, _) = get(addr, "/led_strips/7");
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
+
+#[test]
+fn websockets_are_sent_namespace_changes() {
+    let (addr, oscquery) = spawn(namespace());
+    let (mut websocket, _) = tungstenite::connect(format!("ws://{}/", addr)).unwrap();
+
+    let mut root = Node::container("/");
+    root.insert(Node::method(
+        "/led_strips/0",
+        "rrr",
+        ACCESS_WRITE,
+        "Colors of strip 0",
+    ));
+    root.insert(Node::method(
+        "/ping",
+        "",
+        ACCESS_WRITE,
+        "Replies with /pong",
+    ));
+    oscquery.update(root);
+
+    let mut commands = (0..5)
+        .map(|_| serde_json::from_str::<Value>(&websocket.read().unwrap().into_text().unwrap()))
+        .map(|command| command.unwrap())
+        .collect::<Vec<_>>();
+    commands.sort_by_key(|command| command.to_string());
+
+    assert_eq!(
+        commands,
+        vec![
+            json!({ "COMMAND": "PATH_ADDED", "DATA": {
+                "FULL_PATH": "/ping",
+                "DESCRIPTION": "Replies with /pong",
+                "TYPE": "",
+                "ACCESS": 2,
+            }}),
+            json!({ "COMMAND": "PATH_CHANGED", "DATA": "/led_strips/0" }),
+            json!({ "COMMAND": "PATH_REMOVED", "DATA": "/0" }),
+            json!({ "COMMAND": "PATH_REMOVED", "DATA": "/0/dmx" }),
+            json!({ "COMMAND": "PATH_REMOVED", "DATA": "/0/dmx/0" }),
+        ]
+    );
+}
+
+#[test]
+fn websockets_answer_pings_and_closes() {
+    let (addr, _oscquery) = spawn(namespace());
+    let (mut websocket, _) = tungstenite::connect(format!("ws://{}/", addr)).unwrap();
+    if let MaybeTlsStream::Plain(stream) = websocket.get_ref() {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+    }
+
+    websocket.send(Message::Ping(b"ping"[..].into())).unwrap();
+    assert_eq!(websocket.read().unwrap(), Message::Pong(b"ping"[..].into()));
+
+    websocket.close(None).unwrap();
+    assert!(matches!(websocket.read().unwrap(), Message::Close(_)));
+    assert!(matches!(
+        websocket.read(),
+        Err(tungstenite::Error::ConnectionClosed)
+    ));
+}
+
+#[test]
+fn websocket_frames_sent_with_the_upgrade_request_are_read() {
+    let (addr, _oscquery) = spawn(namespace());
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    // A masked ping with a zero mask
+    let mut request = b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n".to_vec();
+    request.extend_from_slice(&[0x89, 0x84, 0, 0, 0, 0, b'p', b'i', b'n', b'g']);
+    stream.write_all(&request).unwrap();
+
+    let mut response = Vec::new();
+    let pong = [0x8A, 0x04, b'p', b'i', b'n', b'g'];
+    while !response.ends_with(&pong) {
+        let mut buf = [0; 256];
+        let read = stream.read(&mut buf).unwrap();
+        assert_ne!(read, 0, "Connection closed before the pong");
+        response.extend_from_slice(&buf[..read]);
+    }
+
+    assert!(response.starts_with(b"HTTP/1.1 101 Switching Protocols\r\n"));
+}
@@ -0,0 +1,203 @@
+use osc_receiver::error_reply::ErrorCode;
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use osc_receiver::pattern::{PatternKind, PatternParams, Patterns};
+use rosc::OscType;
+
+mod common;
+
+use common::{color, message, rgb, BLACK};
+
+fn handle(
+    patterns: &mut Patterns,
+    addr: &str,
+    args: Vec<OscType>,
+) -> Option<Result<(), ErrorCode>> {
+    patterns
+        .handle_packet(&message(addr, args), 0)
+        .map(|result| result.map_err(|err| err.code))
+}
+
+#[test]
+fn starts_patterns_and_sets_their_parameters() {
+    let mut patterns = Patterns::default();
+
+    assert_eq!(
+        handle(&mut patterns, "/pattern/chase", vec![]),
+        Some(Ok(()))
+    );
+    assert_eq!(patterns.running(), Some(PatternKind::Chase));
+
+    assert_eq!(
+        handle(
+            &mut patterns,
+            "/pattern/rainbow/speed",
+            vec![OscType::Float(0.5)]
+        ),
+        Some(Ok(()))
+    );
+    assert_eq!(patterns.running(), Some(PatternKind::Rainbow));
+    assert_eq!(patterns.params(PatternKind::Rainbow).speed, 0.5);
+    // Each pattern keeps its own parameters
+    assert_eq!(patterns.params(PatternKind::Chase).speed, 1.0);
+
+    handle(
+        &mut patterns,
+        "/pattern/sparkle/color",
+        vec![color(rgb(1, 2, 3))],
+    );
+    assert_eq!(patterns.params(PatternKind::Sparkle).color, rgb(1, 2, 3));
+    handle(
+        &mut patterns,
+        "/pattern/sparkle/color",
+        vec![OscType::String("#102030".to_string())],
+    );
+    assert_eq!(patterns.params(PatternKind::Sparkle).color, rgb(16, 32, 48));
+
+    assert_eq!(handle(&mut patterns, "/pattern/stop", vec![]), Some(Ok(())));
+    assert_eq!(patterns.running(), None);
+
+    assert_eq!(handle(&mut patterns, "/led_strips/0", vec![]), None);
+}
+
+#[test]
+fn rejects_invalid_pattern_messages() {
+    let mut patterns = Patterns::default();
+
+    assert_eq!(
+        handle(&mut patterns, "/pattern/fire", vec![]),
+        Some(Err(ErrorCode::UnknownPattern))
+    );
+    assert_eq!(
+        handle(
+            &mut patterns,
+            "/pattern/rainbow/hue",
+            vec![OscType::Float(1.0)]
+        ),
+        Some(Err(ErrorCode::UnknownAddress))
+    );
+    assert_eq!(
+        handle(
+            &mut patterns,
+            "/pattern/rainbow/speed",
+            vec![OscType::Float(-1.0)]
+        ),
+        Some(Err(ErrorCode::InvalidArgument))
+    );
+    assert_eq!(
+        handle(
+            &mut patterns,
+            "/pattern/chase/density",
+            vec![OscType::Float(2.0)]
+        ),
+        Some(Err(ErrorCode::InvalidArgument))
+    );
+    assert_eq!(
+        handle(&mut patterns, "/pattern/chase/color", vec![]),
+        Some(Err(ErrorCode::InvalidArgument))
+    );
+    assert_eq!(patterns.running(), None);
+}
+
+#[test]
+fn draws_frames_while_running() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 4))
+        .collect::<Vec<_>>();
+    let mut patterns = Patterns::default();
+
+    assert!(!patterns.update(&mut led_strips, 0));
+    assert_eq!(patterns.next_update_in_ms(0), None);
+
+    patterns.start(PatternKind::Rainbow, 1000);
+    assert!(patterns.update(&mut led_strips, 1000));
+    assert_eq!(led_strips[0].data[0], rgb(255, 0, 0));
+    assert_eq!(led_strips[1].data[0], rgb(255, 0, 0));
+
+    // Redrawn every 20ms
+    assert!(!patterns.update(&mut led_strips, 1010));
+    assert_eq!(patterns.next_update_in_ms(1010), Some(10));
+    assert!(patterns.update(&mut led_strips, 1020));
+    assert_ne!(led_strips[0].data[0], rgb(255, 0, 0));
+}
+
+#[test]
+fn breathing_fades_in_and_out() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 1];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 2))
+        .collect::<Vec<_>>();
+    let mut patterns = Patterns::new(PatternParams {
+        color: rgb(200, 100, 0),
+        ..PatternParams::default()
+    });
+
+    patterns.start(PatternKind::Breathing, 0);
+
+    patterns.update(&mut led_strips, 0);
+    assert_eq!(led_strips[0].data, vec![BLACK; 2]);
+
+    patterns.update(&mut led_strips, 2000);
+    assert_eq!(led_strips[0].data, vec![rgb(200, 100, 0); 2]);
+
+    patterns.update(&mut led_strips, 4000);
+    assert_eq!(led_strips[0].data, vec![BLACK; 2]);
+}
+
+#[test]
+fn chase_moves_blocks_along_the_strips() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 1];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 8))
+        .collect::<Vec<_>>();
+    let white = rgb(255, 255, 255);
+    let mut patterns = Patterns::new(PatternParams {
+        color: white,
+        scale: 4.0,
+        density: 0.5,
+        ..PatternParams::default()
+    });
+
+    patterns.start(PatternKind::Chase, 0);
+    patterns.update(&mut led_strips, 0);
+    assert_eq!(
+        led_strips[0].data,
+        vec![white, white, BLACK, BLACK, white, white, BLACK, BLACK]
+    );
+
+    // 30 LEDs per second, so the blocks have moved just past the next LED after 34ms
+    patterns.update(&mut led_strips, 34);
+    assert_eq!(
+        led_strips[0].data,
+        vec![BLACK, BLACK, white, white, BLACK, BLACK, white, white]
+    );
+}
+
+#[test]
+fn sparkle_lights_random_leds() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 1];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 100))
+        .collect::<Vec<_>>();
+    let mut patterns = Patterns::new(PatternParams {
+        density: 1.0,
+        ..PatternParams::default()
+    });
+
+    patterns.start(PatternKind::Sparkle, 0);
+    patterns.update(&mut led_strips, 0);
+    assert!(led_strips[0].data.iter().all(|led| *led == BLACK));
+
+    patterns.update(&mut led_strips, 100);
+    let lit = led_strips[0]
+        .data
+        .iter()
+        .filter(|led| **led != BLACK)
+        .count();
+    assert!(lit > 0 && lit < 100, "{} LEDs lit", lit);
+}
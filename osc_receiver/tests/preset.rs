@@ -0,0 +1,141 @@
+use osc_receiver::error_reply::ErrorCode;
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use osc_receiver::preset::{PresetCommand, Presets};
+use osc_receiver::scene::Scene;
+use rosc::OscType;
+
+mod common;
+
+use common::{rgb, BLACK};
+
+fn parse(addr: &str, args: Vec<OscType>) -> Option<Result<PresetCommand, ErrorCode>> {
+    common::parse(PresetCommand::parse, addr, args)
+}
+
+#[test]
+fn parses_store_and_recall_messages() {
+    assert_eq!(
+        parse("/preset/warm-white/store", vec![]),
+        Some(Ok(PresetCommand::Store {
+            name: "warm-white".to_string()
+        }))
+    );
+    assert_eq!(
+        parse("/preset/blue_1/recall", vec![]),
+        Some(Ok(PresetCommand::Recall {
+            name: "blue_1".to_string(),
+            fade_ms: 0
+        }))
+    );
+    assert_eq!(
+        parse("/preset/blue_1/recall", vec![OscType::Float(1.5)]),
+        Some(Ok(PresetCommand::Recall {
+            name: "blue_1".to_string(),
+            fade_ms: 1500
+        }))
+    );
+    assert_eq!(parse("/led_strips/0", vec![]), None);
+}
+
+#[test]
+fn rejects_invalid_preset_messages() {
+    assert_eq!(
+        parse("/preset/../store", vec![]),
+        Some(Err(ErrorCode::InvalidPresetName))
+    );
+    assert_eq!(
+        parse(&format!("/preset/{}/store", "a".repeat(33)), vec![]),
+        Some(Err(ErrorCode::InvalidPresetName))
+    );
+    assert_eq!(
+        parse("/preset/blue/recall", vec![OscType::Float(-1.0)]),
+        Some(Err(ErrorCode::InvalidArgument))
+    );
+    assert_eq!(
+        parse("/preset/blue/delete", vec![]),
+        Some(Err(ErrorCode::UnknownAddress))
+    );
+}
+
+#[test]
+fn recalls_stored_presets() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 2))
+        .collect::<Vec<_>>();
+
+    let mut presets = Presets::default();
+    led_strips[0].data[1] = rgb(1, 2, 3);
+    presets.insert("first", Scene::capture(&led_strips));
+
+    led_strips[0].data[1] = BLACK;
+    let mut crossfade = presets.recall("first", &led_strips, 0, 100).unwrap();
+
+    assert!(crossfade.update(&mut led_strips, 100));
+    assert!(crossfade.is_finished());
+    assert_eq!(led_strips[0].data, vec![BLACK, rgb(1, 2, 3)]);
+
+    let err = presets.recall("second", &led_strips, 0, 100).unwrap_err();
+    assert_eq!(err.code, ErrorCode::UnknownPreset);
+    assert_eq!(err.addr, "/preset/second/recall");
+}
+
+#[test]
+fn crossfades_to_the_preset() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 1];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 3))
+        .collect::<Vec<_>>();
+    led_strips[0].data[2] = rgb(50, 50, 50);
+
+    // The preset was stored on a shorter strip, so the last LED is left unchanged
+    let scene = Scene {
+        strips: vec![vec![rgb(200, 0, 100); 2]],
+    };
+    let mut presets = Presets::default();
+    presets.insert("pink", scene);
+
+    let mut crossfade = presets.recall("pink", &led_strips, 1000, 0).unwrap();
+
+    assert!(crossfade.update(&mut led_strips, 0));
+    assert_eq!(led_strips[0].data[0], BLACK);
+
+    // Redrawn at most every 33ms
+    assert!(!crossfade.update(&mut led_strips, 10));
+    assert_eq!(crossfade.next_update_in_ms(10), Some(23));
+
+    assert!(crossfade.update(&mut led_strips, 500));
+    assert_eq!(led_strips[0].data[1], rgb(100, 0, 50));
+
+    assert!(crossfade.update(&mut led_strips, 1000));
+    assert_eq!(
+        led_strips[0].data,
+        vec![rgb(200, 0, 100), rgb(200, 0, 100), rgb(50, 50, 50)]
+    );
+    assert_eq!(crossfade.next_update_in_ms(1000), None);
+}
+
+#[test]
+fn presets_round_trip() {
+    let mut presets = Presets::default();
+    presets.insert(
+        "a",
+        Scene {
+            strips: vec![vec![rgb(1, 2, 3); 4]],
+        },
+    );
+    presets.insert(
+        "b",
+        Scene {
+            strips: vec![vec![BLACK], vec![rgb(4, 5, 6)]],
+        },
+    );
+
+    let bytes = presets.encode().unwrap();
+    assert_eq!(Presets::decode(&bytes).unwrap(), presets);
+    assert_eq!(Presets::decode(&[]).unwrap(), Presets::default());
+    assert!(Presets::decode(&bytes[..bytes.len() - 1]).is_err());
+}
@@ -0,0 +1,242 @@
+use osc_receiver::provisioning::{
+    dns_reply, url_decode, DhcpServer, Portal, Request, Settings, AP_IP,
+};
+use simple_dns::rdata::RData;
+use simple_dns::{Name, Packet, Question, CLASS, QCLASS, QTYPE, TYPE};
+
+fn portal() -> Portal {
+    Portal::new(
+        Settings::new("old-network", "old password", "raves-esp32c3"),
+        vec!["venue".to_string(), "<script>".to_string()],
+    )
+}
+
+fn request<'a>(method: &'a str, path: &'a str, body: &'a [u8]) -> Request<'a> {
+    Request { method, path, body }
+}
+
+#[test]
+fn settings_round_trip() {
+    let settings = Settings {
+        osc_port: 8001,
+        ..Settings::new("venue wifi", "hunter22", "stage-left")
+    };
+
+    assert_eq!(Settings::decode(&settings.encode()).unwrap(), settings);
+
+    let bytes = settings.encode();
+    assert!(Settings::decode(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Settings::decode(&[]).is_err());
+    // Erased flash
+    assert!(Settings::decode(&[0xFF; 16]).is_err());
+}
+
+#[test]
+fn validates_settings() {
+    assert!(Settings::new("venue", "", "receiver-1").validate().is_ok());
+    assert!(Settings::new("", "hunter22", "receiver")
+        .validate()
+        .is_err());
+    assert!(Settings::new("venue", "short", "receiver")
+        .validate()
+        .is_err());
+    assert!(Settings::new("venue", "hunter22", "-receiver")
+        .validate()
+        .is_err());
+    assert!(Settings::new("venue", "hunter22", "stage left")
+        .validate()
+        .is_err());
+}
+
+#[test]
+fn parses_requests_once_they_are_complete() {
+    let bytes = b"POST /?x=1 HTTP/1.1\r\nHost: 192.168.4.1\r\nContent-Length: 10\r\n\r\nssid=venu";
+    assert_eq!(Request::parse(bytes).unwrap(), None);
+    assert_eq!(Request::parse(b"GET / HTTP/1.1\r\nHost:").unwrap(), None);
+
+    let bytes = b"POST /?x=1 HTTP/1.1\r\nHost: 192.168.4.1\r\ncontent-length: 10\r\n\r\nssid=venue";
+    assert_eq!(
+        Request::parse(bytes).unwrap(),
+        Some(request("POST", "/", b"ssid=venue"))
+    );
+
+    assert!(Request::parse(&[b'a'; 8 * 1024]).is_err());
+    assert!(Request::parse(
+        b"POST / HTTP/1.1\r\nContent-Length: 18446744073709551615\r\n\r\nssid=venue"
+    )
+    .is_err());
+}
+
+#[test]
+fn serves_the_form() {
+    let (response, settings) = portal().handle(&request("GET", "/", b""));
+
+    assert_eq!(response.status, 200);
+    assert_eq!(settings, None);
+    assert!(response.body.contains("value=\"old-network\""));
+    assert!(response.body.contains("<option value=\"venue\">"));
+    assert!(response.body.contains("&lt;script&gt;"));
+    // The stored password isn't shown
+    assert!(!response.body.contains("old password"));
+
+    let encoded = String::from_utf8(response.encode()).unwrap();
+    assert!(encoded.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(encoded.contains(&format!("Content-Length: {}\r\n", response.body.len())));
+}
+
+#[test]
+fn saves_valid_settings() {
+    let body = b"ssid=Venue+Wi-Fi%21&password=hunter22&name=stage-left&osc_port=8001";
+    let (response, settings) = portal().handle(&request("POST", "/", body));
+
+    assert_eq!(response.status, 200);
+    assert_eq!(
+        settings,
+        Some(Settings {
+            osc_port: 8001,
+            ..Settings::new("Venue Wi-Fi!", "hunter22", "stage-left")
+        })
+    );
+}
+
+#[test]
+fn keeps_the_stored_password_unless_the_network_is_open() {
+    let body = b"ssid=old-network&password=&name=stage-left&osc_port=9000";
+    let (_, settings) = portal().handle(&request("POST", "/", body));
+    assert_eq!(settings.unwrap().password, "old password");
+
+    let body = b"ssid=venue&password=&open_network=on&name=stage-left&osc_port=9000";
+    let (_, settings) = portal().handle(&request("POST", "/", body));
+    assert_eq!(settings.unwrap().password, "");
+}
+
+#[test]
+fn shows_errors_for_invalid_settings() {
+    let body = b"ssid=venue&password=short&name=stage-left&osc_port=9000";
+    let (response, settings) = portal().handle(&request("POST", "/", body));
+
+    assert_eq!(response.status, 400);
+    assert_eq!(settings, None);
+    assert!(response.body.contains("class=\"error\""));
+    // The submitted values are kept
+    assert!(response.body.contains("value=\"venue\""));
+
+    let body = b"ssid=venue&password=&name=stage-left&osc_port=99999";
+    assert_eq!(portal().handle(&request("POST", "/", body)).1, None);
+}
+
+#[test]
+fn redirects_other_pages_to_the_form() {
+    let (response, _) = portal().handle(&request("GET", "/generate_204", b""));
+
+    assert_eq!(response.status, 302);
+    assert_eq!(response.location.as_deref(), Some("http://192.168.4.1/"));
+    assert!(String::from_utf8(response.encode())
+        .unwrap()
+        .contains("Location: http://192.168.4.1/\r\n"));
+}
+
+#[test]
+fn decodes_form_values() {
+    assert_eq!(url_decode("a+b%20c%2Fd"), "a b c/d");
+    assert_eq!(url_decode("caf%C3%A9"), "café");
+    assert_eq!(url_decode("100%"), "100%");
+    assert_eq!(url_decode("%zz"), "%zz");
+}
+
+#[test]
+fn answers_every_dns_query_with_the_portal() {
+    let mut query = Packet::new_query(1234);
+    query.questions.push(Question::new(
+        Name::new_unchecked("connectivitycheck.gstatic.com"),
+        QTYPE::TYPE(TYPE::A),
+        QCLASS::CLASS(CLASS::IN),
+        false,
+    ));
+    let query = query.build_bytes_vec_compressed().unwrap();
+
+    let reply = dns_reply(&query).unwrap();
+    let reply = Packet::parse(&reply).unwrap();
+
+    assert_eq!(reply.id(), 1234);
+    assert_eq!(reply.answers.len(), 1);
+    assert_eq!(
+        reply.answers[0].name.to_string(),
+        "connectivitycheck.gstatic.com"
+    );
+    match &reply.answers[0].rdata {
+        RData::A(a) => assert_eq!(a.address.to_be_bytes(), AP_IP),
+        rdata => panic!("Unexpected record {:?}", rdata),
+    }
+
+    // Replies aren't answered
+    assert_eq!(dns_reply(&[0, 0, 0x84, 0, 0, 0, 0, 0, 0, 0, 0, 0]), None);
+}
+
+fn dhcp_message(message_type: u8, hardware_address: u8, options: &[u8]) -> Vec<u8> {
+    let mut message = vec![0; 240];
+    message[..4].copy_from_slice(&[1, 1, 6, 0]);
+    message[4..8].copy_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+    message[28..34].copy_from_slice(&[2, 0, 0, 0, 0, hardware_address]);
+    message[236..].copy_from_slice(&[99, 130, 83, 99]);
+    message.extend_from_slice(&[53, 1, message_type]);
+    message.extend_from_slice(options);
+    message.push(255);
+    message
+}
+
+// Returns the offered address and the message type of a DHCP reply
+fn parse_dhcp_reply(reply: &[u8]) -> ([u8; 4], u8) {
+    assert_eq!(reply[0], 2);
+    assert_eq!(&reply[4..8], &[0xDE, 0xAD, 0xBE, 0xEF]);
+    assert_eq!(&reply[236..240], &[99, 130, 83, 99]);
+    assert!(reply.len() >= 300);
+    assert_eq!(&reply[240..242], &[53, 1]);
+
+    ([reply[16], reply[17], reply[18], reply[19]], reply[242])
+}
+
+#[test]
+fn leases_addresses_to_clients() {
+    let mut dhcp = DhcpServer::new();
+
+    let offer = dhcp.reply(&dhcp_message(1, 1, &[])).unwrap();
+    assert_eq!(parse_dhcp_reply(&offer), ([192, 168, 4, 2], 2));
+    // DNS queries go to the portal
+    assert!(offer
+        .windows(6)
+        .any(|option| option == [6, 4, 192, 168, 4, 1]));
+
+    let ack = dhcp
+        .reply(&dhcp_message(
+            3,
+            1,
+            &[50, 4, 192, 168, 4, 2, 54, 4, 192, 168, 4, 1],
+        ))
+        .unwrap();
+    assert_eq!(parse_dhcp_reply(&ack), ([192, 168, 4, 2], 5));
+
+    // Other clients get their own addresses
+    let offer = dhcp.reply(&dhcp_message(1, 2, &[])).unwrap();
+    assert_eq!(parse_dhcp_reply(&offer), ([192, 168, 4, 3], 2));
+}
+
+#[test]
+fn refuses_addresses_it_did_not_lease() {
+    let mut dhcp = DhcpServer::new();
+
+    // A client rebooting with the address it had on another network
+    let nak = dhcp
+        .reply(&dhcp_message(3, 1, &[50, 4, 10, 0, 0, 20]))
+        .unwrap();
+    assert_eq!(parse_dhcp_reply(&nak), ([0, 0, 0, 0], 6));
+
+    // Requests for another server's offer are ignored
+    assert_eq!(
+        dhcp.reply(&dhcp_message(3, 1, &[54, 4, 192, 168, 4, 254])),
+        None
+    );
+    // Releases aren't answered
+    assert_eq!(dhcp.reply(&dhcp_message(7, 1, &[])), None);
+    assert_eq!(dhcp.reply(&[1, 1, 6]), None);
+}
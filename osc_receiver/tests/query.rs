@@ -0,0 +1,117 @@
+use osc_receiver::query::{
+    self, QueryHandler, ReceiverInfo, StripInfo, INFO_ADDR, PING_ADDR, PONG_ADDR, STATUS_ADDR,
+};
+use rosc::OscType;
+
+mod common;
+
+use common::{color, message, rgb};
+
+#[test]
+fn ping_replies_with_pong_and_the_same_arguments() {
+    let reply = query::reply(&message(PING_ADDR, vec![OscType::Int(42)]));
+
+    assert_eq!(reply, Some(message(PONG_ADDR, vec![OscType::Int(42)])));
+}
+
+#[test]
+fn color_messages_are_not_queries() {
+    let packet = message("/led_strips/0", vec![color(rgb(1, 2, 3))]);
+
+    assert_eq!(query::reply(&packet), None);
+}
+
+fn handler() -> QueryHandler {
+    let info = ReceiverInfo {
+        version: "0.1.0".to_string(),
+        strips: vec![
+            StripInfo::new(450, "APA102"),
+            StripInfo::new(144, "WS2812B"),
+        ],
+    };
+
+    QueryHandler::new(info, 1_000)
+}
+
+#[test]
+fn info_lists_the_version_and_every_strip() {
+    let reply = handler().reply(&message(INFO_ADDR, vec![]), 1_000);
+
+    assert_eq!(
+        reply,
+        Some(message(
+            INFO_ADDR,
+            vec![
+                OscType::String("0.1.0".to_string()),
+                OscType::Int(2),
+                OscType::Int(450),
+                OscType::String("APA102".to_string()),
+                OscType::Int(144),
+                OscType::String("WS2812B".to_string()),
+            ]
+        ))
+    );
+}
+
+#[test]
+fn strip_info_describes_one_strip() {
+    let handler = handler();
+
+    assert_eq!(
+        handler.reply(&message("/led_strips/1/info", vec![]), 1_000),
+        Some(message(
+            "/led_strips/1/info",
+            vec![OscType::Int(144), OscType::String("WS2812B".to_string())]
+        ))
+    );
+    assert_eq!(
+        handler.reply(&message("/led_strips/2/info", vec![]), 1_000),
+        None
+    );
+}
+
+#[test]
+fn status_reports_uptime_frame_rate_and_counters() {
+    let mut handler = handler();
+
+    // 30 frames over one second
+    for i in 1..=30 {
+        handler.status.frame_displayed(1_000 + i * 1_000 / 30);
+    }
+    handler.status.invalid_packets += 2;
+
+    let reply = handler.reply(&message(STATUS_ADDR, vec![]), 3_500);
+
+    assert_eq!(
+        reply,
+        Some(message(
+            STATUS_ADDR,
+            vec![
+                OscType::Float(2.5),
+                OscType::Float(30.0),
+                OscType::Int(30),
+                OscType::Int(2),
+                OscType::Int(0),
+            ]
+        ))
+    );
+}
+
+#[test]
+fn the_frame_rate_drops_to_zero_once_frames_stop() {
+    let mut handler = handler();
+
+    for i in 1..=30 {
+        handler.status.frame_displayed(1_000 + i * 1_000 / 30);
+    }
+
+    assert_eq!(handler.status.fps(2_500), 30.0);
+    assert_eq!(handler.status.fps(4_000), 0.0);
+}
+
+#[test]
+fn the_handler_still_answers_pings() {
+    let reply = handler().reply(&message(PING_ADDR, vec![OscType::Int(7)]), 1_000);
+
+    assert_eq!(reply, Some(message(PONG_ADDR, vec![OscType::Int(7)])));
+}
@@ -0,0 +1,66 @@
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use osc_receiver::scene::{self, Scene, SAVE_DEFAULT_ADDR};
+use osc_receiver::show::ShowReader;
+
+mod common;
+
+use common::{message, rgb, BLACK};
+
+#[test]
+fn scenes_round_trip_through_the_show_format() {
+    let scene = Scene {
+        strips: vec![vec![rgb(1, 2, 3); 100], vec![rgb(4, 5, 6), BLACK]],
+    };
+
+    let bytes = scene.encode().unwrap();
+
+    assert_eq!(Scene::decode(&bytes).unwrap(), scene);
+    assert_eq!(
+        ShowReader::new(&bytes).unwrap().header.strip_lengths,
+        vec![100, 2]
+    );
+}
+
+#[test]
+fn invalid_scenes_are_rejected() {
+    assert!(Scene::decode(b"not a scene").is_err());
+
+    let mut bytes = Scene {
+        strips: vec![vec![rgb(1, 2, 3); 3]],
+    }
+    .encode()
+    .unwrap();
+    bytes.truncate(bytes.len() - 1);
+    assert!(Scene::decode(&bytes).is_err());
+}
+
+#[test]
+fn captures_and_applies_the_strips() {
+    let mut smart_leds = vec![RecordingSmartLed::new(); 2];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .map(|smart_led| LedStrip::with_length(smart_led, 2))
+        .collect::<Vec<_>>();
+
+    led_strips[1].data[0] = rgb(9, 9, 9);
+    let scene = Scene::capture(&led_strips);
+    assert_eq!(
+        scene.strips,
+        vec![vec![BLACK; 2], vec![rgb(9, 9, 9), BLACK]]
+    );
+
+    // Scenes saved on shorter strips leave the extra LEDs unchanged
+    let scene = Scene {
+        strips: vec![vec![rgb(1, 1, 1)]],
+    };
+    scene.apply(&mut led_strips);
+    assert_eq!(led_strips[0].data, vec![rgb(1, 1, 1), BLACK]);
+    assert_eq!(led_strips[1].data, vec![rgb(9, 9, 9), BLACK]);
+}
+
+#[test]
+fn recognizes_save_default_messages() {
+    assert!(scene::is_save_default(&message(SAVE_DEFAULT_ADDR, vec![])));
+    assert!(!scene::is_save_default(&message("/scene/save", vec![])));
+}
@@ -0,0 +1,125 @@
+use osc_receiver::show::{ShowFrame, ShowHeader, ShowReader};
+
+mod common;
+
+use common::rgb;
+
+fn header() -> ShowHeader {
+    ShowHeader {
+        started_at_unix_ms: 1_660_000_000_000,
+        strip_lengths: vec![3, 2],
+    }
+}
+
+fn encode(header: &ShowHeader, frames: &[ShowFrame]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+
+    header.encode(&mut bytes).unwrap();
+    for frame in frames {
+        frame.encode(&mut bytes);
+    }
+
+    bytes
+}
+
+#[test]
+fn round_trips_headers_and_frames() {
+    let frames = vec![
+        ShowFrame {
+            timestamp_ms: 0,
+            strips: vec![
+                vec![rgb(1, 2, 3), rgb(4, 5, 6), rgb(7, 8, 9)],
+                vec![rgb(10, 11, 12), rgb(13, 14, 15)],
+            ],
+        },
+        ShowFrame {
+            timestamp_ms: 33,
+            strips: vec![vec![rgb(255, 0, 0); 3], vec![rgb(255, 0, 0); 2]],
+        },
+    ];
+    let bytes = encode(&header(), &frames);
+
+    let reader = ShowReader::new(&bytes).unwrap();
+
+    assert_eq!(reader.header, header());
+    assert_eq!(reader.collect::<Result<Vec<_>, _>>().unwrap(), frames);
+}
+
+#[test]
+fn run_length_encodes_repeated_colors() {
+    let header = ShowHeader {
+        started_at_unix_ms: 0,
+        strip_lengths: vec![450, 450],
+    };
+    let frame = ShowFrame {
+        timestamp_ms: 0,
+        strips: vec![vec![rgb(0, 0, 0); 450], vec![rgb(0, 0, 255); 450]],
+    };
+
+    let mut bytes = Vec::new();
+    frame.encode(&mut bytes);
+
+    // Runs of 255 and 195 black LEDs followed by runs of 255 and 195 blue LEDs
+    assert_eq!(bytes.len(), 9 + 4 * 4);
+    assert_eq!(
+        ShowFrame::decode(&header, &bytes).unwrap(),
+        (frame, bytes.len())
+    );
+}
+
+#[test]
+fn rejects_other_files_and_versions() {
+    assert!(ShowReader::new(b"not a show file").is_err());
+
+    let mut bytes = encode(&header(), &[]);
+    bytes[8] = 2;
+    assert!(ShowReader::new(&bytes).is_err());
+}
+
+#[test]
+fn stops_at_a_truncated_frame() {
+    let frame = ShowFrame {
+        timestamp_ms: 10,
+        strips: vec![vec![rgb(1, 2, 3); 3], vec![rgb(4, 5, 6); 2]],
+    };
+    let mut bytes = encode(&header(), &[frame.clone(), frame.clone()]);
+    bytes.truncate(bytes.len() - 2);
+
+    let frames = ShowReader::new(&bytes).unwrap().collect::<Vec<_>>();
+
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].as_ref().unwrap(), &frame);
+    assert!(frames[1].is_err());
+}
+
+#[test]
+fn headers_do_not_size_allocations() {
+    // Claims 65535 strips of 65535 LEDs each
+    let header = ShowHeader {
+        started_at_unix_ms: 0,
+        strip_lengths: vec![u16::MAX as usize; u16::MAX as usize],
+    };
+    let mut bytes = Vec::new();
+    ShowFrame {
+        timestamp_ms: 0,
+        strips: vec![vec![rgb(1, 2, 3)]],
+    }
+    .encode(&mut bytes);
+
+    assert!(ShowFrame::decode(&header, &bytes).is_err());
+
+    // A frame claiming 4GB of LEDs
+    let mut bytes = vec![0, 0, 0, 0, 0];
+    bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+    assert!(ShowFrame::decode(&header, &bytes).is_err());
+}
+
+#[test]
+fn rejects_strips_too_long_to_encode() {
+    let header = ShowHeader {
+        started_at_unix_ms: 0,
+        strip_lengths: vec![u16::MAX as usize + 1],
+    };
+
+    assert!(header.encode(&mut Vec::new()).is_err());
+}
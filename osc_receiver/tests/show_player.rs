@@ -0,0 +1,158 @@
+use osc_receiver::show::{ShowFrame, ShowHeader};
+use osc_receiver::show_player::ShowPlayer;
+use rosc::OscType;
+use smart_leds::RGB8;
+
+mod common;
+
+use common::message;
+
+fn gray(value: u8) -> Vec<Vec<RGB8>> {
+    vec![vec![
+        RGB8 {
+            r: value,
+            g: value,
+            b: value,
+        };
+        2
+    ]]
+}
+
+/// A show with a frame every 100ms where each frame's brightness is its index
+fn player(frame_count: u8) -> ShowPlayer {
+    let mut bytes = Vec::new();
+
+    ShowHeader {
+        started_at_unix_ms: 0,
+        strip_lengths: vec![2],
+    }
+    .encode(&mut bytes)
+    .unwrap();
+
+    for i in 0..frame_count {
+        ShowFrame {
+            timestamp_ms: i as u32 * 100,
+            strips: gray(i),
+        }
+        .encode(&mut bytes);
+    }
+
+    ShowPlayer::new(bytes).unwrap()
+}
+
+fn displayed(frame: Option<ShowFrame>) -> Option<u8> {
+    frame.map(|frame| frame.strips[0][0].r)
+}
+
+#[test]
+fn starts_paused_on_the_first_frame() {
+    let mut player = player(3);
+
+    assert_eq!(player.frame_count(), 3);
+    assert_eq!(player.duration_ms(), 300);
+    assert_eq!(displayed(player.advance(500.0)), Some(0));
+    assert_eq!(displayed(player.advance(500.0)), None);
+}
+
+#[test]
+fn plays_frames_at_their_timestamps() {
+    let mut player = player(3);
+    player.play();
+
+    assert_eq!(displayed(player.advance(0.0)), Some(0));
+    assert_eq!(displayed(player.advance(50.0)), None);
+    assert_eq!(player.next_frame_in_ms(), Some(50.0));
+    assert_eq!(displayed(player.advance(50.0)), Some(1));
+    assert_eq!(displayed(player.advance(100.0)), Some(2));
+}
+
+#[test]
+fn loops_back_to_the_start() {
+    let mut player = player(3);
+    player.play();
+    player.advance(150.0);
+
+    assert_eq!(displayed(player.advance(200.0)), Some(0));
+    assert!(player.playing);
+}
+
+#[test]
+fn stops_at_the_end_without_looping() {
+    let mut player = player(3);
+    player.looping = false;
+    player.play();
+
+    assert_eq!(displayed(player.advance(1000.0)), Some(2));
+    assert!(!player.playing);
+}
+
+#[test]
+fn handles_transport_commands() {
+    let mut player = player(5);
+
+    assert!(player.handle_packet(&message("/show/play", vec![])));
+    assert!(player.playing);
+
+    assert!(player.handle_packet(&message("/show/seek", vec![OscType::Float(0.25)])));
+    assert_eq!(displayed(player.advance(0.0)), Some(2));
+
+    assert!(player.handle_packet(&message("/show/speed", vec![OscType::Int(2)])));
+    assert_eq!(displayed(player.advance(50.0)), Some(3));
+
+    assert!(player.handle_packet(&message("/show/pause", vec![])));
+    assert!(!player.playing);
+
+    assert!(player.handle_packet(&message("/show/loop", vec![OscType::Bool(false)])));
+    assert!(!player.looping);
+
+    assert!(!player.handle_packet(&message("/led_strips/0", vec![])));
+}
+
+#[test]
+fn fast_playback_wraps_around_the_show() {
+    let mut player = player(3);
+    player.speed = 1e30;
+    player.play();
+
+    assert!(player.advance(1000.0).is_some());
+    assert!((0.0..300.0).contains(&player.position_ms()));
+}
+
+#[test]
+fn rejects_invalid_speeds_and_positions() {
+    let mut player = player(5);
+
+    for speed in [
+        OscType::Float(f32::INFINITY),
+        OscType::Float(f32::NAN),
+        OscType::Double(1e300),
+        OscType::Int(0),
+    ] {
+        assert!(player.handle_packet(&message("/show/speed", vec![speed])));
+        assert_eq!(player.speed, 1.0);
+    }
+
+    player.seek(200.0);
+    assert!(player.handle_packet(&message("/show/seek", vec![OscType::Double(f64::NAN)])));
+    assert_eq!(player.position_ms(), 200.0);
+}
+
+#[test]
+fn out_of_order_timestamps_do_not_overflow() {
+    let mut bytes = Vec::new();
+    ShowHeader {
+        started_at_unix_ms: 0,
+        strip_lengths: vec![2],
+    }
+    .encode(&mut bytes)
+    .unwrap();
+    for timestamp_ms in [u32::MAX - 1, 100] {
+        ShowFrame {
+            timestamp_ms,
+            strips: gray(0),
+        }
+        .encode(&mut bytes);
+    }
+
+    assert_eq!(ShowPlayer::new(bytes).unwrap().duration_ms(), 100);
+}
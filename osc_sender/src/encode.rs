@@ -0,0 +1,179 @@
+//! Encodes frames into the `/led_strips` messages that `osc_receiver` displays.
+//!
+//! Each strip is sent to `/led_strips/{n}`. Strips that don't fit in a single packet are split
+//! into several messages sent to `/led_strips/{n}/{offset}`, where `offset` is the index of the
+//! message's first LED within the strip.
+use anyhow::{anyhow, bail, Result};
+use rosc::{OscBundle, OscColor, OscMessage, OscPacket, OscTime, OscType};
+
+use crate::frame::Frame;
+
+/// The largest UDP payload that fits in a 1500 byte ethernet frame without IP fragmentation. The
+/// ESP32 receiver can't reassemble fragmented packets.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 1500 - 20 - 8;
+
+// "#bundle\0" followed by the time tag
+const BUNDLE_HEADER_SIZE: usize = 8 + 8;
+// Each bundle element is prefixed with its size
+const BUNDLE_ELEMENT_HEADER_SIZE: usize = 4;
+
+// Tells the receiver to display the bundle immediately
+const IMMEDIATELY: OscTime = OscTime {
+    seconds: 0,
+    fractional: 1,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    /// Each message is sent in its own packet. The receiver displays each message as it arrives.
+    #[default]
+    Messages,
+    /// Messages are packed into as few bundles as possible. The receiver displays each bundle
+    /// once every message in it has been applied, so frames that fit in one packet never tear.
+    Bundles,
+}
+
+impl Frame {
+    /// Encodes the frame as `/led_strips` messages of at most `max_packet_size` bytes each.
+    pub fn to_messages(&self, max_packet_size: usize) -> Result<Vec<OscMessage>> {
+        let mut messages = Vec::new();
+
+        for (strip_index, strip) in self.strips().iter().enumerate() {
+            let mut offset = 0;
+
+            while offset < strip.len() {
+                let addr = if offset == 0 {
+                    format!("/led_strips/{}", strip_index)
+                } else {
+                    format!("/led_strips/{}/{}", strip_index, offset)
+                };
+
+                let count = max_colors(&addr, max_packet_size).min(strip.len() - offset);
+                if count == 0 {
+                    bail!(
+                        "A max packet size of {} bytes is too small to send any LEDs",
+                        max_packet_size
+                    );
+                }
+
+                messages.push(color_message(addr, &strip[offset..offset + count]));
+                offset += count;
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Encodes the frame as packets of at most `max_packet_size` bytes each.
+    pub fn to_packets(&self, encoding: Encoding, max_packet_size: usize) -> Result<Vec<OscPacket>> {
+        match encoding {
+            Encoding::Messages => Ok(self
+                .to_messages(max_packet_size)?
+                .into_iter()
+                .map(OscPacket::Message)
+                .collect()),
+            Encoding::Bundles => {
+                let max_message_size = max_packet_size
+                    .checked_sub(BUNDLE_HEADER_SIZE + BUNDLE_ELEMENT_HEADER_SIZE)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "A max packet size of {} bytes is too small for a bundle",
+                            max_packet_size
+                        )
+                    })?;
+
+                let mut bundles = Vec::new();
+                let mut content = Vec::new();
+                let mut bundle_size = BUNDLE_HEADER_SIZE;
+
+                for message in self.to_messages(max_message_size)? {
+                    let element_size = BUNDLE_ELEMENT_HEADER_SIZE + message_size(&message);
+
+                    if bundle_size + element_size > max_packet_size {
+                        bundles.push(bundle(std::mem::take(&mut content)));
+                        bundle_size = BUNDLE_HEADER_SIZE;
+                    }
+
+                    content.push(OscPacket::Message(message));
+                    bundle_size += element_size;
+                }
+
+                if !content.is_empty() {
+                    bundles.push(bundle(content));
+                }
+
+                Ok(bundles)
+            }
+        }
+    }
+
+    /// Encodes the frame into UDP payloads of at most `max_packet_size` bytes each.
+    pub fn encode(&self, encoding: Encoding, max_packet_size: usize) -> Result<Vec<Vec<u8>>> {
+        self.to_packets(encoding, max_packet_size)?
+            .iter()
+            .map(encode_packet)
+            .collect()
+    }
+}
+
+/// A message setting a single DMX channel. `value` is between 0 and 1.
+pub fn dmx_message(universe: usize, channel: usize, value: f32) -> OscMessage {
+    OscMessage {
+        addr: format!("/{}/dmx/{}", universe, channel),
+        args: vec![OscType::Float(value)],
+    }
+}
+
+pub fn encode_packet(packet: &OscPacket) -> Result<Vec<u8>> {
+    rosc::encoder::encode(packet).map_err(|err| anyhow!("Unable to encode OSC packet: {:?}", err))
+}
+
+fn color_message(addr: String, leds: &[smart_leds::RGB8]) -> OscMessage {
+    OscMessage {
+        addr,
+        args: leds
+            .iter()
+            .map(|led| {
+                OscType::Color(OscColor {
+                    red: led.r,
+                    green: led.g,
+                    blue: led.b,
+                    alpha: 0xFF,
+                })
+            })
+            .collect(),
+    }
+}
+
+fn bundle(content: Vec<OscPacket>) -> OscPacket {
+    OscPacket::Bundle(OscBundle {
+        timetag: IMMEDIATELY,
+        content,
+    })
+}
+
+/// The size of an OSC string including its null terminator and padding
+fn string_size(length: usize) -> usize {
+    (length + 4) & !3
+}
+
+/// The encoded size of a message of `count` colors
+fn color_message_size(addr: &str, count: usize) -> usize {
+    string_size(addr.len()) + string_size(1 + count) + 4 * count
+}
+
+fn message_size(message: &OscMessage) -> usize {
+    color_message_size(&message.addr, message.args.len())
+}
+
+/// The most colors a message to `addr` can hold without exceeding `max_packet_size`
+fn max_colors(addr: &str, max_packet_size: usize) -> usize {
+    // Each color takes 4 bytes plus a 1 byte type tag
+    let mut count = max_packet_size.saturating_sub(string_size(addr.len()) + 4) / 5;
+
+    while count > 0 && color_message_size(addr, count) > max_packet_size {
+        count -= 1;
+    }
+
+    count
+}
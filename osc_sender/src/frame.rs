@@ -0,0 +1,74 @@
+use smart_leds::RGB8;
+
+/// The colors of every LED of one receiver, one `Vec` per strip in the receiver's output order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Frame {
+    strips: Vec<Vec<RGB8>>,
+}
+
+impl Frame {
+    /// A frame with every LED off.
+    pub fn new(strip_lengths: &[usize]) -> Self {
+        Self::solid(strip_lengths, RGB8::default())
+    }
+
+    pub fn solid(strip_lengths: &[usize], color: RGB8) -> Self {
+        Self {
+            strips: strip_lengths
+                .iter()
+                .map(|length| vec![color; *length])
+                .collect(),
+        }
+    }
+
+    pub fn from_strips(strips: Vec<Vec<RGB8>>) -> Self {
+        Self { strips }
+    }
+
+    pub fn strip_lengths(&self) -> Vec<usize> {
+        self.strips.iter().map(|strip| strip.len()).collect()
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.strips.iter().map(|strip| strip.len()).sum()
+    }
+
+    pub fn strips(&self) -> &[Vec<RGB8>] {
+        &self.strips
+    }
+
+    pub fn strip(&self, strip: usize) -> &[RGB8] {
+        &self.strips[strip]
+    }
+
+    pub fn strip_mut(&mut self, strip: usize) -> &mut [RGB8] {
+        &mut self.strips[strip]
+    }
+
+    /// Every LED of every strip, in strip order.
+    pub fn leds(&self) -> impl Iterator<Item = &RGB8> {
+        self.strips.iter().flat_map(|strip| strip.iter())
+    }
+
+    /// Every LED of every strip, in strip order.
+    pub fn leds_mut(&mut self) -> impl Iterator<Item = &mut RGB8> {
+        self.strips.iter_mut().flat_map(|strip| strip.iter_mut())
+    }
+
+    /// Sets every LED of every strip to `color`.
+    pub fn fill(&mut self, color: RGB8) -> &mut Self {
+        self.leds_mut().for_each(|led| *led = color);
+        self
+    }
+
+    pub fn fill_strip(&mut self, strip: usize, color: RGB8) -> &mut Self {
+        self.strips[strip].iter_mut().for_each(|led| *led = color);
+        self
+    }
+
+    /// Sets a single LED. Panics if the strip or LED does not exist.
+    pub fn set(&mut self, strip: usize, led: usize, color: RGB8) -> &mut Self {
+        self.strips[strip][led] = color;
+        self
+    }
+}
@@ -0,0 +1,24 @@
+//! Sends frames to raves-on-rust receivers.
+//!
+//! ```no_run
+//! use osc_sender::{Frame, Sender, RGB8};
+//!
+//! let mut frame = Frame::new(&[450, 144]);
+//! frame.fill(RGB8 { r: 0x20, g: 0, b: 0 }).set(1, 0, RGB8 { r: 0xFF, g: 0xFF, b: 0xFF });
+//!
+//! let sender = Sender::new()?;
+//! sender.send(&frame, "192.168.1.50:8001".parse()?)?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+pub mod encode;
+pub mod frame;
+pub mod patterns;
+pub mod render_loop;
+pub mod sender;
+
+pub use encode::{Encoding, DEFAULT_MAX_PACKET_SIZE};
+pub use frame::Frame;
+pub use patterns::{Pattern, PatternKind, PatternParams};
+pub use render_loop::RenderLoop;
+pub use sender::Sender;
+pub use smart_leds::RGB8;
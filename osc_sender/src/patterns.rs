@@ -0,0 +1,545 @@
+//! Animated patterns that render into frames.
+//!
+//! Patterns are rendered once per frame with the time in seconds since the animation started.
+//! Each receiver gets its own instance of a pattern since patterns like `Twinkle` and `Fire` keep
+//! state for every LED.
+use std::f32::consts::TAU;
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use smart_leds::RGB8;
+
+use crate::frame::Frame;
+
+pub trait Pattern: Send {
+    fn render(&mut self, time: f64, frame: &mut Frame);
+}
+
+/// The patterns that can be built by name, eg. from the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternKind {
+    Rainbow,
+    Chase,
+    Twinkle,
+    Fire,
+    Plasma,
+    Gradient,
+    Noise,
+}
+
+pub const PATTERN_KINDS: [PatternKind; 7] = [
+    PatternKind::Rainbow,
+    PatternKind::Chase,
+    PatternKind::Twinkle,
+    PatternKind::Fire,
+    PatternKind::Plasma,
+    PatternKind::Gradient,
+    PatternKind::Noise,
+];
+
+/// The parameters shared by every pattern. Each pattern uses the parameters that make sense for
+/// it and ignores the rest.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PatternParams {
+    /// Multiplies how fast the pattern moves
+    pub speed: f32,
+    /// The size of the pattern in LEDs, eg. the length of one rainbow or the gap between chasers
+    pub scale: f32,
+    pub color: RGB8,
+    /// The second color of two color patterns, eg. the end of a gradient
+    pub background: RGB8,
+    /// How busy the pattern is from 0 to 1, eg. how many LEDs twinkle or how often fire sparks
+    pub density: f32,
+    /// Seeds the random patterns
+    pub seed: u64,
+}
+
+impl Default for PatternParams {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            scale: 60.0,
+            color: RGB8 {
+                r: 0xFF,
+                g: 0x80,
+                b: 0x00,
+            },
+            background: RGB8 {
+                r: 0,
+                g: 0,
+                b: 0x40,
+            },
+            density: 0.5,
+            seed: 0x5EED,
+        }
+    }
+}
+
+impl PatternKind {
+    pub fn build(self, params: &PatternParams) -> Box<dyn Pattern> {
+        match self {
+            PatternKind::Rainbow => Box::new(Rainbow {
+                speed: params.speed * 0.25,
+                length: params.scale,
+            }),
+            PatternKind::Chase => Box::new(Chase {
+                color: params.color,
+                background: params.background,
+                spacing: params.scale,
+                width: (params.scale * params.density).max(1.0),
+                speed: params.speed * 30.0,
+            }),
+            PatternKind::Twinkle => Box::new(Twinkle::new(
+                params.color,
+                params.density * 2.0,
+                1.0 / params.speed,
+                params.seed,
+            )),
+            PatternKind::Fire => Box::new(Fire::new(
+                (1.0 - params.density) * 0.5 + 0.1,
+                params.density,
+                params.speed,
+                params.seed,
+            )),
+            PatternKind::Plasma => Box::new(Plasma {
+                speed: params.speed,
+                scale: params.scale,
+            }),
+            PatternKind::Gradient => Box::new(Gradient {
+                from: params.color,
+                to: params.background,
+                speed: params.speed * 0.1,
+            }),
+            PatternKind::Noise => Box::new(Noise {
+                speed: params.speed,
+                scale: params.scale,
+                seed: params.seed,
+            }),
+        }
+    }
+}
+
+impl FromStr for PatternKind {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match PATTERN_KINDS
+            .iter()
+            .find(|kind| kind.to_string().eq_ignore_ascii_case(name))
+        {
+            Some(kind) => Ok(*kind),
+            None => bail!(
+                "Unknown pattern {:?}. Must be one of: {}",
+                name,
+                PATTERN_KINDS
+                    .iter()
+                    .map(|kind| kind.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            PatternKind::Rainbow => "rainbow",
+            PatternKind::Chase => "chase",
+            PatternKind::Twinkle => "twinkle",
+            PatternKind::Fire => "fire",
+            PatternKind::Plasma => "plasma",
+            PatternKind::Gradient => "gradient",
+            PatternKind::Noise => "noise",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// A rainbow scrolling along every strip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rainbow {
+    /// Rainbows per second that scroll past each LED
+    pub speed: f32,
+    /// The number of LEDs in one rainbow
+    pub length: f32,
+}
+
+impl Pattern for Rainbow {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let offset = (time * self.speed as f64).fract() as f32;
+
+        for strip in 0..frame.strips().len() {
+            for (i, led) in frame.strip_mut(strip).iter_mut().enumerate() {
+                *led = hsv(i as f32 / self.length.max(1.0) - offset, 1.0, 1.0);
+            }
+        }
+    }
+}
+
+/// Evenly spaced blocks of color moving along every strip.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chase {
+    pub color: RGB8,
+    pub background: RGB8,
+    /// The distance in LEDs from the start of one block to the start of the next
+    pub spacing: f32,
+    /// The number of LEDs in each block
+    pub width: f32,
+    /// LEDs per second
+    pub speed: f32,
+}
+
+impl Pattern for Chase {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let spacing = self.spacing.max(1.0);
+        let position = (time * self.speed as f64).rem_euclid(spacing as f64) as f32;
+
+        for strip in 0..frame.strips().len() {
+            for (i, led) in frame.strip_mut(strip).iter_mut().enumerate() {
+                let distance = (i as f32 - position).rem_euclid(spacing);
+
+                *led = if distance < self.width {
+                    self.color
+                } else {
+                    self.background
+                };
+            }
+        }
+    }
+}
+
+/// LEDs randomly light up and fade out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Twinkle {
+    pub color: RGB8,
+    /// The chance of each LED lighting up per second
+    pub rate: f32,
+    /// Seconds for a lit LED to fade out
+    pub fade: f32,
+    levels: Vec<f32>,
+    last_time: Option<f64>,
+    rng: Rng,
+}
+
+impl Twinkle {
+    pub fn new(color: RGB8, rate: f32, fade: f32, seed: u64) -> Self {
+        Self {
+            color,
+            rate,
+            fade,
+            levels: Vec::new(),
+            last_time: None,
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Pattern for Twinkle {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let elapsed = time_step(&mut self.last_time, time);
+        self.levels.resize(frame.led_count(), 0.0);
+
+        let fade = elapsed / self.fade.max(0.001);
+        let chance = self.rate * elapsed;
+
+        for (level, led) in self.levels.iter_mut().zip(frame.leds_mut()) {
+            *level = (*level - fade).max(0.0);
+            if self.rng.next_f32() < chance {
+                *level = 1.0;
+            }
+
+            *led = scale(self.color, *level);
+        }
+    }
+}
+
+/// Flames rising from the start of every strip, based on the classic Fire2012 effect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fire {
+    /// How quickly the flames cool as they rise, from 0 to 1
+    pub cooling: f32,
+    /// The chance of a new spark each step, from 0 to 1
+    pub sparking: f32,
+    /// Multiplies the number of simulation steps per second
+    pub speed: f32,
+    heat: Vec<Vec<f32>>,
+    last_time: Option<f64>,
+    pending_steps: f32,
+    rng: Rng,
+}
+
+// Simulation steps per second at a speed of 1
+const FIRE_STEPS_PER_SECOND: f32 = 60.0;
+
+impl Fire {
+    pub fn new(cooling: f32, sparking: f32, speed: f32, seed: u64) -> Self {
+        Self {
+            cooling,
+            sparking,
+            speed,
+            heat: Vec::new(),
+            last_time: None,
+            pending_steps: 0.0,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn step(&mut self) {
+        for heat in self.heat.iter_mut() {
+            let length = heat.len();
+            if length == 0 {
+                continue;
+            }
+
+            // Cool every cell a little
+            let max_cooling = self.cooling * 10.0 / length as f32 + 0.002;
+            for cell in heat.iter_mut() {
+                *cell = (*cell - self.rng.next_f32() * max_cooling).max(0.0);
+            }
+
+            // Heat drifts up and diffuses
+            for i in (2..length).rev() {
+                heat[i] = (heat[i - 1] + heat[i - 2] * 2.0) / 3.0;
+            }
+
+            // Randomly ignite new sparks near the bottom
+            if self.rng.next_f32() < self.sparking {
+                let i = self.rng.next_below(length.min(7));
+                heat[i] = (heat[i] + 0.6 + self.rng.next_f32() * 0.4).min(1.0);
+            }
+        }
+    }
+}
+
+impl Pattern for Fire {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let elapsed = time_step(&mut self.last_time, time);
+
+        let strip_lengths = frame.strip_lengths();
+        if self
+            .heat
+            .iter()
+            .map(|heat| heat.len())
+            .ne(strip_lengths.iter().copied())
+        {
+            self.heat = strip_lengths
+                .iter()
+                .map(|length| vec![0.0; *length])
+                .collect();
+        }
+
+        // Catch up on the steps since the last frame, without stalling after a long pause
+        self.pending_steps =
+            (self.pending_steps + elapsed * self.speed * FIRE_STEPS_PER_SECOND).min(10.0);
+        while self.pending_steps >= 1.0 {
+            self.step();
+            self.pending_steps -= 1.0;
+        }
+
+        for (strip, heat) in self.heat.iter().enumerate() {
+            for (led, cell) in frame.strip_mut(strip).iter_mut().zip(heat.iter()) {
+                *led = heat_color(*cell);
+            }
+        }
+    }
+}
+
+/// Overlapping waves of color.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plasma {
+    pub speed: f32,
+    /// The approximate size in LEDs of each blob of color
+    pub scale: f32,
+}
+
+impl Pattern for Plasma {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let time = (time * self.speed as f64) as f32;
+        let scale = self.scale.max(1.0);
+
+        for strip in 0..frame.strips().len() {
+            let strip_offset = strip as f32 * 1.7;
+
+            for (i, led) in frame.strip_mut(strip).iter_mut().enumerate() {
+                let x = i as f32 / scale;
+                let value = (x * TAU + time).sin()
+                    + ((x * 0.5 - time * 0.7 + strip_offset) * TAU).sin()
+                    + ((x * 0.25 + time * 0.3) * TAU).sin();
+
+                *led = hsv(value / 6.0 + time * 0.05, 1.0, 1.0);
+            }
+        }
+    }
+}
+
+/// A gradient between two colors across each strip that slowly swaps ends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+    pub from: RGB8,
+    pub to: RGB8,
+    /// Back and forth sweeps per second. 0 keeps the gradient still
+    pub speed: f32,
+}
+
+impl Pattern for Gradient {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let offset = (time * self.speed as f64).fract() as f32;
+
+        for strip in 0..frame.strips().len() {
+            let leds = frame.strip_mut(strip);
+            let last = leds.len().saturating_sub(1).max(1) as f32;
+
+            for (i, led) in leds.iter_mut().enumerate() {
+                let position = (i as f32 / last + offset * 2.0) % 2.0;
+                // Triangle wave so that the gradient sweeps back without a seam
+                let amount = if position > 1.0 {
+                    2.0 - position
+                } else {
+                    position
+                };
+
+                *led = lerp(self.from, self.to, amount);
+            }
+        }
+    }
+}
+
+/// Smoothly changing random colors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Noise {
+    pub speed: f32,
+    /// The approximate size in LEDs of each patch of color
+    pub scale: f32,
+    pub seed: u64,
+}
+
+impl Pattern for Noise {
+    fn render(&mut self, time: f64, frame: &mut Frame) {
+        let time = (time * self.speed as f64 * 0.5) as f32;
+        let scale = self.scale.max(1.0);
+
+        for strip in 0..frame.strips().len() {
+            let seed = self.seed.wrapping_add(strip as u64 * 0x9E37_79B9);
+
+            for (i, led) in frame.strip_mut(strip).iter_mut().enumerate() {
+                let x = i as f32 / scale;
+                let hue = value_noise(seed, x + time, time * 0.3);
+                let value = value_noise(seed ^ 0xFF, x * 2.0 - time, time * 0.5);
+
+                *led = hsv(hue * 1.5, 1.0, 0.2 + value * 0.8);
+            }
+        }
+    }
+}
+
+/// Converts a hue (wrapping from 0 to 1), saturation and value to a color.
+pub fn hsv(hue: f32, saturation: f32, value: f32) -> RGB8 {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let sector = hue.floor();
+    let f = hue - sector;
+
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - saturation * f);
+    let t = value * (1.0 - saturation * (1.0 - f));
+
+    let (r, g, b) = match sector as u8 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+
+    RGB8 {
+        r: (r * 255.0) as u8,
+        g: (g * 255.0) as u8,
+        b: (b * 255.0) as u8,
+    }
+}
+
+/// Blends from `from` at 0 to `to` at 1.
+pub fn lerp(from: RGB8, to: RGB8, amount: f32) -> RGB8 {
+    let amount = amount.clamp(0.0, 1.0);
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount) as u8;
+
+    RGB8 {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+    }
+}
+
+/// Scales the brightness of a color by `amount` between 0 and 1.
+pub fn scale(color: RGB8, amount: f32) -> RGB8 {
+    lerp(RGB8::default(), color, amount)
+}
+
+/// Black through red, orange and yellow to white as heat goes from 0 to 1
+fn heat_color(heat: f32) -> RGB8 {
+    let heat = heat.clamp(0.0, 1.0) * 3.0;
+    let ramp = |value: f32| (value.clamp(0.0, 1.0) * 255.0) as u8;
+
+    RGB8 {
+        r: ramp(heat),
+        g: ramp(heat - 1.0),
+        b: ramp(heat - 2.0),
+    }
+}
+
+/// Returns the seconds since the previous frame, which is 0 for the first frame
+fn time_step(last_time: &mut Option<f64>, time: f64) -> f32 {
+    let elapsed = last_time.map(|last_time| time - last_time).unwrap_or(0.0);
+    *last_time = Some(time);
+
+    elapsed.max(0.0) as f32
+}
+
+/// Smoothly interpolated random values between 0 and 1 on a 2D grid
+fn value_noise(seed: u64, x: f32, y: f32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (fx, fy) = (smooth(x - x0), smooth(y - y0));
+
+    let corner = |dx: f32, dy: f32| {
+        let hash = ((x0 + dx) as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ ((y0 + dy) as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ seed;
+        Rng::new(hash).next_f32()
+    };
+
+    let top = corner(0.0, 0.0) + (corner(1.0, 0.0) - corner(0.0, 0.0)) * fx;
+    let bottom = corner(0.0, 1.0) + (corner(1.0, 1.0) - corner(0.0, 1.0)) * fx;
+
+    top + (bottom - top) * fy
+}
+
+/// A small xorshift random number generator, so that random patterns are repeatable for a seed
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at 0 and produces similar sequences for similar seeds
+        Self(seed.wrapping_mul(0x2545_F491_4F6C_DD1D) | 1)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A random value from 0 up to but not including 1
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn next_below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max.max(1) as u64) as usize
+    }
+}
@@ -0,0 +1,106 @@
+use std::net::SocketAddr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use log::warn;
+
+use crate::frame::Frame;
+use crate::patterns::{scale, Pattern};
+use crate::sender::Sender;
+
+/// A receiver and the pattern rendered for it.
+pub struct Target {
+    pub addr: SocketAddr,
+    pub frame: Frame,
+    pub pattern: Box<dyn Pattern>,
+}
+
+/// Renders patterns and sends them to their receivers at a fixed frame rate.
+pub struct RenderLoop {
+    pub sender: Sender,
+    pub targets: Vec<Target>,
+    pub fps: u32,
+    /// Scales every color, from 0 to 1
+    pub brightness: f32,
+}
+
+impl RenderLoop {
+    pub fn new(sender: Sender, fps: u32) -> Self {
+        Self {
+            sender,
+            targets: Vec::new(),
+            fps,
+            brightness: 1.0,
+        }
+    }
+
+    pub fn add_target(
+        &mut self,
+        addr: SocketAddr,
+        strip_lengths: &[usize],
+        pattern: Box<dyn Pattern>,
+    ) -> &mut Self {
+        self.targets.push(Target {
+            addr,
+            frame: Frame::new(strip_lengths),
+            pattern,
+        });
+        self
+    }
+
+    /// Renders and sends one frame to every target.
+    pub fn render(&mut self, time: f64) -> Result<()> {
+        for target in self.targets.iter_mut() {
+            target.pattern.render(time, &mut target.frame);
+
+            if self.brightness < 1.0 {
+                let brightness = self.brightness.max(0.0);
+                target
+                    .frame
+                    .leds_mut()
+                    .for_each(|led| *led = scale(*led, brightness));
+            }
+
+            self.sender.send(&target.frame, target.addr)?;
+        }
+
+        Ok(())
+    }
+
+    /// Renders frames until `duration` has passed, or forever if `duration` is `None`.
+    ///
+    /// Frames are scheduled from the start of the loop so that slow frames don't make the
+    /// animation drift. If rendering falls more than a frame behind the missed frames are skipped.
+    pub fn run(&mut self, duration: Option<Duration>) -> Result<()> {
+        let frame_interval = Duration::from_secs_f64(1.0 / self.fps.max(1) as f64);
+        let started_at = Instant::now();
+        let mut next_frame_at = started_at;
+
+        loop {
+            let time = next_frame_at - started_at;
+            if duration.map(|duration| time >= duration).unwrap_or(false) {
+                return Ok(());
+            }
+
+            self.render(time.as_secs_f64())?;
+
+            next_frame_at += frame_interval;
+
+            let now = Instant::now();
+            if now > next_frame_at + frame_interval {
+                let behind = now - next_frame_at;
+                let skipped = (behind.as_secs_f64() / frame_interval.as_secs_f64()) as u32;
+                warn!(
+                    "Rendering is {:?} behind, skipping {} frames",
+                    behind, skipped
+                );
+                next_frame_at += frame_interval * skipped;
+            }
+
+            if let Some(delay) = next_frame_at.checked_duration_since(Instant::now()) {
+                thread::sleep(delay);
+            }
+        }
+    }
+}
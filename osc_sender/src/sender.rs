@@ -0,0 +1,84 @@
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use anyhow::{Context, Result};
+use log::trace;
+use rosc::OscPacket;
+
+use crate::encode::{encode_packet, Encoding, DEFAULT_MAX_PACKET_SIZE};
+use crate::frame::Frame;
+
+/// Sends frames to one or more receivers over UDP.
+#[derive(Debug)]
+pub struct Sender {
+    socket: UdpSocket,
+    pub encoding: Encoding,
+    /// Frames are split so that no packet exceeds this many bytes
+    pub max_packet_size: usize,
+}
+
+impl Sender {
+    /// Binds an IPv4 socket on a random port.
+    pub fn new() -> Result<Self> {
+        Self::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+    }
+
+    pub fn bind(addr: SocketAddr) -> Result<Self> {
+        let socket =
+            UdpSocket::bind(addr).with_context(|| format!("Unable to bind to {}", addr))?;
+
+        Ok(Self {
+            socket,
+            encoding: Encoding::default(),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+        })
+    }
+
+    pub fn with_encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn with_max_packet_size(mut self, max_packet_size: usize) -> Self {
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Sends a frame to one receiver.
+    pub fn send(&self, frame: &Frame, to: SocketAddr) -> Result<()> {
+        self.send_to_all(frame, [to].iter())
+    }
+
+    /// Sends the same frame to every receiver, encoding it only once.
+    pub fn send_to_all<'a, I>(&self, frame: &Frame, receivers: I) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a SocketAddr>,
+    {
+        let payloads = frame.encode(self.encoding, self.max_packet_size)?;
+
+        for to in receivers {
+            for payload in payloads.iter() {
+                self.send_bytes(payload, *to)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn send_packet(&self, packet: &OscPacket, to: SocketAddr) -> Result<()> {
+        self.send_bytes(&encode_packet(packet)?, to)
+    }
+
+    fn send_bytes(&self, payload: &[u8], to: SocketAddr) -> Result<()> {
+        trace!("Sending {} bytes to {}", payload.len(), to);
+
+        self.socket
+            .send_to(payload, to)
+            .with_context(|| format!("Unable to send to {}", to))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::mock::RecordingSmartLed;
+use osc_sender::{Encoding, Frame, RGB8};
+use rosc::OscPacket;
+
+fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8 { r, g, b }
+}
+
+/// A frame where every LED has a different color
+fn gradient(strip_lengths: &[usize]) -> Frame {
+    let mut frame = Frame::new(strip_lengths);
+    for (i, led) in frame.leds_mut().enumerate() {
+        *led = rgb(i as u8, (i >> 8) as u8, 7);
+    }
+    frame
+}
+
+/// Decodes each payload and displays it on strips of the frame's lengths, returning the last frame
+/// written to each strip and how many times the strips were written to
+fn receive(frame: &Frame, payloads: &[Vec<u8>]) -> (Vec<Vec<RGB8>>, usize) {
+    let mut smart_leds = vec![RecordingSmartLed::new(); frame.strips().len()];
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .zip(frame.strip_lengths())
+        .map(|(smart_led, length)| LedStrip::with_length(smart_led, length))
+        .collect::<Vec<_>>();
+
+    for payload in payloads {
+        let (_, packet) = rosc::decoder::decode_udp(payload).unwrap();
+        LedStrip::update(&mut led_strips, packet).unwrap();
+    }
+
+    drop(led_strips);
+    let writes = smart_leds[0].frames.len();
+    let strips = smart_leds
+        .iter()
+        .map(|smart_led| smart_led.last_frame().unwrap().to_vec())
+        .collect();
+
+    (strips, writes)
+}
+
+#[test]
+fn small_frames_are_sent_as_one_message_per_strip() {
+    let frame = gradient(&[3, 2]);
+
+    let packets = frame.to_packets(Encoding::Messages, 1472).unwrap();
+
+    let addrs = packets
+        .iter()
+        .map(|packet| match packet {
+            OscPacket::Message(message) => message.addr.as_str(),
+            OscPacket::Bundle(_) => panic!("Expected a message"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(addrs, vec!["/led_strips/0", "/led_strips/1"]);
+}
+
+#[test]
+fn large_strips_are_split_to_fit_the_max_packet_size() {
+    let frame = gradient(&[450, 144]);
+
+    let payloads = frame.encode(Encoding::Messages, 256).unwrap();
+
+    assert!(payloads.len() > 2);
+    assert!(payloads.iter().all(|payload| payload.len() <= 256));
+    assert_eq!(receive(&frame, &payloads).0, frame.strips());
+}
+
+#[test]
+fn bundles_are_packed_to_fit_the_max_packet_size() {
+    let frame = gradient(&[450, 144]);
+
+    let payloads = frame.encode(Encoding::Bundles, 1472).unwrap();
+
+    // 594 LEDs take 5 bytes each
+    assert_eq!(payloads.len(), 3);
+    assert!(payloads.iter().all(|payload| payload.len() <= 1472));
+    assert_eq!(receive(&frame, &payloads), (frame.strips().to_vec(), 3));
+}
+
+#[test]
+fn frames_that_fit_in_one_bundle_are_displayed_at_once() {
+    let frame = gradient(&[100, 100]);
+
+    let payloads = frame.encode(Encoding::Bundles, 1472).unwrap();
+
+    assert_eq!(payloads.len(), 1);
+    assert_eq!(receive(&frame, &payloads), (frame.strips().to_vec(), 1));
+}
+
+#[test]
+fn tiny_max_packet_sizes_are_rejected() {
+    assert!(gradient(&[1]).encode(Encoding::Messages, 16).is_err());
+}
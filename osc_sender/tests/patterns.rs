@@ -0,0 +1,98 @@
+use osc_sender::patterns::{hsv, Chase, Gradient, PATTERN_KINDS};
+use osc_sender::{Frame, Pattern, PatternKind, PatternParams, RGB8};
+
+const BLACK: RGB8 = RGB8 { r: 0, g: 0, b: 0 };
+const WHITE: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+
+#[test]
+fn every_pattern_renders_strips_of_any_length() {
+    for kind in PATTERN_KINDS {
+        let mut pattern = kind.build(&PatternParams::default());
+        let mut frame = Frame::new(&[0, 1, 450]);
+
+        for i in 0..120 {
+            pattern.render(i as f64 / 30.0, &mut frame);
+        }
+
+        assert_eq!(frame.strip_lengths(), vec![0, 1, 450], "{}", kind);
+    }
+}
+
+#[test]
+fn patterns_are_parsed_by_name() {
+    assert_eq!(
+        "Rainbow".parse::<PatternKind>().unwrap(),
+        PatternKind::Rainbow
+    );
+    assert!("strobe".parse::<PatternKind>().is_err());
+}
+
+#[test]
+fn random_patterns_are_repeatable_for_a_seed() {
+    let render = |kind: PatternKind| {
+        let mut pattern = kind.build(&PatternParams::default());
+        let mut frame = Frame::new(&[100]);
+        for i in 0..30 {
+            pattern.render(i as f64 / 30.0, &mut frame);
+        }
+        frame
+    };
+
+    for kind in [PatternKind::Twinkle, PatternKind::Fire, PatternKind::Noise] {
+        let frame = render(kind);
+
+        assert_eq!(frame, render(kind), "{}", kind);
+        assert!(frame.leds().any(|led| *led != BLACK), "{}", kind);
+    }
+}
+
+#[test]
+fn chase_moves_blocks_along_the_strip() {
+    let mut chase = Chase {
+        color: WHITE,
+        background: BLACK,
+        spacing: 4.0,
+        width: 1.0,
+        speed: 1.0,
+    };
+    let mut frame = Frame::new(&[8]);
+
+    chase.render(0.0, &mut frame);
+    assert_eq!(
+        frame.strip(0),
+        &[WHITE, BLACK, BLACK, BLACK, WHITE, BLACK, BLACK, BLACK]
+    );
+
+    chase.render(1.0, &mut frame);
+    assert_eq!(
+        frame.strip(0),
+        &[BLACK, WHITE, BLACK, BLACK, BLACK, WHITE, BLACK, BLACK]
+    );
+}
+
+#[test]
+fn gradient_spans_each_strip() {
+    let mut gradient = Gradient {
+        from: BLACK,
+        to: WHITE,
+        speed: 0.0,
+    };
+    let mut frame = Frame::new(&[5, 3]);
+
+    gradient.render(0.0, &mut frame);
+
+    for strip in frame.strips() {
+        assert_eq!(strip.first(), Some(&BLACK));
+        assert_eq!(strip.last(), Some(&WHITE));
+    }
+}
+
+#[test]
+fn hsv_wraps_the_hue() {
+    assert_eq!(hsv(0.0, 1.0, 1.0), RGB8 { r: 255, g: 0, b: 0 });
+    assert_eq!(hsv(1.0 / 3.0, 1.0, 1.0), hsv(4.0 / 3.0, 1.0, 1.0));
+}
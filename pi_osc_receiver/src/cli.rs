@@ -0,0 +1,183 @@
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::Parser;
+use log::LevelFilter;
+
+use crate::config::{Config, CONFIG_ENV_VAR, DEFAULT_CONFIG_PATH};
+use crate::output::{self, LedType, Output, MHZ};
+
+/// Receives OSC color arrays over UDP and displays them on APA102, SK9822 or WS2812B LED strips.
+///
+/// Command line flags override the matching settings in the config file.
+#[derive(Debug, Clone, Parser)]
+#[command(name = "pi_osc_receiver", version)]
+pub struct Cli {
+    /// Path to the TOML config file [default: /etc/pi_osc_receiver.toml]
+    #[arg(short, long, env = CONFIG_ENV_VAR)]
+    pub config: Option<PathBuf>,
+
+    /// UDP port to listen for OSC packets on
+    #[arg(short, long)]
+    pub port: Option<u16>,
+
+    /// Address to listen for OSC packets on
+    #[arg(short, long)]
+    pub address: Option<IpAddr>,
+
+    /// Serve a live browser preview of the strips on this port
+    #[arg(long, value_name = "PORT")]
+    pub preview_port: Option<u16>,
+
+    /// Record every displayed frame to show files in this directory
+    #[arg(long, value_name = "DIRECTORY")]
+    pub record: Option<PathBuf>,
+
+    /// Play a recorded show file on the strips
+    #[arg(long, value_name = "FILE")]
+    pub play: Option<PathBuf>,
+
+    /// Show playback speed multiplier, eg. 0.5 for half speed
+    #[arg(long, value_parser = parse_speed)]
+    pub speed: Option<f32>,
+
+    /// Stop at the end of the show instead of restarting it
+    #[arg(long)]
+    pub no_loop: bool,
+
+    /// Replaces the configured outputs. Formatted as
+    /// `<bus>:<slave_select>[:<clock_mhz>[:<led_type>[:<strip_length>]]]`, may be repeated
+    #[arg(short, long = "output", value_name = "OUTPUT")]
+    pub outputs: Vec<Output>,
+
+    /// SPI bus (0 - 6) of every output
+    #[arg(long, value_parser = output::parse_bus)]
+    pub spi_bus: Option<rppal::spi::Bus>,
+
+    /// SPI slave select (0 - 2) of every output
+    #[arg(long, value_parser = output::parse_slave_select)]
+    pub spi_slave_select: Option<rppal::spi::SlaveSelect>,
+
+    /// SPI clock speed of every output, in MHz
+    #[arg(long, value_name = "MHZ", value_parser = clap::value_parser!(u32).range(1..=32))]
+    pub spi_clock: Option<u32>,
+
+    /// LED chip used by every output
+    #[arg(long)]
+    pub led_type: Option<LedType>,
+
+    /// Number of LEDs on each output, in output order. A single length applies to every output
+    #[arg(short = 'l', long = "strip-length", value_delimiter = ',')]
+    pub strip_lengths: Vec<usize>,
+
+    /// Log level (off, error, warn, info, debug or trace). Defaults to RUST_LOG
+    #[arg(long)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Validate the config and command line flags, print the resulting config and exit
+    #[arg(long)]
+    pub check_config: bool,
+
+    /// Cycle the strips through a test pattern instead of listening for OSC packets
+    #[arg(long)]
+    pub test_pattern: bool,
+}
+
+impl Cli {
+    pub fn init_logger(&self) {
+        let mut builder = pretty_env_logger::formatted_builder();
+
+        if let Ok(filters) = std::env::var("RUST_LOG") {
+            builder.parse_filters(&filters);
+        }
+        if let Some(log_level) = self.log_level {
+            builder.filter_level(log_level);
+        }
+
+        builder.init();
+    }
+
+    /// The config file that is loaded.
+    pub fn config_path(&self) -> &Path {
+        self.config
+            .as_deref()
+            .unwrap_or_else(|| Path::new(DEFAULT_CONFIG_PATH))
+    }
+
+    /// Loads the config file and applies the command line overrides to it.
+    pub fn load_config(&self) -> Result<Config> {
+        let mut config = Config::load(self.config.as_deref())?;
+
+        if let Some(port) = self.port {
+            config.listen.port = port;
+        }
+        if let Some(address) = self.address {
+            config.listen.address = address;
+        }
+        if let Some(preview_port) = self.preview_port {
+            config.preview.enabled = true;
+            config.preview.port = preview_port;
+        }
+        if let Some(directory) = &self.record {
+            config.recording.enabled = true;
+            config.recording.directory = directory.clone();
+        }
+        if let Some(file) = &self.play {
+            config.playback.file = Some(file.clone());
+        }
+        if let Some(speed) = self.speed {
+            config.playback.speed = speed;
+        }
+        if self.no_loop {
+            config.playback.looping = false;
+        }
+        if !self.outputs.is_empty() {
+            config.outputs = self.outputs.clone();
+        }
+
+        match self.strip_lengths[..] {
+            [] => {}
+            [strip_length] => config
+                .outputs
+                .iter_mut()
+                .for_each(|output| output.strip_length = strip_length),
+            ref strip_lengths if strip_lengths.len() == config.outputs.len() => config
+                .outputs
+                .iter_mut()
+                .zip(strip_lengths.iter())
+                .for_each(|(output, strip_length)| output.strip_length = *strip_length),
+            ref strip_lengths => bail!(
+                "--strip-length: Received {} lengths for {} outputs",
+                strip_lengths.len(),
+                config.outputs.len()
+            ),
+        }
+
+        for output in config.outputs.iter_mut() {
+            if let Some(bus) = self.spi_bus {
+                output.bus = bus;
+            }
+            if let Some(slave_select) = self.spi_slave_select {
+                output.slave_select = slave_select;
+            }
+            if let Some(spi_clock) = self.spi_clock {
+                output.clock_speed = Some(spi_clock * MHZ);
+            }
+            if let Some(led_type) = self.led_type {
+                output.led_type = led_type;
+            }
+        }
+
+        config.validate()?;
+
+        Ok(config)
+    }
+}
+
+fn parse_speed(speed: &str) -> Result<f32> {
+    match speed.parse::<f32>() {
+        Ok(speed) if speed.is_finite() && speed > 0.0 => Ok(speed),
+        _ => bail!("Expected a speed greater than 0, got {:?}", speed),
+    }
+}
@@ -0,0 +1,532 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use osc_receiver::failsafe::{self, FailsafeAction};
+use osc_receiver::pattern::{PatternKind, PatternParams};
+use osc_receiver::preview::DEFAULT_PREVIEW_FPS;
+use osc_receiver::scene;
+use rosc::{OscMessage, OscPacket};
+use serde::Deserialize;
+
+use crate::output::{LedType, Output};
+use crate::ws2812;
+
+/// Environment variable overriding the location of the config file.
+pub const CONFIG_ENV_VAR: &str = "RAVES_CONFIG";
+
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/pi_osc_receiver.toml";
+
+/// The receiver's configuration. See `pi_osc_receiver.example.toml` for a documented example.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listen: Listen,
+    pub protocols: Protocols,
+    pub preview: Preview,
+    pub recording: Recording,
+    pub playback: Playback,
+    pub mdns: Mdns,
+    pub oscquery: OscQuery,
+    pub failsafe: Failsafe,
+    pub scenes: Scenes,
+    pub patterns: Patterns,
+    pub outputs: Vec<Output>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Listen {
+    pub address: IpAddr,
+    pub port: u16,
+}
+
+/// The OSC address spaces the receiver responds to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Protocols {
+    /// Color arrays sent to `/led_strips/{n}`
+    pub led_strips: bool,
+    /// Individual channels sent to `/{universe}/dmx/{channel}`
+    pub dmx: bool,
+    /// Reply to the sender with `/error <address> <code> <reason>` when a message is rejected
+    pub error_replies: bool,
+}
+
+/// The browser preview of the LED strips, served over HTTP and WebSockets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preview {
+    pub enabled: bool,
+    pub port: u16,
+    /// The maximum number of frames per second sent to each browser
+    pub fps: u32,
+}
+
+/// Records every displayed frame to show files.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Recording {
+    pub enabled: bool,
+    pub directory: PathBuf,
+    /// A new file is started once the current file reaches this size
+    pub max_file_size_mb: u64,
+    /// The oldest files are deleted once there are more than this many files
+    pub max_files: usize,
+}
+
+/// Plays a recorded show file on the strips without a controller.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Playback {
+    /// The show file to play. Playback is disabled when this is not set
+    pub file: Option<PathBuf>,
+    /// Start playing as soon as the receiver starts instead of waiting for `/show/play`
+    pub autoplay: bool,
+    /// Restart the show once it ends
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    /// The playback speed multiplier
+    pub speed: f32,
+}
+
+/// Advertises the receiver as an `_osc._udp` service with mDNS / DNS-SD so that controllers can
+/// find it without knowing its IP address.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Mdns {
+    pub enabled: bool,
+    /// The service instance name. Defaults to the Pi's hostname
+    pub name: Option<String>,
+}
+
+/// Describes the receiver's OSC addresses to controller apps over OSCQuery (HTTP and WebSockets).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OscQuery {
+    pub enabled: bool,
+    pub port: u16,
+}
+
+/// What the strips show when no frames have arrived for a while, eg. because the controller crashed.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Failsafe {
+    /// Seconds without frames before the failsafe engages. Zero disables the failsafe
+    pub timeout_seconds: f64,
+    /// `hold`, `black`, a comma separated list of `#rrggbb` colors repeated along the strips,
+    /// `pattern:{name}`, `default_scene` or `preset:{name}`
+    pub action: String,
+    /// Seconds to fade from the last frame
+    pub fade_seconds: f64,
+}
+
+/// Where the default scene saved with `/scene/save_default` and presets are stored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenes {
+    pub directory: PathBuf,
+}
+
+/// The built in patterns the receiver runs by itself until frames arrive.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Patterns {
+    /// The pattern started as soon as the receiver starts, if any
+    pub startup: Option<String>,
+    /// The parameters every pattern starts with, until they are changed over OSC
+    pub speed: f32,
+    pub scale: f32,
+    /// A `#rrggbb` color
+    pub color: String,
+    pub density: f32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            listen: Listen::default(),
+            protocols: Protocols::default(),
+            preview: Preview::default(),
+            recording: Recording::default(),
+            playback: Playback::default(),
+            mdns: Mdns::default(),
+            oscquery: OscQuery::default(),
+            failsafe: Failsafe::default(),
+            scenes: Scenes::default(),
+            patterns: Patterns::default(),
+            outputs: vec![Output::default()],
+        }
+    }
+}
+
+impl Default for Listen {
+    fn default() -> Self {
+        Self {
+            address: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8001,
+        }
+    }
+}
+
+impl Default for Protocols {
+    fn default() -> Self {
+        Self {
+            led_strips: true,
+            dmx: true,
+            error_replies: false,
+        }
+    }
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8080,
+            fps: DEFAULT_PREVIEW_FPS,
+        }
+    }
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: PathBuf::from("/var/lib/pi_osc_receiver/shows"),
+            max_file_size_mb: 64,
+            max_files: 16,
+        }
+    }
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            file: None,
+            autoplay: true,
+            looping: true,
+            speed: 1.0,
+        }
+    }
+}
+
+impl Default for Mdns {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            name: None,
+        }
+    }
+}
+
+impl Default for OscQuery {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            port: 8002,
+        }
+    }
+}
+
+impl Default for Failsafe {
+    fn default() -> Self {
+        Self {
+            timeout_seconds: 10.0,
+            action: "hold".to_string(),
+            fade_seconds: 2.0,
+        }
+    }
+}
+
+impl Default for Scenes {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("/var/lib/pi_osc_receiver/scenes"),
+        }
+    }
+}
+
+impl Default for Patterns {
+    fn default() -> Self {
+        Self {
+            startup: None,
+            speed: 1.0,
+            scale: 60.0,
+            color: "#ff8000".to_string(),
+            density: 0.5,
+        }
+    }
+}
+
+impl Failsafe {
+    pub fn action(&self) -> Result<FailsafeAction> {
+        FailsafeAction::parse(&self.action)
+    }
+
+    pub fn build(&self) -> Result<failsafe::Failsafe> {
+        Ok(failsafe::Failsafe::new(
+            (self.timeout_seconds * 1000.0) as u64,
+            (self.fade_seconds * 1000.0) as u64,
+            self.action()?,
+        ))
+    }
+}
+
+impl Scenes {
+    /// The scene shown as soon as the receiver starts.
+    pub fn default_scene_path(&self) -> PathBuf {
+        self.directory.join(format!("default.{}", scene::EXTENSION))
+    }
+
+    /// Presets stored with `/preset/{name}/store`, one file per preset.
+    pub fn presets_directory(&self) -> PathBuf {
+        self.directory.join("presets")
+    }
+}
+
+impl Patterns {
+    pub fn startup(&self) -> Result<Option<PatternKind>> {
+        self.startup
+            .as_deref()
+            .map(|name| {
+                PatternKind::parse(name).with_context(|| {
+                    format!(
+                        "Unknown pattern {:?}. Must be one of: rainbow, breathing, chase, sparkle",
+                        name
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    pub fn params(&self) -> Result<PatternParams> {
+        Ok(PatternParams {
+            speed: self.speed,
+            scale: self.scale,
+            color: failsafe::parse_hex_color(&self.color)?,
+            density: self.density,
+        })
+    }
+}
+
+impl Recording {
+    pub fn max_file_size(&self) -> u64 {
+        self.max_file_size_mb * 1024 * 1024
+    }
+}
+
+impl Listen {
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.address, self.port)
+    }
+}
+
+impl Protocols {
+    /// The address of the first message in the packet that belongs to a disabled protocol.
+    pub fn disabled_addr<'a>(&self, packet: &'a OscPacket) -> Option<&'a str> {
+        match packet {
+            OscPacket::Message(OscMessage { addr, .. }) => {
+                let enabled = if addr.trim_start_matches('/').starts_with("led_strips") {
+                    self.led_strips
+                } else if addr.contains("/dmx/") {
+                    self.dmx
+                } else {
+                    true
+                };
+
+                if enabled {
+                    None
+                } else {
+                    Some(addr.as_str())
+                }
+            }
+            OscPacket::Bundle(bundle) => bundle
+                .content
+                .iter()
+                .find_map(|packet| self.disabled_addr(packet)),
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config file at `path`, or at [`DEFAULT_CONFIG_PATH`] if no path was given.
+    ///
+    /// If the default config file does not exist the default config is used instead, with the
+    /// outputs taken from `RAVES_OUTPUTS` if it is set. A config file that was asked for explicitly
+    /// must exist.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let path = match path {
+            Some(path) => path,
+            None if !Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                return Ok(Self {
+                    outputs: Output::from_env()?,
+                    ..Self::default()
+                });
+            }
+            None => Path::new(DEFAULT_CONFIG_PATH),
+        };
+
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read config file {:?}", path))?;
+
+        Self::parse(&contents).with_context(|| format!("Invalid config file {:?}", path))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents)?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.preview.fps == 0 {
+            bail!("preview.fps: Must be greater than 0");
+        }
+        if self.recording.max_file_size_mb == 0 {
+            bail!("recording.max_file_size_mb: Must be greater than 0");
+        }
+        if self.recording.max_files == 0 {
+            bail!("recording.max_files: Must be greater than 0");
+        }
+        if !self.playback.speed.is_finite() || self.playback.speed <= 0.0 {
+            bail!("playback.speed: Must be a finite number greater than 0");
+        }
+        if matches!(&self.mdns.name, Some(name) if name.is_empty() || name.len() > 63) {
+            bail!("mdns.name: Must be between 1 and 63 characters long");
+        }
+        if self.failsafe.timeout_seconds.is_nan() || self.failsafe.timeout_seconds < 0.0 {
+            bail!("failsafe.timeout_seconds: Must be 0 or more");
+        }
+        if self.failsafe.fade_seconds.is_nan() || self.failsafe.fade_seconds < 0.0 {
+            bail!("failsafe.fade_seconds: Must be 0 or more");
+        }
+        self.failsafe.action().context("failsafe.action")?;
+        self.patterns.startup().context("patterns.startup")?;
+        if self.patterns.speed.is_nan() || self.patterns.speed < 0.0 {
+            bail!("patterns.speed: Must be 0 or more");
+        }
+        if self.patterns.scale.is_nan() || self.patterns.scale < 1.0 {
+            bail!("patterns.scale: Must be 1 or more");
+        }
+        if !(0.0..=1.0).contains(&self.patterns.density) {
+            bail!("patterns.density: Must be between 0 and 1");
+        }
+        self.patterns.params().context("patterns.color")?;
+        if self.outputs.is_empty() {
+            bail!("outputs: At least one output must be configured");
+        }
+
+        let mut spi_devices = HashSet::new();
+
+        for (i, output) in self.outputs.iter().enumerate() {
+            if output.strip_length == 0 {
+                bail!("outputs[{}].strip_length: Must be greater than 0", i);
+            }
+            if output.led_type == LedType::Ws2812b
+                && !(ws2812::MIN_CLOCK_SPEED..=ws2812::MAX_CLOCK_SPEED)
+                    .contains(&output.clock_speed())
+            {
+                bail!(
+                    "outputs[{}].clock_mhz: WS2812B strips need an SPI clock between 2 and 3.8 MHz",
+                    i
+                );
+            }
+            if !spi_devices.insert((output.bus as u8, output.slave_select as u8)) {
+                bail!(
+                    "outputs[{}]: SPI{}.{} is used by more than one output",
+                    i,
+                    output.bus as u8,
+                    output.slave_select as u8,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Listen: {}", self.listen.socket_addr())?;
+        writeln!(
+            f,
+            "Protocols: led_strips = {}, dmx = {}, error_replies = {}",
+            self.protocols.led_strips, self.protocols.dmx, self.protocols.error_replies
+        )?;
+        if self.preview.enabled {
+            writeln!(
+                f,
+                "Preview: port {} at {} fps",
+                self.preview.port, self.preview.fps
+            )?;
+        } else {
+            writeln!(f, "Preview: disabled")?;
+        }
+        if self.recording.enabled {
+            writeln!(
+                f,
+                "Recording: {:?} ({} x {} MB files)",
+                self.recording.directory, self.recording.max_files, self.recording.max_file_size_mb
+            )?;
+        } else {
+            writeln!(f, "Recording: disabled")?;
+        }
+        if let Some(file) = &self.playback.file {
+            writeln!(
+                f,
+                "Playback: {:?} (autoplay = {}, loop = {}, speed = {})",
+                file, self.playback.autoplay, self.playback.looping, self.playback.speed
+            )?;
+        } else {
+            writeln!(f, "Playback: disabled")?;
+        }
+        if self.mdns.enabled {
+            match &self.mdns.name {
+                Some(name) => writeln!(f, "mDNS: advertised as {:?}", name)?,
+                None => writeln!(f, "mDNS: advertised as the hostname")?,
+            }
+        } else {
+            writeln!(f, "mDNS: disabled")?;
+        }
+        if self.oscquery.enabled {
+            writeln!(f, "OSCQuery: port {}", self.oscquery.port)?;
+        } else {
+            writeln!(f, "OSCQuery: disabled")?;
+        }
+        if self.failsafe.timeout_seconds > 0.0 && self.failsafe.action != "hold" {
+            writeln!(
+                f,
+                "Failsafe: {} after {}s without frames (fading over {}s)",
+                self.failsafe.action, self.failsafe.timeout_seconds, self.failsafe.fade_seconds
+            )?;
+        } else {
+            writeln!(f, "Failsafe: hold the last frame")?;
+        }
+        writeln!(f, "Scenes: {:?}", self.scenes.directory)?;
+        match &self.patterns.startup {
+            Some(startup) => writeln!(f, "Patterns: {} on startup", startup)?,
+            None => writeln!(f, "Patterns: none on startup")?,
+        }
+        writeln!(f, "Outputs:")?;
+        for output in self.outputs.iter() {
+            writeln!(f, "  - {}", output)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_config_files_must_exist() {
+        assert!(Config::load(Some(Path::new("/nonexistent/pi_osc_receiver.toml"))).is_err());
+    }
+}
@@ -1,62 +1,564 @@
-use anyhow::{Context, Result};
-use log::info;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use log::{error, info, warn};
 
-use apa102_spi::Apa102;
 use local_ip_address::local_ip;
 
-use osc_receiver::led_strip::{LedStrip, RGB8SmartLedsWrite};
+use osc_receiver::error_reply::{ErrorCode, MessageError};
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::oscquery::{HostInfo, OscQuery};
+use osc_receiver::pattern::Patterns;
+use osc_receiver::preset::{Crossfade, PresetCommand, Presets};
+use osc_receiver::preview::Preview;
+use osc_receiver::query::{QueryHandler, ReceiverInfo, Status, StripInfo};
+use osc_receiver::scene::{self, Scene};
+use osc_receiver::show_player::ShowPlayer;
+use rosc::OscPacket;
 // use smart_leds::RGB8;
 
-const MHZ: u32 = 1_000_000u32;
+mod cli;
+mod config;
+mod mdns;
+mod namespace;
+mod output;
+mod recorder;
+mod scenes;
+mod test_pattern;
+mod ws2812;
 
-const PORT: u16 = 8001;
+use cli::Cli;
+use config::{Config, Playback};
+use mdns::Advertisement;
+use recorder::Recorder;
+
+// How often the main loop stops waiting for packets to check for a SIGHUP
+const RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(250);
 
 pub fn main() -> Result<()> {
-    pretty_env_logger::init();
+    let cli = Cli::parse();
+    cli.init_logger();
+
+    let config = cli.load_config()?;
+
+    if cli.check_config {
+        print!("{}", config);
+        println!("\nConfig OK");
+        return Ok(());
+    }
+
+    if cli.test_pattern {
+        return test_pattern::run(&config);
+    }
 
+    run(&cli, config)
+}
+
+fn run(cli: &Cli, mut config: Config) -> Result<()> {
     // Print the local ip address
-    if let Ok(ip_address) = local_ip() {
-        println!("Listening for OSC packets at {}:{}\n", ip_address, PORT);
+    if !config.listen.address.is_unspecified() {
+        println!(
+            "Listening for OSC packets at {}\n",
+            config.listen.socket_addr()
+        );
+    } else if let Ok(ip_address) = local_ip() {
+        println!(
+            "Listening for OSC packets at {}:{}\n",
+            ip_address, config.listen.port
+        );
     } else {
-        println!("Listening for OSC packets on port {}\n", PORT);
+        println!("Listening for OSC packets on port {}\n", config.listen.port);
     }
 
-    // Modes: https://en.wikipedia.org/wiki/Serial_Peripheral_Interface_Bus#Clock_polarity_and_phase
-    let spi: Spi = Spi::new(Bus::Spi0, SlaveSelect::Ss0, 12 * MHZ, Mode::Mode0)
-        .context("Unable to connect to SPI. Are you not running on a Raspberry Pi?")?;
-
-    let mut apa102 = Apa102::new(spi);
+    let socket = UdpSocket::bind(config.listen.socket_addr())
+        .with_context(|| format!("Unable to bind to {}", config.listen.socket_addr()))?;
 
-    println!("SPI Connected");
+    // Reload the config file on SIGHUP (eg. `systemctl reload pi_osc_receiver`)
+    let reload = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, Arc::clone(&reload))?;
 
-    let mut smart_leds: Vec<&mut dyn RGB8SmartLedsWrite> = Vec::new();
+    let preview = if config.preview.enabled {
+        let addr = SocketAddr::new(config.listen.address, config.preview.port);
+        Some(Preview::spawn(addr, config.preview.fps)?)
+    } else {
+        None
+    };
 
-    smart_leds.push(&mut apa102);
+    let mut presets = load_presets(&config);
 
-    let mut led_strips = smart_leds
-        .into_iter()
-        .map(LedStrip::new)
-        .collect::<Vec<_>>();
+    let oscquery = if config.oscquery.enabled {
+        let addr = SocketAddr::new(config.listen.address, config.oscquery.port);
+        let host_info = HostInfo {
+            name: mdns::instance_name(&config),
+            osc_port: config.listen.port,
+        };
+        Some(OscQuery::spawn(
+            addr,
+            host_info,
+            namespace::build(&config, &presets),
+        )?)
+    } else {
+        None
+    };
 
-    info!("Starting main loop");
+    let mut advertisement = Advertisement::default();
+    advertise(&mut advertisement, &config);
 
-    use std::net::UdpSocket;
+    let mut recorder = start_recorder(&config)?;
+    let mut player = load_player(&config.playback)?;
+    let mut last_advanced = Instant::now();
 
-    let socket = UdpSocket::bind(format!("0.0.0.0:{}", PORT)).expect("couldn't bind to address");
+    let started_at = Instant::now();
+    let now_ms = || started_at.elapsed().as_millis() as u64;
+    let mut queries = QueryHandler::new(receiver_info(&config), now_ms());
+    let mut failsafe = config.failsafe.build()?;
+    // Shown once the strips are first connected, until a controller sends a frame
+    let mut power_on_scene = scenes::load_default(&config.scenes);
+    failsafe.load_scene(power_on_scene.as_ref(), &presets);
+    let mut crossfade: Option<Crossfade> = None;
+    let mut patterns = Patterns::new(config.patterns.params()?);
+    if let Some(startup) = config.patterns.startup()? {
+        info!("Running the {} pattern", startup);
+        patterns.start(startup, now_ms());
+    }
 
     let mut packet_buf = [0; 65_507];
+    let mut smart_leds = output::connect_all(&config.outputs)?;
+    // Outputs connected by a reload, swapped in once the strips using the old outputs are dropped
+    let mut next_smart_leds = None;
 
     loop {
-        let packet_size = socket.recv(&mut packet_buf).unwrap();
+        if let Some(next_smart_leds) = next_smart_leds.take() {
+            smart_leds = next_smart_leds;
+        }
+        let mut led_strips = output::led_strips(&mut smart_leds, &config.outputs);
 
-        let osc_packet = rosc::decoder::decode_udp(&packet_buf[..packet_size]);
+        if let Some(player) = &player {
+            warn_on_strip_mismatch(player, &led_strips);
+        }
 
-        if let Ok((&[], osc_packet)) = osc_packet {
-            info!("OSC Packet Received");
-            // info!("OSC Packet: {}", osc_packet);
-            LedStrip::update(&mut led_strips, osc_packet);
+        if let Some(scene) = power_on_scene.take() {
+            scene.apply(&mut led_strips);
+            let written = LedStrip::write_all(&mut led_strips);
+            count_frame(&mut queries.status, written, now_ms());
+            publish(&led_strips, &preview, &mut recorder);
         }
+
+        info!("Starting main loop");
+
+        loop {
+            if reload.swap(false, Ordering::Relaxed) {
+                info!("SIGHUP received, reloading {:?}", cli.config_path());
+
+                match cli.load_config() {
+                    Ok(mut next_config) => {
+                        if next_config.preview != config.preview {
+                            warn!("Preview settings cannot be changed by a reload. Restart the receiver to apply them");
+                        }
+                        if next_config.oscquery != config.oscquery {
+                            warn!("OSCQuery settings cannot be changed by a reload. Restart the receiver to apply them");
+                        }
+                        if next_config.listen != config.listen {
+                            warn!(
+                                "The listen address cannot be changed by a reload. Restart the receiver to listen on {}",
+                                next_config.listen.socket_addr()
+                            );
+                        }
+                        if next_config.playback != config.playback {
+                            player = load_player(&next_config.playback).unwrap_or_else(|err| {
+                                error!("Unable to load show: {:?}", err);
+                                None
+                            });
+                        }
+                        if next_config.recording != config.recording {
+                            recorder = start_recorder(&next_config).unwrap_or_else(|err| {
+                                error!("Unable to start recording: {:?}", err);
+                                None
+                            });
+                        }
+                        if next_config.outputs != config.outputs {
+                            match output::connect_all(&next_config.outputs) {
+                                Ok(connected) => next_smart_leds = Some(connected),
+                                Err(err) => {
+                                    error!("Keeping the previous outputs: {:?}", err);
+                                    next_config.outputs = config.outputs.clone();
+                                }
+                            }
+                        }
+                        if next_config.failsafe != config.failsafe {
+                            match next_config.failsafe.build() {
+                                Ok(next_failsafe) => failsafe = next_failsafe,
+                                Err(err) => {
+                                    error!("Keeping the previous failsafe: {:?}", err);
+                                    next_config.failsafe = config.failsafe.clone();
+                                }
+                            }
+                        }
+                        if next_config.scenes != config.scenes {
+                            presets = load_presets(&next_config);
+                        }
+                        if next_config.failsafe != config.failsafe
+                            || next_config.scenes != config.scenes
+                        {
+                            let default_scene = scenes::load_default(&next_config.scenes);
+                            failsafe.load_scene(default_scene.as_ref(), &presets);
+                        }
+                        if next_config.patterns != config.patterns {
+                            match next_config.patterns.params() {
+                                Ok(params) => {
+                                    // Keep the running pattern going with the new parameters
+                                    let running = patterns.running();
+                                    patterns = Patterns::new(params);
+                                    if let Some(running) = running {
+                                        patterns.start(running, now_ms());
+                                    }
+                                }
+                                Err(err) => {
+                                    error!("Keeping the previous patterns: {:?}", err);
+                                    next_config.patterns = config.patterns.clone();
+                                }
+                            }
+                        }
+                        let readvertise = next_config.mdns != config.mdns
+                            || next_config.outputs != config.outputs;
+
+                        config = Config {
+                            listen: config.listen,
+                            preview: config.preview,
+                            oscquery: config.oscquery,
+                            ..next_config
+                        };
+
+                        if readvertise {
+                            advertise(&mut advertisement, &config);
+                        }
+                        queries.info = receiver_info(&config);
+                        if let Some(oscquery) = &oscquery {
+                            oscquery.update(namespace::build(&config, &presets));
+                        }
+                        break;
+                    }
+                    Err(err) => {
+                        error!("Keeping the previous config: {:?}", err);
+                    }
+                }
+            }
+
+            let mut read_timeout = RELOAD_POLL_INTERVAL;
+
+            if let Some(player) = &mut player {
+                let now = Instant::now();
+                let elapsed_ms = (now - last_advanced).as_secs_f64() * 1000.0;
+                last_advanced = now;
+
+                if let Some(frame) = player.advance(elapsed_ms) {
+                    let written = ShowPlayer::display(&frame, &mut led_strips);
+                    count_frame(&mut queries.status, written, now_ms());
+                    failsafe.frame_received(now_ms());
+                    crossfade = None;
+                    patterns.stop();
+                    publish(&led_strips, &preview, &mut recorder);
+                }
+
+                if let Some(next_frame_in_ms) = player.next_frame_in_ms() {
+                    read_timeout =
+                        read_timeout.min(Duration::from_secs_f64(next_frame_in_ms / 1000.0));
+                }
+            }
+
+            if failsafe.update(&mut led_strips, &mut patterns, now_ms()) {
+                let written = LedStrip::write_all(&mut led_strips);
+                count_frame(&mut queries.status, written, now_ms());
+                publish(&led_strips, &preview, &mut recorder);
+            }
+            if let Some(next_update_in_ms) = failsafe.next_update_in_ms(now_ms()) {
+                read_timeout = read_timeout.min(Duration::from_millis(next_update_in_ms));
+            }
+
+            if let Some(fade) = &mut crossfade {
+                if fade.update(&mut led_strips, now_ms()) {
+                    let written = LedStrip::write_all(&mut led_strips);
+                    count_frame(&mut queries.status, written, now_ms());
+                    publish(&led_strips, &preview, &mut recorder);
+                }
+                match fade.next_update_in_ms(now_ms()) {
+                    Some(next_update_in_ms) => {
+                        read_timeout = read_timeout.min(Duration::from_millis(next_update_in_ms));
+                    }
+                    None => crossfade = None,
+                }
+            }
+
+            if patterns.update(&mut led_strips, now_ms()) {
+                let written = LedStrip::write_all(&mut led_strips);
+                count_frame(&mut queries.status, written, now_ms());
+                publish(&led_strips, &preview, &mut recorder);
+            }
+            if let Some(next_update_in_ms) = patterns.next_update_in_ms(now_ms()) {
+                read_timeout = read_timeout.min(Duration::from_millis(next_update_in_ms));
+            }
+
+            // A zero timeout is rejected by set_read_timeout
+            socket.set_read_timeout(Some(read_timeout.max(Duration::from_millis(1))))?;
+
+            let (packet_size, sender) = match socket.recv_from(&mut packet_buf) {
+                Ok(received) => received,
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    continue;
+                }
+                Err(err) => return Err(err).context("Unable to receive OSC packet"),
+            };
+
+            let osc_packet = rosc::decoder::decode_udp(&packet_buf[..packet_size]);
+
+            if let Ok((&[], osc_packet)) = osc_packet {
+                info!("OSC Packet Received");
+                // info!("OSC Packet: {}", osc_packet);
+                if let Some(reply) = queries.reply(&osc_packet, now_ms()) {
+                    send_reply(&socket, &reply, sender);
+                    continue;
+                }
+
+                if let Some(player) = &mut player {
+                    if player.handle_packet(&osc_packet) {
+                        continue;
+                    }
+                }
+
+                if scene::is_save_default(&osc_packet) {
+                    let scene = Scene::capture(&led_strips);
+                    match scenes::save_default(&config.scenes, &scene) {
+                        Ok(()) => failsafe.load_scene(Some(&scene), &presets),
+                        Err(err) => {
+                            error!("Unable to save the default scene: {:?}", err);
+                            let reason = format!("Unable to save the default scene: {:#}", err);
+                            let error = MessageError::new(
+                                scene::SAVE_DEFAULT_ADDR,
+                                ErrorCode::StorageFailed,
+                                reason,
+                            );
+                            reply_with_errors(&socket, &config, &[error], sender);
+                        }
+                    }
+                    continue;
+                }
+
+                if let Some(command) = PresetCommand::parse(&osc_packet) {
+                    match command {
+                        Ok(PresetCommand::Store { name }) => {
+                            let scene = Scene::capture(&led_strips);
+                            match scenes::save_preset(&config.scenes, &name, &scene) {
+                                Ok(()) => {
+                                    presets.insert(&name, scene);
+                                    let default_scene = scenes::load_default(&config.scenes);
+                                    failsafe.load_scene(default_scene.as_ref(), &presets);
+                                    if let Some(oscquery) = &oscquery {
+                                        oscquery.update(namespace::build(&config, &presets));
+                                    }
+                                }
+                                Err(err) => {
+                                    error!("Unable to store preset {:?}: {:?}", name, err);
+                                    let reason =
+                                        format!("Unable to store preset {:?}: {:#}", name, err);
+                                    let addr = format!("/preset/{}/store", name);
+                                    let error =
+                                        MessageError::new(&addr, ErrorCode::StorageFailed, reason);
+                                    reply_with_errors(&socket, &config, &[error], sender);
+                                }
+                            }
+                        }
+                        Ok(PresetCommand::Recall { name, fade_ms }) => {
+                            match presets.recall(&name, &led_strips, fade_ms, now_ms()) {
+                                Ok(fade) => {
+                                    info!("Recalling preset {:?} over {}ms", name, fade_ms);
+                                    // The preset replaces the show and stays up until the next frame
+                                    if let Some(player) = &mut player {
+                                        player.pause();
+                                    }
+                                    failsafe.suspend();
+                                    patterns.stop();
+                                    crossfade = Some(fade);
+                                }
+                                Err(error) => {
+                                    warn!("{}", error.reason);
+                                    reply_with_errors(&socket, &config, &[error], sender);
+                                }
+                            }
+                        }
+                        Err(error) => reply_with_errors(&socket, &config, &[error], sender),
+                    }
+                    continue;
+                }
+
+                if let Some(handled) = patterns.handle_packet(&osc_packet, now_ms()) {
+                    match handled {
+                        Ok(()) => {
+                            if let Some(running) = patterns.running() {
+                                info!("Running the {} pattern", running);
+                                // The pattern replaces the show and runs until the next frame
+                                if let Some(player) = &mut player {
+                                    player.pause();
+                                }
+                                failsafe.suspend();
+                                crossfade = None;
+                            }
+                        }
+                        Err(error) => reply_with_errors(&socket, &config, &[error], sender),
+                    }
+                    continue;
+                }
+
+                if let Some(addr) = config.protocols.disabled_addr(&osc_packet) {
+                    let reason = format!("The protocol handling {} is disabled", addr);
+                    let error = MessageError::new(addr, ErrorCode::ProtocolDisabled, reason);
+                    reply_with_errors(&socket, &config, &[error], sender);
+                    continue;
+                }
+
+                let mut errors = Vec::new();
+                // Packets that set no LEDs, eg. ones with unknown addresses, aren't frames
+                if LedStrip::apply(&mut led_strips, osc_packet, &mut errors) {
+                    let written = LedStrip::write_all(&mut led_strips);
+                    count_frame(&mut queries.status, written, now_ms());
+                    failsafe.frame_received(now_ms());
+                    crossfade = None;
+                    patterns.stop();
+                    publish(&led_strips, &preview, &mut recorder);
+                }
+                reply_with_errors(&socket, &config, &errors, sender);
+            } else {
+                queries.status.invalid_packets += 1;
+
+                let reason = match osc_packet {
+                    Ok(_) => "Unexpected bytes after the OSC packet".to_string(),
+                    Err(err) => format!("Invalid OSC packet: {:?}", err),
+                };
+                let error = MessageError::new("", ErrorCode::InvalidPacket, reason);
+                reply_with_errors(&socket, &config, &[error], sender);
+            }
+        }
+    }
+}
+
+fn advertise(advertisement: &mut Advertisement, config: &Config) {
+    if let Err(err) = advertisement.update(config) {
+        error!("Unable to advertise the receiver with mDNS: {:?}", err);
+    }
+}
+
+/// The receiver's version and outputs, reported to `/info`.
+fn receiver_info(config: &Config) -> ReceiverInfo {
+    ReceiverInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        strips: config
+            .outputs
+            .iter()
+            .map(|output| StripInfo::new(output.strip_length, &output.led_type.to_string()))
+            .collect(),
+    }
+}
+
+/// Counts a frame written to the LEDs in the status reported to `/status`.
+fn count_frame(status: &mut Status, written: Result<()>, now_ms: u64) {
+    match written {
+        Ok(()) => status.frame_displayed(now_ms),
+        Err(err) => {
+            status.write_errors += 1;
+            error!("Unable to write to the LEDs: {:?}", err);
+        }
+    }
+}
+
+fn send_reply(socket: &UdpSocket, reply: &OscPacket, to: SocketAddr) {
+    let sent = rosc::encoder::encode(reply)
+        .map_err(|err| anyhow!("{:?}", err))
+        .and_then(|bytes| Ok(socket.send_to(&bytes, to)?));
+
+    if let Err(err) = sent {
+        warn!("Unable to reply to {}: {:?}", to, err);
+    }
+}
+
+/// Sends an `/error` reply for each rejected message if error replies are turned on.
+fn reply_with_errors(socket: &UdpSocket, config: &Config, errors: &[MessageError], to: SocketAddr) {
+    if !config.protocols.error_replies {
+        return;
+    }
+
+    for error in errors {
+        send_reply(socket, &error.reply(), to);
+    }
+}
+
+/// Sends the displayed frame to the browser preview and the recording.
+fn publish(led_strips: &[LedStrip], preview: &Option<Preview>, recorder: &mut Option<Recorder>) {
+    if let Some(preview) = preview {
+        preview.publish(led_strips);
+    }
+    if let Some(recorder) = recorder {
+        if let Err(err) = recorder.record(led_strips) {
+            error!("Unable to record frame: {:?}", err);
+        }
+    }
+}
+
+fn load_player(playback: &Playback) -> Result<Option<ShowPlayer>> {
+    let file = match &playback.file {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+
+    let bytes =
+        std::fs::read(file).with_context(|| format!("Unable to read show file {:?}", file))?;
+    let mut player =
+        ShowPlayer::new(bytes).with_context(|| format!("Invalid show file {:?}", file))?;
+
+    player.looping = playback.looping;
+    player.speed = playback.speed;
+    if playback.autoplay {
+        player.play();
+    }
+
+    info!(
+        "Loaded show {:?}: {} frames, {:.1}s",
+        file,
+        player.frame_count(),
+        player.duration_ms() as f64 / 1000.0
+    );
+
+    Ok(Some(player))
+}
+
+fn load_presets(config: &Config) -> Presets {
+    scenes::load_presets(&config.scenes).unwrap_or_else(|err| {
+        error!("Unable to load presets: {:?}", err);
+        Presets::default()
+    })
+}
+
+fn warn_on_strip_mismatch(player: &ShowPlayer, led_strips: &[LedStrip]) {
+    let strip_lengths = led_strips
+        .iter()
+        .map(|led_strip| led_strip.data.len())
+        .collect::<Vec<_>>();
+
+    if player.header.strip_lengths != strip_lengths {
+        warn!(
+            "The show was recorded on strips of length {:?} but the outputs have lengths {:?}. Extra LEDs are left unchanged",
+            player.header.strip_lengths, strip_lengths
+        );
+    }
+}
+
+fn start_recorder(config: &Config) -> Result<Option<Recorder>> {
+    if config.recording.enabled {
+        Ok(Some(Recorder::new(&config.recording)?))
+    } else {
+        Ok(None)
     }
 }
@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+use osc_receiver::mdns::{self, SERVICE_DOMAIN};
+
+use crate::config::Config;
+
+const FALLBACK_HOSTNAME: &str = "raves-on-rust";
+
+/// The DNS-SD service type of OSCQuery servers
+const OSCQUERY_SERVICE_DOMAIN: &str = "_oscjson._tcp.local.";
+
+/// Advertises the receiver as an `_osc._udp` service on the local network, and its OSCQuery server
+/// as an `_oscjson._tcp` service.
+#[derive(Default)]
+pub struct Advertisement {
+    daemon: Option<ServiceDaemon>,
+    /// The full names of the registered services
+    fullnames: Vec<String>,
+}
+
+impl Advertisement {
+    /// Registers the services described by `config`, replacing any previous registrations so that
+    /// the TXT records follow changes to the outputs.
+    pub fn update(&mut self, config: &Config) -> Result<()> {
+        if let Some(daemon) = &self.daemon {
+            for fullname in self.fullnames.drain(..) {
+                if let Err(err) = daemon.unregister(&fullname) {
+                    warn!("Unable to unregister {}: {:?}", fullname, err);
+                }
+            }
+        }
+
+        if !config.mdns.enabled {
+            return Ok(());
+        }
+
+        let daemon = match &mut self.daemon {
+            Some(daemon) => daemon,
+            daemon => daemon.insert(ServiceDaemon::new().map_err(|err| anyhow!("{:?}", err))?),
+        };
+
+        let strip_lengths = config
+            .outputs
+            .iter()
+            .map(|output| output.strip_length)
+            .collect::<Vec<_>>();
+
+        let mut led_types = config
+            .outputs
+            .iter()
+            .map(|output| output.led_type.to_string())
+            .collect::<Vec<_>>();
+        led_types.sort();
+        led_types.dedup();
+
+        let txt = mdns::txt_records(
+            &strip_lengths,
+            &led_types.join(","),
+            env!("CARGO_PKG_VERSION"),
+        );
+
+        let fullname = register(daemon, config, SERVICE_DOMAIN, config.listen.port, &txt)?;
+        self.fullnames.push(fullname);
+
+        if config.oscquery.enabled {
+            let fullname = register(
+                daemon,
+                config,
+                OSCQUERY_SERVICE_DOMAIN,
+                config.oscquery.port,
+                &[],
+            )?;
+            self.fullnames.push(fullname);
+        }
+
+        Ok(())
+    }
+}
+
+/// Registers a service of type `service_domain` on `port`, returning its full name.
+fn register(
+    daemon: &ServiceDaemon,
+    config: &Config,
+    service_domain: &str,
+    port: u16,
+    txt: &[(String, String)],
+) -> Result<String> {
+    let addresses = if config.listen.address.is_unspecified() {
+        vec![]
+    } else {
+        vec![config.listen.address]
+    };
+
+    let mut service = ServiceInfo::new(
+        service_domain,
+        &instance_name(config),
+        &format!("{}.local.", hostname()),
+        &addresses[..],
+        port,
+        txt,
+    )
+    .map_err(|err| anyhow!("{:?}", err))?;
+
+    // Follow DHCP address changes when listening on every interface
+    if addresses.is_empty() {
+        service = service.enable_addr_auto();
+    }
+
+    let fullname = service.get_fullname().to_string();
+    daemon
+        .register(service)
+        .map_err(|err| anyhow!("{:?}", err))?;
+
+    info!("Advertising {} with mDNS", fullname);
+
+    Ok(fullname)
+}
+
+/// The name the receiver is advertised as, which defaults to the Pi's hostname.
+pub fn instance_name(config: &Config) -> String {
+    config.mdns.name.clone().unwrap_or_else(hostname)
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .ok()
+        .filter(|hostname| !hostname.is_empty())
+        .unwrap_or_else(|| FALLBACK_HOSTNAME.to_string())
+}
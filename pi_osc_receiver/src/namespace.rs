@@ -0,0 +1,206 @@
+use osc_receiver::oscquery::{Node, ACCESS_WRITE};
+use osc_receiver::pattern::{PATTERN_KINDS, PATTERN_PREFIX, STOP_ADDR};
+use osc_receiver::preset::{Presets, PRESET_PREFIX};
+use osc_receiver::query::{INFO_ADDR, PING_ADDR, STATUS_ADDR};
+use osc_receiver::scene::SAVE_DEFAULT_ADDR;
+
+use crate::config::Config;
+
+/// The number of DMX channels in a universe
+const UNIVERSE_SIZE: usize = 512;
+
+const COLOR_NAMES: [&str; 3] = ["Red", "Green", "Blue"];
+
+/// The OSC address space of the receiver described by `config` with the stored presets, served over
+/// OSCQuery.
+pub fn build(config: &Config, presets: &Presets) -> Node {
+    let mut root = Node::container("/").with_description("pi_osc_receiver");
+
+    if config.protocols.led_strips {
+        for (i, output) in config.outputs.iter().enumerate() {
+            let description = format!(
+                "Colors of the {} LEDs on strip {}. Send to /led_strips/{}/<offset> to start at an offset. Extra colors overflow into the next strip",
+                output.strip_length, i, i
+            );
+            root.insert(Node::method(
+                &format!("/led_strips/{}", i),
+                &"r".repeat(output.strip_length),
+                ACCESS_WRITE,
+                &description,
+            ));
+            root.insert(Node::method(
+                &format!("/led_strips/{}/info", i),
+                "",
+                ACCESS_WRITE,
+                "Replies with the strip's length and LED type",
+            ));
+        }
+    }
+
+    if config.protocols.dmx {
+        let mut channel = 0;
+
+        for (i, output) in config.outputs.iter().enumerate() {
+            for led in 0..output.strip_length {
+                for color in COLOR_NAMES.iter() {
+                    let description = format!("{} of LED {} on strip {}", color, led, i);
+                    let addr = format!(
+                        "/{}/dmx/{}",
+                        channel / UNIVERSE_SIZE,
+                        channel % UNIVERSE_SIZE
+                    );
+
+                    root.insert(
+                        Node::method(&addr, "f", ACCESS_WRITE, &description)
+                            .with_range(Some(0.0), Some(1.0)),
+                    );
+                    channel += 1;
+                }
+            }
+        }
+    }
+
+    root.insert(Node::method(
+        PING_ADDR,
+        "",
+        ACCESS_WRITE,
+        "Replies with /pong and the same arguments",
+    ));
+    root.insert(Node::method(
+        INFO_ADDR,
+        "",
+        ACCESS_WRITE,
+        "Replies with the receiver's version, strip count and each strip's length and LED type",
+    ));
+    root.insert(Node::method(
+        STATUS_ADDR,
+        "",
+        ACCESS_WRITE,
+        "Replies with the uptime, frames per second, frames displayed, invalid packets and write errors",
+    ));
+
+    root.insert(Node::method(
+        SAVE_DEFAULT_ADDR,
+        "",
+        ACCESS_WRITE,
+        "Saves the LEDs currently displayed as the scene shown when the receiver starts",
+    ));
+
+    root.insert(Node::container("/preset").with_description(
+        "Send /preset/<name>/store to store the LEDs currently displayed as a preset",
+    ));
+    for name in presets.names() {
+        root.insert(Node::method(
+            &format!("{}{}/store", PRESET_PREFIX, name),
+            "",
+            ACCESS_WRITE,
+            &format!(
+                "Replaces preset {:?} with the LEDs currently displayed",
+                name
+            ),
+        ));
+        root.insert(
+            Node::method(
+                &format!("{}{}/recall", PRESET_PREFIX, name),
+                "f",
+                ACCESS_WRITE,
+                &format!(
+                    "Recalls preset {:?}, crossfading over the given number of seconds",
+                    name
+                ),
+            )
+            .with_range(Some(0.0), None),
+        );
+    }
+
+    for kind in PATTERN_KINDS.iter() {
+        let path = format!("{}{}", PATTERN_PREFIX, kind);
+
+        root.insert(Node::method(
+            &path,
+            "",
+            ACCESS_WRITE,
+            &format!("Runs the {} pattern until the next frame arrives", kind),
+        ));
+        root.insert(
+            Node::method(
+                &format!("{}/speed", path),
+                "f",
+                ACCESS_WRITE,
+                "Multiplies how fast the pattern moves",
+            )
+            .with_range(Some(0.0), None),
+        );
+        root.insert(
+            Node::method(
+                &format!("{}/scale", path),
+                "f",
+                ACCESS_WRITE,
+                "The size of the pattern in LEDs",
+            )
+            .with_range(Some(1.0), None),
+        );
+        root.insert(Node::method(
+            &format!("{}/color", path),
+            "r",
+            ACCESS_WRITE,
+            "The pattern's color",
+        ));
+        root.insert(
+            Node::method(
+                &format!("{}/density", path),
+                "f",
+                ACCESS_WRITE,
+                "How busy the pattern is",
+            )
+            .with_range(Some(0.0), Some(1.0)),
+        );
+    }
+    root.insert(Node::method(
+        STOP_ADDR,
+        "",
+        ACCESS_WRITE,
+        "Stops the running pattern",
+    ));
+
+    if config.playback.file.is_some() {
+        root.insert(Node::method(
+            "/show/play",
+            "",
+            ACCESS_WRITE,
+            "Plays the show",
+        ));
+        root.insert(Node::method(
+            "/show/pause",
+            "",
+            ACCESS_WRITE,
+            "Pauses the show",
+        ));
+        root.insert(
+            Node::method(
+                "/show/seek",
+                "f",
+                ACCESS_WRITE,
+                "Jumps to a position in the show, in seconds",
+            )
+            .with_range(Some(0.0), None),
+        );
+        root.insert(
+            Node::method(
+                "/show/speed",
+                "f",
+                ACCESS_WRITE,
+                "Sets the playback speed multiplier",
+            )
+            .with_range(Some(0.0), None),
+        );
+        root.insert(Node::method(
+            "/show/loop",
+            "T",
+            ACCESS_WRITE,
+            "Restarts the show once it ends when true",
+        ));
+    }
+
+    root
+}
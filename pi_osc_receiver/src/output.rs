@@ -0,0 +1,264 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context, Result};
+use apa102_spi::Apa102;
+use rppal::spi::{Bus, Mode, SlaveSelect, Spi};
+use serde::{de, Deserialize, Deserializer};
+
+use osc_receiver::led_strip::{LedStrip, RGB8SmartLedsWrite, STRIP_LENGTH};
+
+use crate::ws2812::{self, PiWs2812};
+
+pub const MHZ: u32 = 1_000_000u32;
+
+/// Environment variable listing the SPI outputs to drive, separated by commas.
+///
+/// Each output is written as `<bus>:<slave_select>[:<clock_mhz>[:<led_type>[:<strip_length>]]]`, eg.
+/// `RAVES_OUTPUTS="0:0:12:apa102:450,1:0:8:apa102:144"`.
+pub const OUTPUTS_ENV_VAR: &str = "RAVES_OUTPUTS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LedType {
+    Apa102,
+    Ws2812b,
+}
+
+impl LedType {
+    pub fn default_clock_speed(&self) -> u32 {
+        match self {
+            // These LED strips should work up to 12 MHz but depending on wiring interference may limit that
+            LedType::Apa102 => 12 * MHZ,
+            LedType::Ws2812b => ws2812::DEFAULT_CLOCK_SPEED,
+        }
+    }
+}
+
+impl FromStr for LedType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "APA102" | "SK9822" => Ok(LedType::Apa102),
+            "WS2812B" | "WS2812" => Ok(LedType::Ws2812b),
+            _ => bail!(
+                "Invalid LED type {:?}. Must be 'APA102', 'SK9822' or 'WS2812B'",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for LedType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedType::Apa102 => write!(f, "APA102"),
+            LedType::Ws2812b => write!(f, "WS2812B"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for LedType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let led_type = String::deserialize(deserializer)?;
+
+        led_type.parse().map_err(de::Error::custom)
+    }
+}
+
+/// A single LED strip connected to one of the Pi's SPI bus / chip select pairs.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Output {
+    #[serde(deserialize_with = "deserialize_bus")]
+    pub bus: Bus,
+    #[serde(deserialize_with = "deserialize_slave_select")]
+    pub slave_select: SlaveSelect,
+    /// Defaults to the LED type's default clock speed
+    #[serde(rename = "clock_mhz", deserialize_with = "deserialize_mhz")]
+    pub clock_speed: Option<u32>,
+    pub led_type: LedType,
+    pub strip_length: usize,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            bus: Bus::Spi0,
+            slave_select: SlaveSelect::Ss0,
+            clock_speed: None,
+            led_type: LedType::Apa102,
+            strip_length: STRIP_LENGTH,
+        }
+    }
+}
+
+impl Output {
+    /// Reads the outputs from `RAVES_OUTPUTS`, falling back to a single APA102 strip on SPI0 / CE0.
+    pub fn from_env() -> Result<Vec<Self>> {
+        match std::env::var(OUTPUTS_ENV_VAR) {
+            Ok(outputs) => outputs
+                .split(',')
+                .map(|output| {
+                    output
+                        .parse()
+                        .with_context(|| format!("Invalid output in {}", OUTPUTS_ENV_VAR))
+                })
+                .collect(),
+            Err(_) => Ok(vec![Output::default()]),
+        }
+    }
+
+    pub fn clock_speed(&self) -> u32 {
+        self.clock_speed
+            .unwrap_or_else(|| self.led_type.default_clock_speed())
+    }
+
+    pub fn connect(&self) -> Result<Box<dyn RGB8SmartLedsWrite>> {
+        // Modes: https://en.wikipedia.org/wiki/Serial_Peripheral_Interface_Bus#Clock_polarity_and_phase
+        let spi = Spi::new(self.bus, self.slave_select, self.clock_speed(), Mode::Mode0)
+            .with_context(|| {
+                format!(
+                    "Unable to connect to {}. Are you not running on a Raspberry Pi?",
+                    self
+                )
+            })?;
+
+        let smart_led: Box<dyn RGB8SmartLedsWrite> = match self.led_type {
+            LedType::Apa102 => Box::new(Apa102::new(spi)),
+            LedType::Ws2812b => Box::new(PiWs2812::new(spi, self.strip_length)?),
+        };
+
+        Ok(smart_led)
+    }
+}
+
+/// Connects to the SPI device of each output.
+pub fn connect_all(outputs: &[Output]) -> Result<Vec<Box<dyn RGB8SmartLedsWrite>>> {
+    outputs
+        .iter()
+        .map(|output| {
+            let smart_led = output.connect()?;
+            println!("SPI Connected: {}", output);
+
+            Ok(smart_led)
+        })
+        .collect()
+}
+
+/// Creates an `LedStrip` for each connected output.
+pub fn led_strips<'a>(
+    smart_leds: &'a mut [Box<dyn RGB8SmartLedsWrite>],
+    outputs: &[Output],
+) -> Vec<LedStrip<'a>> {
+    smart_leds
+        .iter_mut()
+        .zip(outputs.iter())
+        .map(|(smart_led, output)| LedStrip::with_length(smart_led.as_mut(), output.strip_length))
+        .collect()
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SPI{}.{} ({} x {} LEDs at {} Hz)",
+            self.bus as u8,
+            self.slave_select as u8,
+            self.led_type,
+            self.strip_length,
+            self.clock_speed(),
+        )
+    }
+}
+
+impl FromStr for Output {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut fields = s.trim().split(':');
+        let mut output = Output::default();
+
+        let bus = fields.next().filter(|field| !field.is_empty());
+        output.bus = parse_bus(bus.ok_or_else(|| anyhow!("Missing SPI bus in {:?}", s))?)?;
+
+        if let Some(slave_select) = fields.next() {
+            output.slave_select = parse_slave_select(slave_select)?;
+        }
+        // An empty clock speed selects the LED type's default, eg. `0:0::ws2812b`
+        if let Some(clock_mhz) = fields.next().filter(|field| !field.is_empty()) {
+            let clock_mhz: u32 = clock_mhz
+                .parse()
+                .with_context(|| format!("Invalid SPI clock speed (MHz): {:?}", clock_mhz))?;
+            output.clock_speed = Some(clock_mhz * MHZ);
+        }
+        if let Some(led_type) = fields.next() {
+            output.led_type = led_type.parse()?;
+        }
+        if let Some(strip_length) = fields.next() {
+            output.strip_length = strip_length
+                .parse()
+                .with_context(|| format!("Invalid strip length: {:?}", strip_length))?;
+        }
+        if fields.next().is_some() {
+            bail!("Too many fields in output {:?}", s);
+        }
+
+        Ok(output)
+    }
+}
+
+pub fn parse_bus(bus: &str) -> Result<Bus> {
+    let bus = match bus.trim_start_matches("spi") {
+        "0" => Bus::Spi0,
+        "1" => Bus::Spi1,
+        "2" => Bus::Spi2,
+        "3" => Bus::Spi3,
+        "4" => Bus::Spi4,
+        "5" => Bus::Spi5,
+        "6" => Bus::Spi6,
+        _ => bail!("Invalid SPI bus {:?}. Must be between 0 and 6", bus),
+    };
+
+    Ok(bus)
+}
+
+pub fn parse_slave_select(slave_select: &str) -> Result<SlaveSelect> {
+    let slave_select = match slave_select.trim_start_matches("ss") {
+        "0" => SlaveSelect::Ss0,
+        "1" => SlaveSelect::Ss1,
+        "2" => SlaveSelect::Ss2,
+        _ => bail!(
+            "Invalid SPI slave select {:?}. Must be between 0 and 2",
+            slave_select
+        ),
+    };
+
+    Ok(slave_select)
+}
+
+fn deserialize_bus<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bus, D::Error> {
+    let bus = u8::deserialize(deserializer)?;
+
+    parse_bus(&bus.to_string()).map_err(de::Error::custom)
+}
+
+fn deserialize_slave_select<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<SlaveSelect, D::Error> {
+    let slave_select = u8::deserialize(deserializer)?;
+
+    parse_slave_select(&slave_select.to_string()).map_err(de::Error::custom)
+}
+
+fn deserialize_mhz<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    let clock_mhz = u32::deserialize(deserializer)?;
+
+    match clock_mhz {
+        1..=32 => Ok(Some(clock_mhz * MHZ)),
+        _ => Err(de::Error::custom(format!(
+            "Invalid SPI clock speed {} MHz. Must be between 1 and 32 MHz",
+            clock_mhz
+        ))),
+    }
+}
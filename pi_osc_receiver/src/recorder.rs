@@ -0,0 +1,206 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+
+use osc_receiver::led_strip::LedStrip;
+use osc_receiver::show::{self, ShowFrame, ShowHeader};
+
+use crate::config::Recording;
+
+// Limits how much of a recording can be lost to a power cut
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Recordings are named `show-{started_at_unix_ms}.show`
+const FILE_NAME_PREFIX: &str = "show-";
+
+/// Records every displayed frame to show files in the recording directory, starting a new file
+/// whenever the current one reaches the maximum file size and deleting the oldest files so that at
+/// most `max_files` are kept.
+pub struct Recorder {
+    config: Recording,
+    file: Option<ShowFile>,
+    frame_buf: Vec<u8>,
+}
+
+struct ShowFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    size: u64,
+    started_at: Instant,
+    flushed_at: Instant,
+    strip_lengths: Vec<usize>,
+}
+
+impl Recorder {
+    pub fn new(config: &Recording) -> Result<Self> {
+        fs::create_dir_all(&config.directory).with_context(|| {
+            format!(
+                "Unable to create recording directory {:?}",
+                config.directory
+            )
+        })?;
+
+        Ok(Self {
+            config: config.clone(),
+            file: None,
+            frame_buf: Vec::new(),
+        })
+    }
+
+    pub fn record(&mut self, led_strips: &[LedStrip]) -> Result<()> {
+        let strip_lengths = led_strips
+            .iter()
+            .map(|led_strip| led_strip.data.len())
+            .collect::<Vec<_>>();
+
+        let mut file = match self.file.take() {
+            Some(file) if file.strip_lengths == strip_lengths => file,
+            _ => self.create_file(strip_lengths)?,
+        };
+
+        self.frame_buf.clear();
+        ShowFrame::encode_strips(
+            file.started_at.elapsed().as_millis() as u32,
+            led_strips.iter().map(|led_strip| &led_strip.data[..]),
+            &mut self.frame_buf,
+        );
+
+        if file.size + self.frame_buf.len() as u64 > self.config.max_file_size() {
+            file.writer.flush()?;
+            info!("Recording {:?} is full", file.path);
+
+            file = self.create_file(file.strip_lengths)?;
+
+            // Re-encode the frame relative to the start of the new file
+            self.frame_buf.clear();
+            ShowFrame::encode_strips(
+                0,
+                led_strips.iter().map(|led_strip| &led_strip.data[..]),
+                &mut self.frame_buf,
+            );
+        }
+
+        file.writer.write_all(&self.frame_buf)?;
+        file.size += self.frame_buf.len() as u64;
+
+        if file.flushed_at.elapsed() >= FLUSH_INTERVAL {
+            file.writer.flush()?;
+            file.flushed_at = Instant::now();
+        }
+
+        self.file = Some(file);
+
+        Ok(())
+    }
+
+    fn create_file(&self, strip_lengths: Vec<usize>) -> Result<ShowFile> {
+        let started_at_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        let path = self.config.directory.join(format!(
+            "{}{}.{}",
+            FILE_NAME_PREFIX,
+            started_at_unix_ms,
+            show::EXTENSION
+        ));
+
+        let mut header = Vec::new();
+        ShowHeader {
+            started_at_unix_ms,
+            strip_lengths: strip_lengths.clone(),
+        }
+        .encode(&mut header)?;
+
+        let mut writer = BufWriter::new(
+            File::create(&path).with_context(|| format!("Unable to create {:?}", path))?,
+        );
+        writer.write_all(&header)?;
+
+        info!("Recording frames to {:?}", path);
+
+        self.remove_old_files()?;
+
+        Ok(ShowFile {
+            path,
+            writer,
+            size: header.len() as u64,
+            started_at: Instant::now(),
+            flushed_at: Instant::now(),
+            strip_lengths,
+        })
+    }
+
+    /// Removes the oldest recordings beyond `max_files`. Other files in the directory, eg. shows
+    /// copied there to be played back, are left alone.
+    fn remove_old_files(&self) -> Result<()> {
+        let mut recordings = fs::read_dir(&self.config.directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter_map(|path| recording_started_at(&path).map(|started_at| (started_at, path)))
+            .collect::<Vec<_>>();
+
+        // Oldest first
+        recordings.sort();
+
+        let excess_files = recordings.len().saturating_sub(self.config.max_files);
+
+        for (_, path) in recordings.into_iter().take(excess_files) {
+            info!("Removing old recording {:?}", path);
+
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("Unable to remove old recording {:?}: {:?}", path, err);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The start time in the name of a file written by the recorder, or `None` for any other file.
+fn recording_started_at(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(FILE_NAME_PREFIX)?
+        .strip_suffix(show::EXTENSION)?
+        .strip_suffix('.')?
+        .parse()
+        .ok()
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.as_mut() {
+            let _ = file.writer.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_recorders_own_files_are_recordings() {
+        assert_eq!(
+            recording_started_at(Path::new("recordings/show-1700000000000.show")),
+            Some(1_700_000_000_000)
+        );
+        assert_eq!(
+            recording_started_at(Path::new("recordings/opening.show")),
+            None
+        );
+        assert_eq!(
+            recording_started_at(Path::new("recordings/show-final.show")),
+            None
+        );
+        assert_eq!(
+            recording_started_at(Path::new("recordings/show-1.show.bak")),
+            None
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+
+use osc_receiver::preset::{self, Presets};
+use osc_receiver::scene::{self, Scene};
+
+use crate::config::Scenes;
+
+/// Loads the scene shown as soon as the receiver starts, if one has been saved.
+pub fn load_default(scenes: &Scenes) -> Option<Scene> {
+    let path = scenes.default_scene_path();
+    if !path.exists() {
+        return None;
+    }
+
+    match read(&path) {
+        Ok(scene) => {
+            info!("Loaded the default scene {:?}", path);
+            Some(scene)
+        }
+        Err(err) => {
+            error!("Unable to load the default scene: {:?}", err);
+            None
+        }
+    }
+}
+
+pub fn save_default(scenes: &Scenes, scene: &Scene) -> Result<()> {
+    let path = scenes.default_scene_path();
+    write(&path, scene)?;
+
+    info!("Saved the default scene to {:?}", path);
+
+    Ok(())
+}
+
+/// Loads every preset in the presets directory. Files that aren't valid presets are skipped.
+pub fn load_presets(scenes: &Scenes) -> Result<Presets> {
+    let directory = scenes.presets_directory();
+    let mut presets = Presets::default();
+
+    if !directory.exists() {
+        return Ok(presets);
+    }
+
+    let entries = fs::read_dir(&directory)
+        .with_context(|| format!("Unable to read presets directory {:?}", directory))?;
+
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(scene::EXTENSION) {
+            continue;
+        }
+
+        let name = match path.file_stem().and_then(|name| name.to_str()) {
+            Some(name) if preset::is_valid_name(name) => name,
+            _ => {
+                warn!("Skipping preset with an invalid name {:?}", path);
+                continue;
+            }
+        };
+
+        match read(&path) {
+            Ok(scene) => presets.insert(name, scene),
+            Err(err) => warn!("Skipping preset {:?}: {:?}", path, err),
+        }
+    }
+
+    info!("Loaded {} presets from {:?}", presets.len(), directory);
+
+    Ok(presets)
+}
+
+pub fn save_preset(scenes: &Scenes, name: &str, scene: &Scene) -> Result<()> {
+    let path = scenes
+        .presets_directory()
+        .join(format!("{}.{}", name, scene::EXTENSION));
+    write(&path, scene)?;
+
+    info!("Stored preset {:?} in {:?}", name, path);
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Scene> {
+    let bytes = fs::read(path).with_context(|| format!("Unable to read scene file {:?}", path))?;
+
+    Scene::decode(&bytes).with_context(|| format!("Invalid scene file {:?}", path))
+}
+
+fn write(path: &Path, scene: &Scene) -> Result<()> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("Unable to create scene directory {:?}", directory))?;
+    }
+
+    // Write to a temporary file first so that a power cut can't leave a half written scene
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, scene.encode()?)
+        .with_context(|| format!("Unable to write scene file {:?}", temp_path))?;
+    fs::rename(&temp_path, path).with_context(|| format!("Unable to write scene file {:?}", path))
+}
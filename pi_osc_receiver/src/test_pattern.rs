@@ -0,0 +1,74 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::info;
+use smart_leds::RGB8;
+
+use crate::config::Config;
+use crate::output;
+
+const STEP_DURATION: Duration = Duration::from_secs(1);
+
+// Dim enough to run long strips off of a small power supply
+const COLORS: [(&str, RGB8); 4] = [
+    (
+        "Red",
+        RGB8 {
+            r: 0x20,
+            g: 0,
+            b: 0,
+        },
+    ),
+    (
+        "Green",
+        RGB8 {
+            r: 0,
+            g: 0x20,
+            b: 0,
+        },
+    ),
+    (
+        "Blue",
+        RGB8 {
+            r: 0,
+            g: 0,
+            b: 0x20,
+        },
+    ),
+    (
+        "White",
+        RGB8 {
+            r: 0x20,
+            g: 0x20,
+            b: 0x20,
+        },
+    ),
+];
+
+/// Cycles every strip through red, green, blue and white so that the wiring, color order and strip
+/// lengths can be checked without sending any OSC packets.
+///
+/// The last LED of each strip is always lit white to make it easy to spot a wrong strip length.
+pub fn run(config: &Config) -> Result<()> {
+    let mut smart_leds = output::connect_all(&config.outputs)?;
+    let mut led_strips = output::led_strips(&mut smart_leds, &config.outputs);
+
+    info!("Starting test pattern");
+
+    for (name, color) in COLORS.iter().cycle() {
+        for led_strip in led_strips.iter_mut() {
+            led_strip.data.iter_mut().for_each(|led| *led = *color);
+            if let Some(last_led) = led_strip.data.last_mut() {
+                *last_led = COLORS[3].1;
+            }
+
+            led_strip.write()?;
+        }
+        println!("{}", name);
+
+        sleep(STEP_DURATION);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use std::cell::RefCell;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use anyhow::{bail, Context, Result};
+use embedded_hal::spi::FullDuplex;
+use rppal::spi::Spi;
+use smart_leds::RGB8;
+use ws2812_spi::Ws2812;
+
+use osc_receiver::led_strip::RGB8SmartLedsWrite;
+
+use crate::output::MHZ;
+
+/// WS2812B strips need an SPI clock between 2 and 3.8 MHz. See `ws2812_spi::Ws2812::new`.
+pub const MIN_CLOCK_SPEED: u32 = 2 * MHZ;
+pub const MAX_CLOCK_SPEED: u32 = 3_800_000;
+pub const DEFAULT_CLOCK_SPEED: u32 = 3 * MHZ;
+
+// Each color byte is sent as 4 SPI bytes
+const SPI_BYTES_PER_LED: usize = 3 * 4;
+
+// Zeros sent before and after each frame. At 3 MHz this holds the data line low for ~340us which is
+// longer than the 280us reset period of newer WS2812B revisions and ensures the first bit of a frame
+// is never merged into the previous one.
+const RESET_BYTES: usize = 128;
+
+const SPIDEV_BUFSIZ_PATH: &str = "/sys/module/spidev/parameters/bufsiz";
+
+/// Collects the bytes sent by the `Ws2812` driver instead of sending them one at a time.
+///
+/// rppal's `FullDuplex` implementation performs a separate ioctl for every byte which leaves gaps
+/// long enough for the LEDs to latch mid-frame so frames are buffered and written in one transfer.
+struct FrameBuffer {
+    bytes: Rc<RefCell<Vec<u8>>>,
+    /// Bytes sent but not read back yet. The driver keeps one byte in flight, reading it back at
+    /// the end of the frame
+    pending_reads: usize,
+}
+
+impl FullDuplex<u8> for FrameBuffer {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        if self.pending_reads == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.pending_reads -= 1;
+
+        Ok(0)
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.bytes.borrow_mut().push(byte);
+        self.pending_reads += 1;
+
+        Ok(())
+    }
+}
+
+/// Where finished frames are written, which is the Pi's SPI port outside of tests.
+pub trait FrameWriter {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()>;
+}
+
+impl FrameWriter for Spi {
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.write(frame).context("Writing to LED SPI port")?;
+
+        Ok(())
+    }
+}
+
+/// A WS2812B strip connected to the Pi's SPI MOSI pin.
+pub struct PiWs2812<S = Spi> {
+    spi: S,
+    frame: Rc<RefCell<Vec<u8>>>,
+    ws2812: Ws2812<FrameBuffer>,
+}
+
+impl<S: FrameWriter> PiWs2812<S> {
+    pub fn new(spi: S, strip_length: usize) -> Result<Self> {
+        let frame_size = frame_size(strip_length);
+
+        // spidev rejects transfers larger than its buffer (4096 bytes by default)
+        if let Some(bufsiz) = spidev_bufsiz() {
+            if frame_size > bufsiz {
+                bail!(
+                    "{} WS2812B LEDs need a {} byte SPI buffer but spidev's buffer is {} bytes. Add `spidev.bufsiz=65536` to /boot/cmdline.txt and reboot",
+                    strip_length,
+                    frame_size,
+                    bufsiz,
+                );
+            }
+        }
+
+        let frame = Rc::new(RefCell::new(Vec::with_capacity(frame_size)));
+        let ws2812 = Ws2812::new(FrameBuffer {
+            bytes: Rc::clone(&frame),
+            pending_reads: 0,
+        });
+
+        Ok(Self { spi, frame, ws2812 })
+    }
+}
+
+impl<S: FrameWriter> RGB8SmartLedsWrite for PiWs2812<S> {
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        {
+            let mut frame = self.frame.borrow_mut();
+            frame.clear();
+            frame.resize(RESET_BYTES, 0);
+        }
+
+        self.ws2812.write_rgb8(iterator)?;
+
+        let mut frame = self.frame.borrow_mut();
+        let frame_len = frame.len() + RESET_BYTES;
+        frame.resize(frame_len, 0);
+
+        self.spi.write_frame(&frame)
+    }
+}
+
+fn frame_size(strip_length: usize) -> usize {
+    // The driver also sends 1 byte of padding before and 20 bytes of reset after the LEDs
+    2 * RESET_BYTES + strip_length * SPI_BYTES_PER_LED + 21
+}
+
+fn spidev_bufsiz() -> Option<usize> {
+    std::fs::read_to_string(SPIDEV_BUFSIZ_PATH)
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records the frames instead of writing them to an SPI port
+    #[derive(Clone, Default)]
+    struct RecordingSpi(Rc<RefCell<Vec<Vec<u8>>>>);
+
+    impl FrameWriter for RecordingSpi {
+        fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+            self.0.borrow_mut().push(frame.to_vec());
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_frames_with_reset_bytes() {
+        let spi = RecordingSpi::default();
+        let mut ws2812 = PiWs2812::new(spi.clone(), 1).unwrap();
+
+        let color = RGB8 {
+            r: 0xFF,
+            g: 0x00,
+            b: 0x0F,
+        };
+        ws2812.write_rgb8(&mut std::iter::once(color)).unwrap();
+
+        let mut expected = vec![0; RESET_BYTES];
+        // The driver's padding byte
+        expected.push(0);
+        // Green, red then blue, with two bits in each byte
+        expected.extend_from_slice(&[0x88; 4]);
+        expected.extend_from_slice(&[0xEE; 4]);
+        expected.extend_from_slice(&[0x88, 0x88, 0xEE, 0xEE]);
+        // The driver's reset
+        expected.extend_from_slice(&[0; 20]);
+        expected.extend_from_slice(&[0; RESET_BYTES]);
+
+        let frames = spi.0.borrow();
+        assert_eq!(*frames, vec![expected]);
+        assert_eq!(frames[0].len(), frame_size(1));
+    }
+}
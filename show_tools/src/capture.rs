@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use etherparse::defrag::IpDefragPool;
+use etherparse::{IpNumber, SlicedPacket, TransportSlice, UdpSlice};
+use log::{debug, warn};
+use pcap_file::pcap::PcapReader;
+use pcap_file::pcapng::blocks::interface_description::InterfaceDescriptionOption;
+use pcap_file::pcapng::{Block, PcapNgReader};
+use pcap_file::DataLink;
+
+const PCAPNG_MAGIC: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+
+/// The UDP packets sent to the receiver in a capture.
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// The time the first packet was captured at since the unix epoch
+    pub started_at: Duration,
+    pub packets: Vec<CapturedPacket>,
+}
+
+/// The payload of a UDP datagram sent to the receiver port.
+#[derive(Debug, Clone)]
+pub struct CapturedPacket {
+    /// The time the packet was captured at, relative to the first captured packet
+    pub timestamp: Duration,
+    pub payload: Vec<u8>,
+}
+
+/// Reads every UDP packet addressed to `port` from a `.pcap` or `.pcapng` capture, reassembling
+/// packets that were split into IP fragments.
+pub fn read(path: &Path, port: u16) -> Result<Capture> {
+    let mut file = BufReader::new(
+        File::open(path).with_context(|| format!("Unable to open capture {:?}", path))?,
+    );
+
+    let mut magic = [0; 4];
+    file.read_exact(&mut magic)
+        .with_context(|| format!("Unable to read capture {:?}", path))?;
+    let file = magic.chain(file);
+
+    let mut filter = UdpFilter::new(port);
+
+    if magic == PCAPNG_MAGIC {
+        let mut reader = PcapNgReader::new(file).context("Invalid pcapng capture")?;
+
+        // The link type and timestamp resolution of each interface in the current section
+        let mut interfaces = Vec::new();
+
+        while let Some(block) = reader.next_block() {
+            match block.context("Invalid pcapng block")? {
+                Block::SectionHeader(_) => interfaces.clear(),
+                Block::InterfaceDescription(interface) => {
+                    // Timestamps default to microseconds
+                    let resolution = interface
+                        .options
+                        .iter()
+                        .find_map(|option| match option {
+                            InterfaceDescriptionOption::IfTsResol(resolution) => Some(*resolution),
+                            _ => None,
+                        })
+                        .unwrap_or(6);
+
+                    interfaces.push((interface.linktype, resolution));
+                }
+                Block::EnhancedPacket(packet) => {
+                    let (linktype, resolution) = match interfaces.get(packet.interface_id as usize)
+                    {
+                        Some(interface) => *interface,
+                        None => bail!(
+                            "Packet references unknown interface {}",
+                            packet.interface_id
+                        ),
+                    };
+
+                    // pcap-file reads the raw timestamp as nanoseconds regardless of the
+                    // interface's resolution
+                    let timestamp =
+                        timestamp_from_units(packet.timestamp.as_nanos() as u64, resolution)?;
+
+                    filter.push(linktype, timestamp, &packet.data)?;
+                }
+                _ => {}
+            }
+        }
+    } else {
+        let mut reader = PcapReader::new(file).context("Invalid pcap capture")?;
+        let datalink = reader.header().datalink;
+
+        while let Some(packet) = reader.next_packet() {
+            let packet = packet.context("Invalid pcap packet")?;
+
+            filter.push(datalink, packet.timestamp, &packet.data)?;
+        }
+    }
+
+    Ok(filter.finish())
+}
+
+/// Converts a pcapng timestamp in units of `10^-n` seconds (or `2^-n` seconds if the most
+/// significant bit of the resolution is set) to a `Duration`. Fails for decimal resolutions finer
+/// than `10^-38` seconds, which don't fit in a `u128`.
+fn timestamp_from_units(units: u64, resolution: u8) -> Result<Duration> {
+    let exponent = (resolution & 0x7F) as u32;
+    let units_per_second = if resolution & 0x80 == 0 {
+        match 10u128.checked_pow(exponent) {
+            Some(units_per_second) => units_per_second,
+            None => bail!(
+                "Unsupported timestamp resolution of 10^-{} seconds",
+                exponent
+            ),
+        }
+    } else {
+        1u128 << exponent
+    };
+
+    let nanos = units as u128 * 1_000_000_000 / units_per_second;
+
+    Ok(Duration::from_nanos(nanos as u64))
+}
+
+struct UdpFilter {
+    port: u16,
+    defrag: IpDefragPool,
+    first_timestamp: Option<Duration>,
+    packets: Vec<CapturedPacket>,
+}
+
+impl UdpFilter {
+    fn new(port: u16) -> Self {
+        Self {
+            port,
+            defrag: IpDefragPool::new(),
+            first_timestamp: None,
+            packets: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, datalink: DataLink, timestamp: Duration, data: &[u8]) -> Result<()> {
+        let sliced = match datalink {
+            DataLink::ETHERNET => SlicedPacket::from_ethernet(data),
+            DataLink::LINUX_SLL => SlicedPacket::from_linux_sll(data),
+            DataLink::RAW | DataLink::IPV4 | DataLink::IPV6 => SlicedPacket::from_ip(data),
+            // Loopback captures start with a 4 byte address family
+            DataLink::NULL | DataLink::LOOP if data.len() >= 4 => SlicedPacket::from_ip(&data[4..]),
+            datalink => bail!("Unsupported capture link type: {:?}", datalink),
+        };
+
+        let sliced = match sliced {
+            Ok(sliced) => sliced,
+            Err(err) => {
+                debug!("Skipping unparsable packet: {:?}", err);
+                return Ok(());
+            }
+        };
+
+        let reassembled = match self.defrag.process_sliced_packet(&sliced, (), ()) {
+            Ok(reassembled) => reassembled,
+            Err(err) => {
+                warn!("Skipping malformed IP fragment: {:?}", err);
+                return Ok(());
+            }
+        };
+
+        let udp = match (&reassembled, &sliced.transport) {
+            (Some(reassembled), _) if reassembled.ip_number == IpNumber::UDP => {
+                UdpSlice::from_slice(&reassembled.payload).ok()
+            }
+            (None, Some(TransportSlice::Udp(udp))) => Some(udp.clone()),
+            _ => None,
+        };
+
+        if let Some(udp) = udp {
+            if udp.destination_port() == self.port {
+                let first_timestamp = *self.first_timestamp.get_or_insert(timestamp);
+
+                self.packets.push(CapturedPacket {
+                    timestamp: timestamp.saturating_sub(first_timestamp),
+                    payload: udp.payload().to_vec(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Capture {
+        Capture {
+            started_at: self.first_timestamp.unwrap_or_default(),
+            packets: self.packets,
+        }
+    }
+}
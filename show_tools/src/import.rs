@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use smart_leds::RGB8;
+
+use osc_receiver::led_strip::{LedStrip, RGB8SmartLedsWrite};
+use osc_receiver::show::{ShowFrame, ShowHeader};
+
+use crate::capture::Capture;
+
+/// Stands in for the LEDs. Only the strip data is needed to build each frame.
+struct NullSmartLed;
+
+impl RGB8SmartLedsWrite for NullSmartLed {
+    fn write_rgb8(&mut self, _iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The frames displayed by the packets in a capture.
+pub struct Imported {
+    pub header: ShowHeader,
+    pub frames: Vec<ShowFrame>,
+    /// The number of packets that could not be decoded as OSC
+    pub invalid_packets: usize,
+}
+
+/// Decodes each captured packet exactly as the receiver would, returning the frame displayed after
+/// each packet.
+pub fn decode(capture: &Capture, strip_lengths: &[usize]) -> Result<Imported> {
+    if capture.packets.is_empty() {
+        bail!("The capture does not contain any packets sent to the receiver port");
+    }
+
+    let mut smart_leds = strip_lengths
+        .iter()
+        .map(|_| NullSmartLed)
+        .collect::<Vec<_>>();
+    let mut led_strips = smart_leds
+        .iter_mut()
+        .zip(strip_lengths.iter())
+        .map(|(smart_led, length)| {
+            LedStrip::with_length(smart_led as &mut dyn RGB8SmartLedsWrite, *length)
+        })
+        .collect::<Vec<_>>();
+
+    let mut imported = Imported {
+        header: ShowHeader {
+            started_at_unix_ms: capture.started_at.as_millis() as u64,
+            strip_lengths: strip_lengths.to_vec(),
+        },
+        frames: Vec::new(),
+        invalid_packets: 0,
+    };
+
+    for packet in capture.packets.iter() {
+        match rosc::decoder::decode_udp(&packet.payload) {
+            Ok((&[], osc_packet)) => {
+                LedStrip::update(&mut led_strips, osc_packet)?;
+
+                imported.frames.push(ShowFrame {
+                    timestamp_ms: packet.timestamp.as_millis() as u32,
+                    strips: led_strips
+                        .iter()
+                        .map(|led_strip| led_strip.data.clone())
+                        .collect(),
+                });
+            }
+            _ => imported.invalid_packets += 1,
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Converts a capture to a show file.
+pub fn run(capture: &Capture, strip_lengths: &[usize], output: &Path) -> Result<()> {
+    let imported = decode(capture, strip_lengths)?;
+
+    let mut bytes = Vec::new();
+    imported.header.encode(&mut bytes)?;
+    for frame in imported.frames.iter() {
+        frame.encode(&mut bytes);
+    }
+
+    fs::write(output, &bytes).with_context(|| format!("Unable to write show file {:?}", output))?;
+
+    info!(
+        "Imported {} frames ({} invalid packets skipped) into {:?}",
+        imported.frames.len(),
+        imported.invalid_packets,
+        output
+    );
+    println!(
+        "Wrote {} frames spanning {:.1}s to {:?}",
+        imported.frames.len(),
+        capture.packets.last().unwrap().timestamp.as_secs_f64(),
+        output
+    );
+
+    Ok(())
+}
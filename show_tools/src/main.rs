@@ -0,0 +1,173 @@
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use log::warn;
+
+use osc_receiver::led_strip::STRIP_LENGTH;
+use osc_receiver::show::{ShowFrame, ShowHeader, ShowReader};
+
+mod capture;
+mod import;
+mod render;
+mod replay;
+
+use render::RenderOptions;
+
+/// Tools for working with recorded shows and packet captures of OSC streams.
+#[derive(Debug, Parser)]
+#[command(name = "show_tools", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Decode the OSC packets in a .pcap or .pcapng capture and save the frames they display as a
+    /// show file
+    Import {
+        /// The Wireshark / tcpdump capture to import
+        capture: PathBuf,
+
+        /// The show file to write
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// UDP port the receiver was listening for OSC packets on
+        #[arg(short, long, default_value_t = 8001)]
+        port: u16,
+
+        /// Number of LEDs on each of the receiver's strips, eg. `450,144` for two strips
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+    },
+    /// Send the OSC packets in a .pcap or .pcapng capture to a receiver at their original timing
+    Replay {
+        /// The Wireshark / tcpdump capture to replay
+        capture: PathBuf,
+
+        /// The receiver to send the packets to, eg. `192.168.1.50:8001`
+        #[arg(short, long)]
+        to: SocketAddr,
+
+        /// UDP port the receiver was listening for OSC packets on when the capture was made
+        #[arg(short, long, default_value_t = 8001)]
+        port: u16,
+
+        /// Playback speed multiplier, eg. 0.5 for half speed
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+    },
+    /// Render a show file or capture to a PNG waterfall (one row per frame, one column per LED) or
+    /// an animated GIF (one row per strip)
+    Render {
+        /// The show file, or .pcap / .pcapng capture, to render
+        input: PathBuf,
+
+        /// The .png or .gif file to write
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// UDP port the receiver was listening for OSC packets on. Only used for captures
+        #[arg(short, long, default_value_t = 8001)]
+        port: u16,
+
+        /// Number of LEDs on each of the receiver's strips. Only used for captures
+        #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+        strip_lengths: Vec<usize>,
+
+        /// Width and height in pixels of each LED. Defaults to 1 for PNGs and 8 for GIFs
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..=64))]
+        scale: Option<u16>,
+
+        /// Frames per second of animated GIFs
+        #[arg(long, default_value_t = 25, value_parser = clap::value_parser!(u32).range(1..=100))]
+        fps: u32,
+
+        /// Only render frames from this many seconds into the show
+        #[arg(long, value_name = "SECONDS")]
+        start: Option<f64>,
+
+        /// Only render frames up to this many seconds into the show
+        #[arg(long, value_name = "SECONDS")]
+        end: Option<f64>,
+    },
+}
+
+pub fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    match Cli::parse().command {
+        Command::Import {
+            capture,
+            output,
+            port,
+            strip_lengths,
+        } => import::run(&capture::read(&capture, port)?, &strip_lengths, &output),
+        Command::Replay {
+            capture,
+            to,
+            port,
+            speed,
+        } => replay::run(&capture::read(&capture, port)?, to, speed),
+        Command::Render {
+            input,
+            output,
+            port,
+            strip_lengths,
+            scale,
+            fps,
+            start,
+            end,
+        } => {
+            let (header, mut frames) = load_frames(&input, port, &strip_lengths)?;
+
+            let start_ms = start.map(|start| start * 1000.0).unwrap_or(0.0);
+            let end_ms = end.map(|end| end * 1000.0).unwrap_or(f64::INFINITY);
+            frames.retain(|frame| (start_ms..=end_ms).contains(&(frame.timestamp_ms as f64)));
+
+            let is_gif = output.extension().map(|extension| extension == "gif") == Some(true);
+            let options = RenderOptions {
+                scale: scale.map(usize::from).unwrap_or(if is_gif { 8 } else { 1 }),
+                fps,
+            };
+
+            render::run(&header, &frames, &output, options)
+        }
+    }
+}
+
+/// Reads the frames of a show file, or decodes the frames displayed by the packets in a capture.
+fn load_frames(
+    input: &Path,
+    port: u16,
+    strip_lengths: &[usize],
+) -> Result<(ShowHeader, Vec<ShowFrame>)> {
+    let extension = input.extension().and_then(|extension| extension.to_str());
+
+    if matches!(extension, Some("pcap" | "pcapng")) {
+        let imported = import::decode(&capture::read(input, port)?, strip_lengths)?;
+        return Ok((imported.header, imported.frames));
+    }
+
+    let bytes = std::fs::read(input).with_context(|| format!("Unable to read {:?}", input))?;
+    let reader =
+        ShowReader::new(&bytes).with_context(|| format!("Invalid show file {:?}", input))?;
+    let header = reader.header.clone();
+
+    let mut frames = Vec::new();
+    for frame in reader {
+        match frame {
+            Ok(frame) => frames.push(frame),
+            Err(err) => warn!("Skipping the end of the show: {:?}", err),
+        }
+    }
+
+    if frames.is_empty() {
+        bail!("{:?} does not contain any frames", input);
+    }
+
+    Ok((header, frames))
+}
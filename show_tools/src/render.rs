@@ -0,0 +1,147 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use osc_receiver::show::{ShowFrame, ShowHeader};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RenderOptions {
+    /// The width and height in pixels of each LED
+    pub scale: usize,
+    /// Frames per second of animated GIFs
+    pub fps: u32,
+}
+
+/// Renders the frames to a PNG waterfall or an animated GIF depending on the extension of `output`.
+pub fn run(
+    header: &ShowHeader,
+    frames: &[ShowFrame],
+    output: &Path,
+    options: RenderOptions,
+) -> Result<()> {
+    if frames.is_empty() {
+        bail!("There are no frames to render");
+    }
+
+    let file =
+        File::create(output).with_context(|| format!("Unable to create image {:?}", output))?;
+    let writer = BufWriter::new(file);
+
+    let extension = output
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("png") => waterfall(header, frames, writer, options),
+        Some("gif") => animation(header, frames, writer, options),
+        _ => bail!(
+            "Unsupported image format {:?}. Use a .png or .gif file",
+            output
+        ),
+    }
+    .with_context(|| format!("Unable to render {:?}", output))?;
+
+    println!("Rendered {} frames to {:?}", frames.len(), output);
+
+    Ok(())
+}
+
+/// One row per frame and one column per LED, with every strip placed side by side.
+fn waterfall(
+    header: &ShowHeader,
+    frames: &[ShowFrame],
+    writer: BufWriter<File>,
+    options: RenderOptions,
+) -> Result<()> {
+    let width = header.led_count() * options.scale;
+    let height = frames.len() * options.scale;
+
+    let mut encoder = png::Encoder::new(writer, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut data = Vec::with_capacity(width * height * 3);
+
+    for frame in frames.iter() {
+        let row = frame
+            .strips
+            .iter()
+            .flat_map(|strip| strip.iter())
+            .flat_map(|led| [led.r, led.g, led.b].repeat(options.scale))
+            .collect::<Vec<_>>();
+
+        for _ in 0..options.scale {
+            data.extend_from_slice(&row);
+        }
+    }
+
+    encoder.write_header()?.write_image_data(&data)?;
+
+    Ok(())
+}
+
+/// One row per strip, sampling the frame displayed at each point in time at a fixed frame rate.
+fn animation(
+    header: &ShowHeader,
+    frames: &[ShowFrame],
+    writer: BufWriter<File>,
+    options: RenderOptions,
+) -> Result<()> {
+    let columns = header.strip_lengths.iter().copied().max().unwrap_or(0);
+    let width = columns * options.scale;
+    let height = header.strip_lengths.len() * options.scale;
+
+    if width > u16::MAX as usize || height > u16::MAX as usize {
+        bail!(
+            "{}x{} is too large for a GIF, reduce --scale",
+            width,
+            height
+        );
+    }
+
+    let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &[])?;
+    encoder.set_repeat(gif::Repeat::Infinite)?;
+
+    // GIF delays are in hundredths of a second
+    let delay = (100 / options.fps).max(1) as u16;
+    let interval_ms = delay as u32 * 10;
+
+    let end_ms = frames.last().map(|frame| frame.timestamp_ms).unwrap_or(0);
+    let mut position_ms = frames[0].timestamp_ms;
+    let mut frame_index = 0;
+
+    while position_ms <= end_ms {
+        // The last frame at or before the current position
+        while frames
+            .get(frame_index + 1)
+            .map(|frame| frame.timestamp_ms <= position_ms)
+            .unwrap_or(false)
+        {
+            frame_index += 1;
+        }
+
+        let mut pixels = Vec::with_capacity(width * height * 3);
+
+        for strip in frames[frame_index].strips.iter() {
+            let row = (0..columns)
+                .map(|i| strip.get(i).copied().unwrap_or_default())
+                .flat_map(|led| [led.r, led.g, led.b].repeat(options.scale))
+                .collect::<Vec<_>>();
+
+            for _ in 0..options.scale {
+                pixels.extend_from_slice(&row);
+            }
+        }
+
+        let mut gif_frame = gif::Frame::from_rgb_speed(width as u16, height as u16, &pixels, 10);
+        gif_frame.delay = delay;
+        encoder.write_frame(&gif_frame)?;
+
+        position_ms += interval_ms;
+    }
+
+    Ok(())
+}
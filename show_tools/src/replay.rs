@@ -0,0 +1,40 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::debug;
+
+use crate::capture::Capture;
+
+/// Sends the captured packets to `to`, preserving the time between them.
+pub fn run(capture: &Capture, to: SocketAddr, speed: f64) -> Result<()> {
+    if speed.is_nan() || speed <= 0.0 {
+        bail!("--speed: Must be greater than 0");
+    }
+
+    let bind_addr: SocketAddr = if to.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_addr).context("Unable to bind a UDP socket")?;
+
+    println!("Replaying {} packets to {}", capture.packets.len(), to);
+
+    let started_at = Instant::now();
+
+    for packet in capture.packets.iter() {
+        let send_at = started_at + Duration::from_secs_f64(packet.timestamp.as_secs_f64() / speed);
+        if let Some(delay) = send_at.checked_duration_since(Instant::now()) {
+            thread::sleep(delay);
+        }
+
+        debug!("Sending {} bytes", packet.payload.len());
+        socket
+            .send_to(&packet.payload, to)
+            .with_context(|| format!("Unable to send packet to {}", to))?;
+    }
+
+    Ok(())
+}
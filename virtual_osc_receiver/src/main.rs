@@ -0,0 +1,185 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{anyhow, Context, Result};
+use clap::Parser;
+use log::{error, info, warn};
+
+use local_ip_address::local_ip;
+
+use osc_receiver::error_reply::{ErrorCode, MessageError};
+use osc_receiver::led_strip::{LedStrip, RGB8SmartLedsWrite, STRIP_LENGTH};
+use osc_receiver::preview::{Preview, DEFAULT_PREVIEW_FPS};
+use osc_receiver::query::{QueryHandler, ReceiverInfo, StripInfo};
+use rosc::OscPacket;
+
+mod terminal;
+mod virtual_strip;
+
+use terminal::PacketStats;
+use virtual_strip::VirtualStrip;
+
+/// Receives OSC color arrays exactly like pi_osc_receiver but displays them in the terminal so
+/// that patterns can be developed without any LED hardware.
+#[derive(Debug, Parser)]
+#[command(name = "virtual_osc_receiver", version)]
+struct Cli {
+    /// UDP port to listen for OSC packets on
+    #[arg(short, long, default_value_t = 8001)]
+    port: u16,
+
+    /// Address to listen for OSC packets on
+    #[arg(short, long, default_value_t = IpAddr::V4(Ipv4Addr::UNSPECIFIED))]
+    address: IpAddr,
+
+    /// Number of LEDs on each virtual strip, eg. `450,144` for two strips
+    #[arg(short = 'l', long = "strip-length", value_delimiter = ',', default_values_t = [STRIP_LENGTH])]
+    strip_lengths: Vec<usize>,
+
+    /// How many times a second the terminal is redrawn
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+
+    /// Serve a live browser preview of the strips on this port
+    #[arg(long, value_name = "PORT")]
+    preview_port: Option<u16>,
+
+    /// The maximum number of frames per second sent to each browser
+    #[arg(long, value_name = "FPS", default_value_t = DEFAULT_PREVIEW_FPS)]
+    preview_fps: u32,
+
+    /// Reply to the sender with `/error <address> <code> <reason>` when a message is rejected
+    #[arg(long)]
+    error_replies: bool,
+}
+
+pub fn main() -> Result<()> {
+    pretty_env_logger::init();
+
+    let cli = Cli::parse();
+    let listen = SocketAddr::new(cli.address, cli.port);
+
+    // Show the local ip address
+    let title = match (cli.address.is_unspecified(), local_ip()) {
+        (true, Ok(ip_address)) => {
+            format!("Listening for OSC packets at {}:{}", ip_address, cli.port)
+        }
+        _ => format!("Listening for OSC packets at {}", listen),
+    };
+
+    let socket =
+        UdpSocket::bind(listen).with_context(|| format!("Unable to bind to {}", listen))?;
+
+    let mut virtual_strips = cli
+        .strip_lengths
+        .iter()
+        .map(|_| VirtualStrip::new())
+        .collect::<Vec<_>>();
+
+    let preview = cli
+        .preview_port
+        .map(|port| Preview::spawn(SocketAddr::new(cli.address, port), cli.preview_fps))
+        .transpose()?;
+
+    let stats = Arc::new(PacketStats::default());
+
+    terminal::spawn_renderer(
+        title,
+        virtual_strips
+            .iter()
+            .map(|virtual_strip| Arc::clone(&virtual_strip.state))
+            .collect(),
+        Arc::clone(&stats),
+        cli.fps,
+    );
+
+    let mut led_strips = virtual_strips
+        .iter_mut()
+        .zip(cli.strip_lengths.iter())
+        .map(|(virtual_strip, length)| {
+            LedStrip::with_length(virtual_strip as &mut dyn RGB8SmartLedsWrite, *length)
+        })
+        .collect::<Vec<_>>();
+
+    // Draw the strips before the first packet arrives
+    for led_strip in led_strips.iter_mut() {
+        led_strip.write()?;
+    }
+
+    let started_at = Instant::now();
+    let now_ms = || started_at.elapsed().as_millis() as u64;
+
+    let info = ReceiverInfo {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        strips: cli
+            .strip_lengths
+            .iter()
+            .map(|length| StripInfo::new(*length, "virtual"))
+            .collect(),
+    };
+    let mut queries = QueryHandler::new(info, now_ms());
+
+    info!("Starting main loop");
+
+    let mut packet_buf = [0; 65_507];
+
+    loop {
+        let (packet_size, sender) = socket
+            .recv_from(&mut packet_buf)
+            .context("Unable to receive OSC packet")?;
+
+        stats.received.fetch_add(1, Ordering::Relaxed);
+
+        let osc_packet = rosc::decoder::decode_udp(&packet_buf[..packet_size]);
+
+        if let Ok((&[], osc_packet)) = osc_packet {
+            if let Some(reply) = queries.reply(&osc_packet, now_ms()) {
+                send_reply(&socket, &reply, sender);
+                continue;
+            }
+
+            let mut errors = Vec::new();
+            match LedStrip::update_with_errors(&mut led_strips, osc_packet, &mut errors) {
+                Ok(()) => queries.status.frame_displayed(now_ms()),
+                Err(err) => {
+                    queries.status.write_errors += 1;
+                    error!("Unable to draw the strips: {:?}", err);
+                }
+            }
+
+            if let Some(preview) = &preview {
+                preview.publish(&led_strips);
+            }
+
+            if cli.error_replies {
+                for error in errors {
+                    send_reply(&socket, &error.reply(), sender);
+                }
+            }
+        } else {
+            stats.invalid.fetch_add(1, Ordering::Relaxed);
+            queries.status.invalid_packets += 1;
+
+            if cli.error_replies {
+                let reason = match osc_packet {
+                    Ok(_) => "Unexpected bytes after the OSC packet".to_string(),
+                    Err(err) => format!("Invalid OSC packet: {:?}", err),
+                };
+                let error = MessageError::new("", ErrorCode::InvalidPacket, reason);
+                send_reply(&socket, &error.reply(), sender);
+            }
+        }
+    }
+}
+
+fn send_reply(socket: &UdpSocket, reply: &OscPacket, to: SocketAddr) {
+    let sent = rosc::encoder::encode(reply)
+        .map_err(|err| anyhow!("{:?}", err))
+        .and_then(|bytes| Ok(socket.send_to(&bytes, to)?));
+
+    if let Err(err) = sent {
+        warn!("Unable to reply to {}: {:?}", to, err);
+    }
+}
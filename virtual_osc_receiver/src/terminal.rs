@@ -0,0 +1,187 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use crossterm::{
+    cursor, queue,
+    style::{Color, Print, ResetColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType},
+};
+use smart_leds::RGB8;
+
+use crate::virtual_strip::VirtualStripState;
+
+// Every LED is drawn as a full block
+const LED: char = '█';
+
+/// Packet counters updated by the receive loop
+#[derive(Debug, Default)]
+pub struct PacketStats {
+    pub received: AtomicU64,
+    pub invalid: AtomicU64,
+}
+
+/// Renders the title and one row of truecolor blocks per virtual strip followed by frame rate and packet
+/// statistics, redrawing the terminal `fps` times a second.
+pub fn spawn_renderer(
+    title: String,
+    strips: Vec<Arc<Mutex<VirtualStripState>>>,
+    stats: Arc<PacketStats>,
+    fps: u32,
+) -> thread::JoinHandle<()> {
+    let frame_duration = Duration::from_secs(1) / fps.max(1);
+
+    thread::spawn(move || {
+        let mut rates = Rates::new();
+        let mut stdout = io::stdout();
+
+        let _ = queue!(stdout, Clear(ClearType::All));
+
+        loop {
+            let started_at = Instant::now();
+
+            if let Err(err) = render(&mut stdout, &title, &strips, &stats, &mut rates) {
+                log::error!("Unable to render virtual strips: {:?}", err);
+            }
+
+            thread::sleep(frame_duration.saturating_sub(started_at.elapsed()));
+        }
+    })
+}
+
+/// Frames and packets per second, averaged over roughly one second.
+struct Rates {
+    sampled_at: Instant,
+    frame_count: u64,
+    packet_count: u64,
+    fps: f64,
+    packets_per_second: f64,
+}
+
+impl Rates {
+    fn new() -> Self {
+        Self {
+            sampled_at: Instant::now(),
+            frame_count: 0,
+            packet_count: 0,
+            fps: 0.0,
+            packets_per_second: 0.0,
+        }
+    }
+
+    fn update(&mut self, frame_count: u64, packet_count: u64) {
+        let elapsed = self.sampled_at.elapsed().as_secs_f64();
+
+        if elapsed >= 1.0 {
+            self.fps = (frame_count - self.frame_count) as f64 / elapsed;
+            self.packets_per_second = (packet_count - self.packet_count) as f64 / elapsed;
+
+            self.sampled_at = Instant::now();
+            self.frame_count = frame_count;
+            self.packet_count = packet_count;
+        }
+    }
+}
+
+fn render(
+    stdout: &mut io::Stdout,
+    title: &str,
+    strips: &[Arc<Mutex<VirtualStripState>>],
+    stats: &PacketStats,
+    rates: &mut Rates,
+) -> Result<()> {
+    let (width, _) = terminal::size().unwrap_or((80, 24));
+
+    queue!(
+        stdout,
+        cursor::MoveTo(0, 0),
+        Print(title),
+        Clear(ClearType::UntilNewLine),
+        Print("\r\n\r\n")
+    )?;
+
+    let mut frame_count = 0;
+
+    for (i, strip) in strips.iter().enumerate() {
+        let (frame, strip_frame_count) = {
+            let state = strip.lock().unwrap();
+            (state.frame.clone(), state.frame_count)
+        };
+        frame_count = frame_count.max(strip_frame_count);
+
+        let label = format!("{:>2} ", i);
+        let columns = (width as usize).saturating_sub(label.len());
+
+        queue!(stdout, Print(label))?;
+        for color in downsample(&frame, columns) {
+            queue!(
+                stdout,
+                SetForegroundColor(Color::Rgb {
+                    r: color.r,
+                    g: color.g,
+                    b: color.b,
+                }),
+                Print(LED)
+            )?;
+        }
+        queue!(
+            stdout,
+            ResetColor,
+            Clear(ClearType::UntilNewLine),
+            Print("\r\n")
+        )?;
+    }
+
+    let packet_count = stats.received.load(Ordering::Relaxed);
+    rates.update(frame_count, packet_count);
+
+    queue!(
+        stdout,
+        Print(format!(
+            "\r\nFrames: {} ({:.1} fps)  Packets: {} ({:.1}/s)  Invalid packets: {}",
+            frame_count,
+            rates.fps,
+            packet_count,
+            rates.packets_per_second,
+            stats.invalid.load(Ordering::Relaxed),
+        )),
+        Clear(ClearType::UntilNewLine),
+    )?;
+
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Averages the LEDs of a strip down to at most `columns` colors so that long strips fit on one row.
+fn downsample(frame: &[RGB8], columns: usize) -> Vec<RGB8> {
+    if frame.len() <= columns || columns == 0 {
+        return frame.to_vec();
+    }
+
+    (0..columns)
+        .map(|column| {
+            let start = column * frame.len() / columns;
+            let end = ((column + 1) * frame.len() / columns).max(start + 1);
+            let leds = &frame[start..end];
+
+            let sum = leds.iter().fold([0u32; 3], |sum, led| {
+                [
+                    sum[0] + led.r as u32,
+                    sum[1] + led.g as u32,
+                    sum[2] + led.b as u32,
+                ]
+            });
+            let n = leds.len() as u32;
+
+            RGB8 {
+                r: (sum[0] / n) as u8,
+                g: (sum[1] / n) as u8,
+                b: (sum[2] / n) as u8,
+            }
+        })
+        .collect()
+}
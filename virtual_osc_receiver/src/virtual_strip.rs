@@ -0,0 +1,38 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use smart_leds::RGB8;
+
+use osc_receiver::led_strip::RGB8SmartLedsWrite;
+
+/// The most recent frame written to a virtual strip along with the number of frames written to it.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualStripState {
+    pub frame: Vec<RGB8>,
+    pub frame_count: u64,
+}
+
+/// An `RGB8SmartLedsWrite` backend that stores each frame for the terminal to render instead of
+/// driving real LEDs.
+#[derive(Debug, Clone, Default)]
+pub struct VirtualStrip {
+    pub state: Arc<Mutex<VirtualStripState>>,
+}
+
+impl VirtualStrip {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RGB8SmartLedsWrite for VirtualStrip {
+    fn write_rgb8(&mut self, iterator: &mut dyn Iterator<Item = RGB8>) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+
+        state.frame.clear();
+        state.frame.extend(iterator);
+        state.frame_count += 1;
+
+        Ok(())
+    }
+}